@@ -0,0 +1,49 @@
+//! Backdrops drawn before the SVG is rasterized on top.
+
+use tiny_skia::{Color, Pixmap, PremultipliedColorU8};
+
+const CELL_SIZE: usize = 8;
+const LIGHT: u8 = 0xff;
+const DARK: u8 = 0xcc;
+
+/// Covers `pixmap` with the light/dark grid image editors use to show transparency.
+pub fn draw_checkerboard(pixmap: &mut Pixmap) {
+    let width = pixmap.width() as usize;
+    let light = PremultipliedColorU8::from_rgba(LIGHT, LIGHT, LIGHT, 0xff).unwrap();
+    let dark = PremultipliedColorU8::from_rgba(DARK, DARK, DARK, 0xff).unwrap();
+    for (i, pixel) in pixmap.pixels_mut().iter_mut().enumerate() {
+        let (x, y) = (i % width, i / width);
+        *pixel = if (x / CELL_SIZE + y / CELL_SIZE).is_multiple_of(2) {
+            light
+        } else {
+            dark
+        };
+    }
+}
+
+/// Parses a CSS color such as `gray`, `#rrggbb` or `transparent`.
+pub fn parse_color(value: &str) -> Option<Color> {
+    let color: svgtypes::Color = value.trim().parse().ok()?;
+    Some(Color::from_rgba8(
+        color.red,
+        color.green,
+        color.blue,
+        color.alpha,
+    ))
+}
+
+/// Returns the backdrop after `(color, checkerboard)` in the white, black, gray,
+/// checkerboard, transparent cycle.
+pub fn next(color: Color, checkerboard: bool) -> (Color, bool) {
+    let cycle = [
+        (Color::WHITE, false),
+        (Color::BLACK, false),
+        (Color::from_rgba8(0x80, 0x80, 0x80, 0xff), false),
+        (Color::WHITE, true),
+        (Color::TRANSPARENT, false),
+    ];
+    let current = cycle
+        .iter()
+        .position(|&backdrop| backdrop == (color, checkerboard));
+    cycle[current.map_or(0, |i| (i + 1) % cycle.len())]
+}
@@ -0,0 +1,153 @@
+//! Reading and parsing SVGs, from files or from memory.
+
+use crate::error::Failure;
+use crate::fonts::{self, Fonts};
+use anyhow::{Context, Result};
+use std::io::Read;
+use std::path::{Path, PathBuf};
+use std::time::{Duration, Instant};
+use usvg::{Options, Tree};
+
+/// A parsed SVG along with its markup and the options it was parsed with, so that it can
+/// be parsed again, e.g. with other fonts or after changing the markup.
+pub struct Document {
+    /// The file it was read from, if any; what to watch for changes.
+    pub path: Option<PathBuf>,
+    /// The markup, inflated if it was gzip compressed.
+    pub source: Vec<u8>,
+    pub tree: Tree,
+    pub options: Options,
+    /// Whether `options` has the fonts, which are only loaded for documents with text.
+    pub fonts_loaded: bool,
+    pub font_time: Duration,
+    pub parse_time: Duration,
+}
+
+impl Document {
+    pub fn from_file(path: &Path, fonts: &Fonts) -> Result<Self> {
+        let data = std::fs::read(path)
+            .with_context(|| Failure::Input(format!("could not read {}", path.display())))?;
+        let name = path.display().to_string();
+        Self::parse(data, Some(path.to_path_buf()), &name, fonts)
+    }
+
+    /// An SVG that only lives in memory, so there is nothing to watch; `name` says where
+    /// it came from in error messages.
+    pub fn from_data(data: Vec<u8>, name: &str, fonts: &Fonts) -> Result<Self> {
+        Self::parse(data, None, name, fonts)
+    }
+
+    /// Replaces the markup with `data`, e.g. a new version of the file, keeping the
+    /// document as it is if `data` is not a valid SVG.
+    pub fn set_source(&mut self, data: Vec<u8>, fonts: &Fonts) -> Result<()> {
+        let name = self.name();
+        let invalid = || Failure::Parse(format!("{} is not a valid SVG", name));
+        let source = decompress(data).with_context(invalid)?;
+        if !self.fonts_loaded && fonts::has_text(&source) {
+            fonts::load(&mut self.options.fontdb, fonts);
+            self.fonts_loaded = true;
+        }
+        self.tree = Tree::from_data(&source, &self.options.to_ref()).with_context(invalid)?;
+        self.source = source;
+        Ok(())
+    }
+
+    // what to call the document in error messages
+    fn name(&self) -> String {
+        match &self.path {
+            Some(path) => path.display().to_string(),
+            None => "the SVG".into(),
+        }
+    }
+
+    fn parse(data: Vec<u8>, path: Option<PathBuf>, name: &str, fonts: &Fonts) -> Result<Self> {
+        let invalid = || Failure::Parse(format!("{} is not a valid SVG", name));
+        let source = decompress(data).with_context(invalid)?;
+        let mut options = Options {
+            resources_dir: path
+                .as_deref()
+                .and_then(Path::parent)
+                .map(Path::to_path_buf),
+            ..Default::default()
+        };
+        let start = Instant::now();
+        // fonts are only needed for text
+        let fonts_loaded = fonts::has_text(&source);
+        if fonts_loaded {
+            fonts::load(&mut options.fontdb, fonts);
+        }
+        if let Some(family) = &fonts.sans_serif_family {
+            options.fontdb.set_sans_serif_family(family);
+        }
+        if let Some(family) = &fonts.serif_family {
+            options.fontdb.set_serif_family(family);
+        }
+        if let Some(family) = &fonts.monospace_family {
+            options.fontdb.set_monospace_family(family);
+        }
+        if let Some(family) = &fonts.default_font {
+            options.font_family = family.clone();
+        }
+        if let Some(dpi) = fonts.dpi {
+            options.dpi = dpi;
+        }
+        let font_time = start.elapsed();
+        let start = Instant::now();
+        let tree = Tree::from_data(&source, &options.to_ref()).with_context(invalid)?;
+        let parse_time = start.elapsed();
+        Ok(Self {
+            path,
+            source,
+            tree,
+            options,
+            fonts_loaded,
+            font_time,
+            parse_time,
+        })
+    }
+}
+
+pub fn is_svg(path: &Path) -> bool {
+    path.extension()
+        .and_then(|ext| ext.to_str())
+        .map(|ext| ext.eq_ignore_ascii_case("svg") || ext.eq_ignore_ascii_case("svgz"))
+        .unwrap_or(false)
+}
+
+/// The `*.svg`/`*.svgz` files directly inside `dir`, in alphabetical order.
+pub fn svgs_in(dir: &Path) -> Vec<PathBuf> {
+    let mut paths: Vec<PathBuf> = std::fs::read_dir(dir)
+        .into_iter()
+        .flatten()
+        .filter_map(|entry| entry.ok())
+        .map(|entry| entry.path())
+        .filter(|path| is_svg(path))
+        .collect();
+    paths.sort();
+    paths
+}
+
+/// The most recently modified `*.svg`/`*.svgz` file directly inside `dir`.
+pub fn newest_svg(dir: &Path) -> Option<PathBuf> {
+    svgs_in(dir)
+        .into_iter()
+        .filter_map(|path| Some((path.metadata().ok()?.modified().ok()?, path)))
+        .max()
+        .map(|(_, path)| path)
+}
+
+/// The file's SVG markup, inflated if it is gzip compressed (`.svgz`).
+pub fn read_svg(path: &Path) -> std::io::Result<Vec<u8>> {
+    decompress(std::fs::read(path)?)
+}
+
+// usvg detects gzip by its magic bytes as well, but the markup is also read directly,
+// e.g. by the definitions overlay
+fn decompress(data: Vec<u8>) -> std::io::Result<Vec<u8>> {
+    if !data.starts_with(&[0x1f, 0x8b]) {
+        return Ok(data);
+    }
+    let mut inflated = Vec::with_capacity(data.len() * 2);
+    flate2::read::GzDecoder::new(&data[..]).read_to_end(&mut inflated)?;
+    Ok(inflated)
+}
@@ -0,0 +1,40 @@
+//! Failures that end svgview, told apart by their exit code so scripts can react.
+
+use std::fmt;
+
+/// Attached as context to an `anyhow::Error` to pick the exit code.
+#[derive(Debug)]
+pub enum Failure {
+    /// The options given do not go together, as with clap's own usage errors.
+    Usage(String),
+    /// An input file or directory does not exist or could not be read.
+    Input(String),
+    /// An input is not a valid SVG.
+    Parse(String),
+    /// Rendering failed, or its result could not be written.
+    Render(String),
+}
+
+impl Failure {
+    pub fn exit_code(&self) -> i32 {
+        match self {
+            Failure::Usage(_) => 2,
+            Failure::Input(_) => 3,
+            Failure::Parse(_) => 4,
+            Failure::Render(_) => 5,
+        }
+    }
+}
+
+impl fmt::Display for Failure {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Failure::Usage(message)
+            | Failure::Input(message)
+            | Failure::Parse(message)
+            | Failure::Render(message) => f.write_str(message),
+        }
+    }
+}
+
+impl std::error::Error for Failure {}
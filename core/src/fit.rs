@@ -0,0 +1,44 @@
+//! How the SVG is sized relative to the window before pan and zoom are applied.
+
+use usvg::{FitTo, ScreenSize};
+
+#[derive(Clone, Copy, PartialEq, Debug, serde::Deserialize)]
+#[cfg_attr(feature = "clap", derive(clap::ValueEnum))]
+#[serde(rename_all = "kebab-case")]
+pub enum FitMode {
+    /// The whole SVG is visible.
+    Fit,
+    /// The SVG covers the whole window.
+    Fill,
+    FitWidth,
+    FitHeight,
+    /// One SVG user unit per logical pixel.
+    ActualSize,
+}
+
+impl FitMode {
+    pub fn next(self) -> Self {
+        match self {
+            FitMode::Fit => FitMode::Fill,
+            FitMode::Fill => FitMode::FitWidth,
+            FitMode::FitWidth => FitMode::FitHeight,
+            FitMode::FitHeight => FitMode::ActualSize,
+            FitMode::ActualSize => FitMode::Fit,
+        }
+    }
+
+    /// The resvg sizing for an SVG of `svg_size` shown in a `width` x `height` area with
+    /// `scale_factor` pixels per logical pixel.
+    pub fn fit_to(self, width: u32, height: u32, svg_size: ScreenSize, scale_factor: f32) -> FitTo {
+        match self {
+            FitMode::Fit => FitTo::Size(width, height),
+            FitMode::Fill => FitTo::Zoom(
+                (width as f32 / svg_size.width() as f32)
+                    .max(height as f32 / svg_size.height() as f32),
+            ),
+            FitMode::FitWidth => FitTo::Width(width),
+            FitMode::FitHeight => FitTo::Height(height),
+            FitMode::ActualSize => FitTo::Zoom(scale_factor),
+        }
+    }
+}
@@ -0,0 +1,258 @@
+//! Loading the fonts for text only once something needs them, from the font files found in
+//! each directory on an earlier run. Those are kept in `svgview/fonts` in the cache
+//! directory along with the modification times of the directories they were found in, so
+//! directories are only scanned again once files were added to or removed from them.
+
+use log::warn;
+use std::collections::BTreeMap;
+use std::path::{Path, PathBuf};
+use std::time::{Duration, UNIX_EPOCH};
+use usvg::fontdb::Database;
+
+const EXTENSIONS: &[&str] = &["ttf", "ttc", "otf", "otc"];
+
+// the directories below a scanned directory and the font files found in them
+#[derive(Default)]
+struct Scan {
+    dirs: Vec<(PathBuf, Duration)>,
+    fonts: Vec<PathBuf>,
+}
+
+impl Scan {
+    fn is_current(&self) -> bool {
+        !self.dirs.is_empty()
+            && self
+                .dirs
+                .iter()
+                .all(|(dir, time)| modified(dir) == Some(*time))
+    }
+
+    // loads the fonts below `dir`, remembering the files that had any
+    fn walk(&mut self, dir: &Path, fontdb: &mut Database) {
+        let (entries, time) = match (std::fs::read_dir(dir), modified(dir)) {
+            (Ok(entries), Some(time)) => (entries, time),
+            _ => return,
+        };
+        self.dirs.push((dir.to_path_buf(), time));
+        for path in entries.flatten().map(|entry| entry.path()) {
+            if path.is_dir() {
+                self.walk(&path, fontdb);
+            } else if is_font(&path) {
+                let faces = fontdb.len();
+                match fontdb.load_font_file(&path) {
+                    Ok(()) if fontdb.len() > faces => self.fonts.push(path),
+                    Ok(()) => {}
+                    Err(e) => warn!("Could not load {}: {}", path.display(), e),
+                }
+            }
+        }
+    }
+}
+
+/// Where fonts for the SVG's text are loaded from, and how its text and lengths are sized.
+#[derive(Clone, Default)]
+#[cfg_attr(feature = "clap", derive(clap::Args))]
+pub struct Fonts {
+    /// Load fonts from this directory too; can be given more than once
+    #[cfg_attr(
+        feature = "clap",
+        arg(
+            long = "font-dir",
+            value_name = "DIR",
+            global = true,
+            help_heading = "Rendering"
+        )
+    )]
+    pub dirs: Vec<PathBuf>,
+
+    /// Do not load the system's fonts, only those of --font-dir
+    #[cfg_attr(
+        feature = "clap",
+        arg(long = "skip-system-fonts", global = true, help_heading = "Rendering")
+    )]
+    pub skip_system: bool,
+
+    /// Font for the generic `sans-serif` family [default: Arial]
+    #[cfg_attr(
+        feature = "clap",
+        arg(long, value_name = "FAMILY", global = true, help_heading = "Rendering")
+    )]
+    pub sans_serif_family: Option<String>,
+
+    /// Font for the generic `serif` family [default: Times New Roman]
+    #[cfg_attr(
+        feature = "clap",
+        arg(long, value_name = "FAMILY", global = true, help_heading = "Rendering")
+    )]
+    pub serif_family: Option<String>,
+
+    /// Font for the generic `monospace` family [default: Courier New]
+    #[cfg_attr(
+        feature = "clap",
+        arg(long, value_name = "FAMILY", global = true, help_heading = "Rendering")
+    )]
+    pub monospace_family: Option<String>,
+
+    /// Font for text without a `font-family` [default: Times New Roman]
+    #[cfg_attr(
+        feature = "clap",
+        arg(
+            long,
+            value_name = "FAMILY",
+            env = "SVGVIEW_FONT_FAMILY",
+            global = true,
+            help_heading = "Rendering"
+        )
+    )]
+    pub default_font: Option<String>,
+
+    /// Pixels per inch, for lengths in physical units such as `mm` and `pt` [default: 96]
+    #[cfg_attr(
+        feature = "clap",
+        arg(long, env = "SVGVIEW_DPI", global = true, help_heading = "Rendering")
+    )]
+    pub dpi: Option<f64>,
+}
+
+/// Whether `source` has any text to lay out, so that fonts are needed to render it. A
+/// source that cannot be read as XML is assumed to have some.
+pub fn has_text(source: &[u8]) -> bool {
+    let text = match std::str::from_utf8(source) {
+        Ok(text) => text,
+        Err(_) => return true,
+    };
+    let opts = roxmltree::ParsingOptions { allow_dtd: true };
+    match roxmltree::Document::parse_with_options(text, opts) {
+        Ok(doc) => doc.descendants().any(|node| node.has_tag_name("text")),
+        Err(_) => true,
+    }
+}
+
+/// Loads the system fonts, unless skipped, and those of the font directories into `fontdb`.
+pub fn load(fontdb: &mut Database, fonts: &Fonts) {
+    let mut dirs = if fonts.skip_system {
+        vec![]
+    } else {
+        system_dirs()
+    };
+    dirs.extend(fonts.dirs.iter().cloned());
+
+    let mut cache = read_cache();
+    let mut scanned = false;
+    for dir in dirs {
+        let dir = std::fs::canonicalize(&dir).unwrap_or(dir);
+        match cache.get(&dir) {
+            Some(scan) if scan.is_current() => {
+                for font in &scan.fonts {
+                    if let Err(e) = fontdb.load_font_file(font) {
+                        warn!("Could not load {}: {}", font.display(), e);
+                    }
+                }
+            }
+            _ => {
+                let mut scan = Scan::default();
+                scan.walk(&dir, fontdb);
+                // a directory that does not exist is looked for again next time
+                scanned |= !scan.dirs.is_empty();
+                cache.insert(dir, scan);
+            }
+        }
+    }
+    if scanned {
+        write_cache(&cache);
+    }
+}
+
+// the directories fontdb looks for system fonts in
+fn system_dirs() -> Vec<PathBuf> {
+    let mut dirs: Vec<PathBuf> = vec![];
+    let home = std::env::var_os("HOME").map(PathBuf::from);
+    if cfg!(target_os = "windows") {
+        dirs.push("C:\\Windows\\Fonts\\".into());
+    } else if cfg!(target_os = "macos") {
+        dirs.push("/Library/Fonts".into());
+        dirs.push("/System/Library/Fonts".into());
+        dirs.push("/System/Library/AssetsV2/com_apple_MobileAsset_Font6".into());
+        dirs.push("/Network/Library/Fonts".into());
+        dirs.extend(home.map(|home| home.join("Library/Fonts")));
+    } else if cfg!(unix) {
+        dirs.push("/usr/share/fonts/".into());
+        dirs.push("/usr/local/share/fonts/".into());
+        if let Some(home) = home {
+            dirs.push(home.join(".fonts"));
+            dirs.push(home.join(".local/share/fonts"));
+        }
+    }
+    dirs
+}
+
+fn is_font(path: &Path) -> bool {
+    let extension = path
+        .extension()
+        .and_then(|e| e.to_str())
+        .unwrap_or_default();
+    EXTENSIONS
+        .iter()
+        .any(|font| extension.eq_ignore_ascii_case(font))
+}
+
+fn modified(path: &Path) -> Option<Duration> {
+    let time = std::fs::metadata(path).ok()?.modified().ok()?;
+    time.duration_since(UNIX_EPOCH).ok()
+}
+
+// one `root <dir>` line for each scanned directory, followed by a `dir <nanoseconds> <dir>`
+// line for it and each directory below it, and a `font <file>` line for each font file
+fn read_cache() -> BTreeMap<PathBuf, Scan> {
+    let text = match crate::cache_path("fonts").map(std::fs::read_to_string) {
+        Some(Ok(text)) => text,
+        _ => return BTreeMap::new(),
+    };
+    let mut scans: Vec<(PathBuf, Scan)> = vec![];
+    for line in text.lines() {
+        let (kind, rest) = line.split_once(' ').unwrap_or_default();
+        if kind == "root" {
+            scans.push((PathBuf::from(rest), Scan::default()));
+        }
+        let scan = match scans.last_mut() {
+            Some((_, scan)) => scan,
+            None => continue,
+        };
+        match kind {
+            "dir" => {
+                let dir = rest.split_once(' ').and_then(|(nanos, dir)| {
+                    let nanos: u64 = nanos.parse().ok()?;
+                    Some((PathBuf::from(dir), Duration::from_nanos(nanos)))
+                });
+                scan.dirs.extend(dir);
+            }
+            "font" => scan.fonts.push(PathBuf::from(rest)),
+            _ => {}
+        }
+    }
+    scans.into_iter().collect()
+}
+
+fn write_cache(cache: &BTreeMap<PathBuf, Scan>) {
+    let path = match crate::cache_path("fonts") {
+        Some(path) => path,
+        None => return,
+    };
+    let mut text = String::new();
+    for (root, scan) in cache {
+        text += &format!("root {}\n", root.to_string_lossy());
+        for (dir, time) in &scan.dirs {
+            text += &format!("dir {} {}\n", time.as_nanos(), dir.to_string_lossy());
+        }
+        for font in &scan.fonts {
+            text += &format!("font {}\n", font.to_string_lossy());
+        }
+    }
+    let written = path
+        .parent()
+        .map_or(Ok(()), std::fs::create_dir_all)
+        .and_then(|_| std::fs::write(&path, text));
+    if let Err(e) = written {
+        warn!("Could not remember the font files: {}", e);
+    }
+}
@@ -0,0 +1,800 @@
+//! Rendering on the GPU for `Backend::Gpu`. Paths are flattened into triangles on the CPU
+//! and filled on the GPU by marking their pixels in the stencil buffer, then covering them
+//! with their color, multisampled for antialiasing. The result is read back into a pixmap,
+//! so the overlays are drawn over it as over any other render.
+//!
+//! Only solid colors are drawn this way. Documents with gradients, patterns, images, clip
+//! paths, masks, filters or group opacity are left to resvg.
+
+use anyhow::{anyhow, Context, Result};
+use std::f64::consts::PI;
+use std::num::NonZeroU32;
+use std::ops::Range;
+use tiny_skia::Pixmap;
+use usvg::{NodeKind, Paint, PathSegment, Transform, Tree, Visibility};
+use wgpu::util::DeviceExt;
+
+const SAMPLES: u32 = 4;
+const COLOR_FORMAT: wgpu::TextureFormat = wgpu::TextureFormat::Rgba8Unorm;
+const STENCIL_FORMAT: wgpu::TextureFormat = wgpu::TextureFormat::Depth24PlusStencil8;
+
+// how far flattened curves may be off, in pixels
+const TOLERANCE: f64 = 0.2;
+
+const SHADER: &str = "
+struct Vertex {
+    [[builtin(position)]] position: vec4<f32>;
+    [[location(0)]] color: vec4<f32>;
+};
+
+[[stage(vertex)]]
+fn vertex([[location(0)]] position: vec2<f32>, [[location(1)]] color: vec4<f32>) -> Vertex {
+    var out: Vertex;
+    out.position = vec4<f32>(position, 0.0, 1.0);
+    out.color = color;
+    return out;
+}
+
+[[stage(fragment)]]
+fn fragment(in: Vertex) -> [[location(0)]] vec4<f32> {
+    return in.color;
+}
+";
+
+/// Whether everything in `tree` can be drawn on the GPU.
+pub fn supports(tree: &Tree) -> bool {
+    supports_children(&tree.root())
+}
+
+fn supports_children(node: &usvg::Node) -> bool {
+    node.children().all(|child| match &*child.borrow() {
+        NodeKind::Group(group) => {
+            group.filter.is_empty()
+                && group.mask.is_none()
+                && group.clip_path.is_none()
+                && group.opacity.value() == 1.0
+                && supports_children(&child)
+        }
+        NodeKind::Path(path) => {
+            let fill = path.fill.iter().map(|fill| &fill.paint);
+            let stroke = path.stroke.iter().map(|stroke| &stroke.paint);
+            fill.chain(stroke)
+                .all(|paint| matches!(paint, Paint::Color(_)))
+        }
+        NodeKind::Image(_) => false,
+        // definitions are only drawn by what refers to them
+        _ => true,
+    })
+}
+
+// how the stencil buffer counts the triangles of a shape
+#[derive(Clone, Copy)]
+enum Rule {
+    NonZero,
+    EvenOdd,
+    // any pixel covered at least once, for strokes
+    Union,
+}
+
+// a fill or stroke: the triangles marking its pixels in the stencil buffer, and the two
+// triangles of its bounding box painting the marked pixels
+struct Shape {
+    rule: Rule,
+    stencil: Range<u32>,
+    cover: Range<u32>,
+}
+
+// the position of a vertex in normalized device coordinates and its premultiplied color
+type Vertex = [f32; 6];
+
+type Point = (f64, f64);
+
+struct Mesh {
+    vertices: Vec<Vertex>,
+    shapes: Vec<Shape>,
+    width: f64,
+    height: f64,
+}
+
+impl Mesh {
+    // `ts` maps the user space of `node` to pixels
+    fn children(&mut self, node: &usvg::Node, ts: Transform) {
+        for child in node.children() {
+            match &*child.borrow() {
+                NodeKind::Group(group) => {
+                    let mut ts = ts;
+                    ts.append(&group.transform);
+                    self.children(&child, ts);
+                }
+                NodeKind::Path(path) => self.path(path, ts),
+                _ => {}
+            }
+        }
+    }
+
+    fn path(&mut self, path: &usvg::Path, mut ts: Transform) {
+        if path.visibility != Visibility::Visible {
+            return;
+        }
+        ts.append(&path.transform);
+        let scale = (ts.a * ts.d - ts.b * ts.c).abs().sqrt();
+        if !scale.is_normal() {
+            return;
+        }
+        let contours = flatten(&path.data, TOLERANCE / scale);
+        let to_pixels = |points: &mut Vec<Point>| {
+            for point in points.iter_mut() {
+                *point = ts.apply(point.0, point.1);
+            }
+        };
+
+        if let Some(fill) = &path.fill {
+            if let Paint::Color(color) = fill.paint {
+                let mut triangles = vec![];
+                for contour in &contours {
+                    let mut points = contour.points.clone();
+                    to_pixels(&mut points);
+                    fan(&points, &mut triangles);
+                }
+                let rule = match fill.rule {
+                    usvg::FillRule::NonZero => Rule::NonZero,
+                    usvg::FillRule::EvenOdd => Rule::EvenOdd,
+                };
+                self.push(rule, &triangles, premultiply(color, fill.opacity.value()));
+            }
+        }
+        if let Some(stroke) = &path.stroke {
+            if let Paint::Color(color) = stroke.paint {
+                let mut triangles = vec![];
+                outline(&contours, stroke, scale, &mut triangles);
+                to_pixels(&mut triangles);
+                self.push(
+                    Rule::Union,
+                    &triangles,
+                    premultiply(color, stroke.opacity.value()),
+                );
+            }
+        }
+    }
+
+    // adds a shape of `triangles`, in pixels
+    fn push(&mut self, rule: Rule, triangles: &[Point], color: [f32; 4]) {
+        if triangles.is_empty() {
+            return;
+        }
+        let (mut left, mut top) = (f64::INFINITY, f64::INFINITY);
+        let (mut right, mut bottom) = (f64::NEG_INFINITY, f64::NEG_INFINITY);
+        for &(x, y) in triangles {
+            left = left.min(x);
+            top = top.min(y);
+            right = right.max(x);
+            bottom = bottom.max(y);
+        }
+        let start = self.vertices.len() as u32;
+        for &point in triangles {
+            self.vertices.push(self.vertex(point, [0.0; 4]));
+        }
+        let middle = self.vertices.len() as u32;
+        for point in [
+            (left, top),
+            (right, top),
+            (right, bottom),
+            (left, top),
+            (right, bottom),
+            (left, bottom),
+        ] {
+            self.vertices.push(self.vertex(point, color));
+        }
+        self.shapes.push(Shape {
+            rule,
+            stencil: start..middle,
+            cover: middle..self.vertices.len() as u32,
+        });
+    }
+
+    fn vertex(&self, (x, y): Point, [r, g, b, a]: [f32; 4]) -> Vertex {
+        let x = x / self.width * 2.0 - 1.0;
+        let y = 1.0 - y / self.height * 2.0;
+        [x as f32, y as f32, r, g, b, a]
+    }
+}
+
+fn premultiply(color: usvg::Color, opacity: f64) -> [f32; 4] {
+    let alpha = opacity as f32;
+    let channel = |value: u8| value as f32 / 255.0 * alpha;
+    [
+        channel(color.red),
+        channel(color.green),
+        channel(color.blue),
+        alpha,
+    ]
+}
+
+// a subpath as a polygon or polyline
+struct Contour {
+    points: Vec<Point>,
+    closed: bool,
+}
+
+// the subpaths of `data` with their curves split into lines no more than `tolerance` off
+fn flatten(data: &usvg::PathData, tolerance: f64) -> Vec<Contour> {
+    let mut contours = vec![];
+    let mut current = Contour {
+        points: vec![],
+        closed: false,
+    };
+    let push = |points: &mut Vec<Point>, point: Point| {
+        if points.last() != Some(&point) {
+            points.push(point);
+        }
+    };
+    for segment in data.iter() {
+        match *segment {
+            PathSegment::MoveTo { x, y } => {
+                let done = std::mem::replace(&mut current.points, vec![(x, y)]);
+                if done.len() > 1 {
+                    contours.push(Contour {
+                        points: done,
+                        closed: false,
+                    });
+                }
+            }
+            PathSegment::LineTo { x, y } => push(&mut current.points, (x, y)),
+            PathSegment::CurveTo {
+                x1,
+                y1,
+                x2,
+                y2,
+                x,
+                y,
+            } => {
+                let (x0, y0) = current.points.last().copied().unwrap_or((x1, y1));
+                // the second differences bound how far evenly split pieces are off
+                let dd = |a: f64, b: f64, c: f64| a - 2.0 * b + c;
+                let dd1 = dd(x0, x1, x2).hypot(dd(y0, y1, y2));
+                let dd2 = dd(x1, x2, x).hypot(dd(y1, y2, y));
+                let pieces = (0.75 * dd1.max(dd2) / tolerance).sqrt().ceil();
+                let pieces = pieces.clamp(1.0, 1000.0) as usize;
+                for i in 1..=pieces {
+                    let t = i as f64 / pieces as f64;
+                    let u = 1.0 - t;
+                    let (a, b, c, d) = (u * u * u, 3.0 * u * u * t, 3.0 * u * t * t, t * t * t);
+                    let point = (
+                        a * x0 + b * x1 + c * x2 + d * x,
+                        a * y0 + b * y1 + c * y2 + d * y,
+                    );
+                    push(&mut current.points, point);
+                }
+            }
+            PathSegment::ClosePath => {
+                let start = current.points.first().copied();
+                let mut points = std::mem::take(&mut current.points);
+                if points.len() > 1 && points.first() == points.last() {
+                    points.pop();
+                }
+                if points.len() > 1 {
+                    contours.push(Contour {
+                        points,
+                        closed: true,
+                    });
+                }
+                // a line after closing starts where the subpath did
+                current.points.extend(start);
+            }
+        }
+    }
+    if current.points.len() > 1 {
+        contours.push(current);
+    }
+    contours
+}
+
+// a triangle fan over `polygon`, which marks the pixels inside by its winding
+fn fan(polygon: &[Point], out: &mut Vec<Point>) {
+    for pair in polygon.windows(2).skip(1) {
+        out.extend([polygon[0], pair[0], pair[1]]);
+    }
+}
+
+// triangles covering the stroke of `contours` in user space, drawn at `scale` pixels per unit
+fn outline(contours: &[Contour], stroke: &usvg::Stroke, scale: f64, out: &mut Vec<Point>) {
+    let half = stroke.width.value() / 2.0;
+    // enough sides for a round join to look round at its size in pixels
+    let sides = ((PI * half * scale).ceil() as usize).clamp(8, 512);
+    let lines = match &stroke.dasharray {
+        Some(dashes) => dash(contours, dashes, stroke.dashoffset as f64),
+        None => contours
+            .iter()
+            .map(|contour| (contour.points.clone(), contour.closed))
+            .collect(),
+    };
+    for (points, closed) in lines {
+        let n = points.len();
+        if n < 2 {
+            continue;
+        }
+        let segments = if closed { n } else { n - 1 };
+        for i in 0..segments {
+            let (a, b) = (points[i], points[(i + 1) % n]);
+            let normal = normal(a, b, half);
+            out.extend([add(a, normal), add(b, normal), sub(b, normal)]);
+            out.extend([add(a, normal), sub(b, normal), sub(a, normal)]);
+        }
+        let corners = if closed { 0..n } else { 1..n - 1 };
+        for i in corners {
+            let (before, at, after) = (points[(i + n - 1) % n], points[i], points[(i + 1) % n]);
+            join(before, at, after, stroke, half, sides, out);
+        }
+        if !closed {
+            cap(points[1], points[0], stroke.linecap, half, sides, out);
+            cap(
+                points[n - 2],
+                points[n - 1],
+                stroke.linecap,
+                half,
+                sides,
+                out,
+            );
+        }
+    }
+}
+
+// the pieces of `contours` that the dash pattern draws, as polylines
+fn dash(contours: &[Contour], dashes: &[f64], offset: f64) -> Vec<(Vec<Point>, bool)> {
+    let mut pattern = dashes.to_vec();
+    if pattern.len() % 2 == 1 {
+        pattern.extend_from_slice(dashes);
+    }
+    let total: f64 = pattern.iter().sum();
+    if total <= 0.0 || total.is_nan() || pattern.iter().any(|dash| *dash < 0.0) {
+        let all = contours.iter();
+        return all
+            .map(|contour| (contour.points.clone(), contour.closed))
+            .collect();
+    }
+    let mut pieces = vec![];
+    for contour in contours {
+        // each subpath starts the pattern again
+        let (mut index, mut left) = (0, pattern[0]);
+        let mut skip = offset.rem_euclid(total);
+        while skip > 0.0 {
+            if skip < left {
+                left -= skip;
+                break;
+            }
+            skip -= left;
+            index = (index + 1) % pattern.len();
+            left = pattern[index];
+        }
+        let mut points = contour.points.clone();
+        if contour.closed {
+            points.push(points[0]);
+        }
+        let mut piece = if index % 2 == 0 {
+            vec![points[0]]
+        } else {
+            vec![]
+        };
+        for pair in points.windows(2) {
+            let (a, b) = (pair[0], pair[1]);
+            let length = (b.0 - a.0).hypot(b.1 - a.1);
+            let mut along = 0.0;
+            while length - along > left {
+                along += left;
+                let t = along / length;
+                let point = (a.0 + (b.0 - a.0) * t, a.1 + (b.1 - a.1) * t);
+                if index % 2 == 0 {
+                    piece.push(point);
+                    pieces.push((std::mem::take(&mut piece), false));
+                } else {
+                    piece = vec![point];
+                }
+                index = (index + 1) % pattern.len();
+                left = pattern[index];
+            }
+            left -= length - along;
+            if index % 2 == 0 {
+                piece.push(b);
+            }
+        }
+        if index % 2 == 0 {
+            pieces.push((piece, false));
+        }
+    }
+    pieces
+}
+
+fn join(
+    before: Point,
+    at: Point,
+    after: Point,
+    stroke: &usvg::Stroke,
+    half: f64,
+    sides: usize,
+    out: &mut Vec<Point>,
+) {
+    let (n1, n2) = (normal(before, at, half), normal(at, after, half));
+    match stroke.linejoin {
+        usvg::LineJoin::Round => circle(at, half, sides, out),
+        usvg::LineJoin::Bevel => bevel(at, n1, n2, out),
+        usvg::LineJoin::Miter => {
+            bevel(at, n1, n2, out);
+            // the normals' mean is shorter the sharper the corner is
+            let mean = ((n1.0 + n2.0) / 2.0, (n1.1 + n2.1) / 2.0);
+            let length = mean.0.hypot(mean.1);
+            if length == 0.0 || half / length > stroke.miterlimit.value() {
+                return;
+            }
+            // the tip is on the outside of the turn
+            let turn = sub(direction(before, at), direction(at, after));
+            let side = if mean.0 * turn.0 + mean.1 * turn.1 >= 0.0 {
+                1.0
+            } else {
+                -1.0
+            };
+            let reach = side * half * half / (length * length);
+            let tip = (at.0 + mean.0 * reach, at.1 + mean.1 * reach);
+            let (a, b) = ((side * n1.0, side * n1.1), (side * n2.0, side * n2.1));
+            out.extend([add(at, a), tip, add(at, b)]);
+        }
+    }
+}
+
+fn bevel(at: Point, n1: Point, n2: Point, out: &mut Vec<Point>) {
+    out.extend([at, add(at, n1), add(at, n2)]);
+    out.extend([at, sub(at, n1), sub(at, n2)]);
+}
+
+// the cap at the end `at` of a line coming from `from`
+fn cap(from: Point, at: Point, cap: usvg::LineCap, half: f64, sides: usize, out: &mut Vec<Point>) {
+    match cap {
+        usvg::LineCap::Butt => {}
+        usvg::LineCap::Round => circle(at, half, sides, out),
+        usvg::LineCap::Square => {
+            let (dx, dy) = direction(from, at);
+            let end = (at.0 + dx * half, at.1 + dy * half);
+            let normal = normal(from, at, half);
+            out.extend([add(at, normal), add(end, normal), sub(end, normal)]);
+            out.extend([add(at, normal), sub(end, normal), sub(at, normal)]);
+        }
+    }
+}
+
+fn circle(center: Point, radius: f64, sides: usize, out: &mut Vec<Point>) {
+    let corner = |i: usize| {
+        let angle = i as f64 / sides as f64 * 2.0 * PI;
+        (
+            center.0 + radius * angle.cos(),
+            center.1 + radius * angle.sin(),
+        )
+    };
+    for i in 0..sides {
+        out.extend([center, corner(i), corner(i + 1)]);
+    }
+}
+
+fn direction(a: Point, b: Point) -> Point {
+    let length = (b.0 - a.0).hypot(b.1 - a.1);
+    ((b.0 - a.0) / length, (b.1 - a.1) / length)
+}
+
+// perpendicular to the line from `a` to `b`, `length` long
+fn normal(a: Point, b: Point, length: f64) -> Point {
+    let (dx, dy) = direction(a, b);
+    (-dy * length, dx * length)
+}
+
+fn add(a: Point, b: Point) -> Point {
+    (a.0 + b.0, a.1 + b.1)
+}
+
+fn sub(a: Point, b: Point) -> Point {
+    (a.0 - b.0, a.1 - b.1)
+}
+
+// the textures of one render size
+struct Targets {
+    width: u32,
+    height: u32,
+    // multisampled, resolved into `color`
+    samples: wgpu::TextureView,
+    color: wgpu::Texture,
+    color_view: wgpu::TextureView,
+    stencil: wgpu::TextureView,
+}
+
+/// A GPU device and the pipelines drawing with it.
+pub struct Gpu {
+    device: wgpu::Device,
+    queue: wgpu::Queue,
+    // marking the pixels of a shape for each `Rule`, and painting them
+    non_zero: wgpu::RenderPipeline,
+    even_odd: wgpu::RenderPipeline,
+    union: wgpu::RenderPipeline,
+    cover: wgpu::RenderPipeline,
+    targets: Option<Targets>,
+}
+
+impl Gpu {
+    pub fn new() -> Result<Self> {
+        let instance = wgpu::Instance::new(wgpu::Backends::PRIMARY);
+        let adapter = pollster::block_on(instance.request_adapter(&wgpu::RequestAdapterOptions {
+            power_preference: wgpu::PowerPreference::HighPerformance,
+            force_fallback_adapter: false,
+            compatible_surface: None,
+        }))
+        .ok_or_else(|| anyhow!("no GPU was found"))?;
+        let descriptor = wgpu::DeviceDescriptor {
+            label: None,
+            features: wgpu::Features::empty(),
+            limits: wgpu::Limits::default(),
+        };
+        let (device, queue) = pollster::block_on(adapter.request_device(&descriptor, None))
+            .context("could not open the GPU")?;
+
+        let shader = device.create_shader_module(&wgpu::ShaderModuleDescriptor {
+            label: None,
+            source: wgpu::ShaderSource::Wgsl(SHADER.into()),
+        });
+        let pipeline = |operation: wgpu::StencilOperation, paint: bool| {
+            let mark = wgpu::StencilFaceState {
+                compare: wgpu::CompareFunction::Always,
+                fail_op: wgpu::StencilOperation::Keep,
+                depth_fail_op: wgpu::StencilOperation::Keep,
+                pass_op: operation,
+            };
+            // painting the marked pixels clears them for the next shape
+            let cover = wgpu::StencilFaceState {
+                compare: wgpu::CompareFunction::NotEqual,
+                fail_op: wgpu::StencilOperation::Keep,
+                depth_fail_op: wgpu::StencilOperation::Keep,
+                pass_op: wgpu::StencilOperation::Zero,
+            };
+            let (front, back) = match operation {
+                wgpu::StencilOperation::IncrementWrap => (
+                    mark,
+                    wgpu::StencilFaceState {
+                        pass_op: wgpu::StencilOperation::DecrementWrap,
+                        ..mark
+                    },
+                ),
+                _ if paint => (cover, cover),
+                _ => (mark, mark),
+            };
+            let target = wgpu::ColorTargetState {
+                format: COLOR_FORMAT,
+                blend: Some(wgpu::BlendState::PREMULTIPLIED_ALPHA_BLENDING),
+                write_mask: if paint {
+                    wgpu::ColorWrites::ALL
+                } else {
+                    wgpu::ColorWrites::empty()
+                },
+            };
+            device.create_render_pipeline(&wgpu::RenderPipelineDescriptor {
+                label: None,
+                layout: None,
+                vertex: wgpu::VertexState {
+                    module: &shader,
+                    entry_point: "vertex",
+                    buffers: &[wgpu::VertexBufferLayout {
+                        array_stride: std::mem::size_of::<Vertex>() as wgpu::BufferAddress,
+                        step_mode: wgpu::VertexStepMode::Vertex,
+                        attributes: &wgpu::vertex_attr_array![0 => Float32x2, 1 => Float32x4],
+                    }],
+                },
+                primitive: wgpu::PrimitiveState::default(),
+                depth_stencil: Some(wgpu::DepthStencilState {
+                    format: STENCIL_FORMAT,
+                    depth_write_enabled: false,
+                    depth_compare: wgpu::CompareFunction::Always,
+                    stencil: wgpu::StencilState {
+                        front,
+                        back,
+                        read_mask: !0,
+                        write_mask: !0,
+                    },
+                    bias: wgpu::DepthBiasState::default(),
+                }),
+                multisample: wgpu::MultisampleState {
+                    count: SAMPLES,
+                    ..Default::default()
+                },
+                fragment: Some(wgpu::FragmentState {
+                    module: &shader,
+                    entry_point: "fragment",
+                    targets: &[target],
+                }),
+                multiview: None,
+            })
+        };
+        Ok(Self {
+            non_zero: pipeline(wgpu::StencilOperation::IncrementWrap, false),
+            even_odd: pipeline(wgpu::StencilOperation::Invert, false),
+            union: pipeline(wgpu::StencilOperation::IncrementClamp, false),
+            cover: pipeline(wgpu::StencilOperation::Keep, true),
+            device,
+            queue,
+            targets: None,
+        })
+    }
+
+    /// Renders `tree` like `resvg::render` into a transparent pixmap of the given size, or
+    /// returns `None` if that is larger than the GPU can draw.
+    pub fn render(
+        &mut self,
+        tree: &Tree,
+        fit_to: usvg::FitTo,
+        transform: tiny_skia::Transform,
+        width: u32,
+        height: u32,
+    ) -> Option<Pixmap> {
+        let max_size = self.device.limits().max_texture_dimension_2d;
+        if width > max_size || height > max_size {
+            return None;
+        }
+        let svg = tree.svg_node();
+        let size = fit_to.fit_to(svg.size.to_screen_size())?;
+        let tiny_skia::Transform {
+            sx,
+            kx,
+            ky,
+            sy,
+            tx,
+            ty,
+        } = transform;
+        let mut ts = Transform::new(
+            sx as f64, ky as f64, kx as f64, sy as f64, tx as f64, ty as f64,
+        );
+        ts.append(&usvg::utils::view_box_to_transform(
+            svg.view_box.rect,
+            svg.view_box.aspect,
+            size.to_size(),
+        ));
+        let mut mesh = Mesh {
+            vertices: vec![],
+            shapes: vec![],
+            width: width as f64,
+            height: height as f64,
+        };
+        mesh.children(&tree.root(), ts);
+
+        let mut pixmap = Pixmap::new(width, height)?;
+        if mesh.vertices.is_empty() {
+            return Some(pixmap);
+        }
+        self.prepare_targets(width, height);
+        let targets = self.targets.as_ref()?;
+        let vertices: Vec<u8> = mesh
+            .vertices
+            .iter()
+            .flatten()
+            .flat_map(|v| v.to_le_bytes())
+            .collect();
+        let buffer = self
+            .device
+            .create_buffer_init(&wgpu::util::BufferInitDescriptor {
+                label: None,
+                contents: &vertices,
+                usage: wgpu::BufferUsages::VERTEX,
+            });
+        let mut encoder = self.device.create_command_encoder(&Default::default());
+        {
+            let mut pass = encoder.begin_render_pass(&wgpu::RenderPassDescriptor {
+                label: None,
+                color_attachments: &[wgpu::RenderPassColorAttachment {
+                    view: &targets.samples,
+                    resolve_target: Some(&targets.color_view),
+                    ops: wgpu::Operations {
+                        load: wgpu::LoadOp::Clear(wgpu::Color::TRANSPARENT),
+                        store: true,
+                    },
+                }],
+                depth_stencil_attachment: Some(wgpu::RenderPassDepthStencilAttachment {
+                    view: &targets.stencil,
+                    depth_ops: Some(wgpu::Operations {
+                        load: wgpu::LoadOp::Clear(1.0),
+                        store: false,
+                    }),
+                    stencil_ops: Some(wgpu::Operations {
+                        load: wgpu::LoadOp::Clear(0),
+                        store: false,
+                    }),
+                }),
+            });
+            pass.set_vertex_buffer(0, buffer.slice(..));
+            pass.set_stencil_reference(0);
+            for shape in &mesh.shapes {
+                let mark = match shape.rule {
+                    Rule::NonZero => &self.non_zero,
+                    Rule::EvenOdd => &self.even_odd,
+                    Rule::Union => &self.union,
+                };
+                pass.set_pipeline(mark);
+                pass.draw(shape.stencil.clone(), 0..1);
+                pass.set_pipeline(&self.cover);
+                pass.draw(shape.cover.clone(), 0..1);
+            }
+        }
+
+        // rows are copied out of textures in multiples of 256 bytes
+        let row = 4 * width;
+        let align = wgpu::COPY_BYTES_PER_ROW_ALIGNMENT;
+        let padded_row = row.div_ceil(align) * align;
+        let readback = self.device.create_buffer(&wgpu::BufferDescriptor {
+            label: None,
+            size: padded_row as u64 * height as u64,
+            usage: wgpu::BufferUsages::MAP_READ | wgpu::BufferUsages::COPY_DST,
+            mapped_at_creation: false,
+        });
+        encoder.copy_texture_to_buffer(
+            targets.color.as_image_copy(),
+            wgpu::ImageCopyBuffer {
+                buffer: &readback,
+                layout: wgpu::ImageDataLayout {
+                    offset: 0,
+                    bytes_per_row: NonZeroU32::new(padded_row),
+                    rows_per_image: None,
+                },
+            },
+            wgpu::Extent3d {
+                width,
+                height,
+                depth_or_array_layers: 1,
+            },
+        );
+        self.queue.submit([encoder.finish()]);
+
+        let slice = readback.slice(..);
+        let mapped = slice.map_async(wgpu::MapMode::Read);
+        self.device.poll(wgpu::Maintain::Wait);
+        pollster::block_on(mapped).ok()?;
+        {
+            let data = slice.get_mapped_range();
+            let rows = data.chunks(padded_row as usize);
+            let pixels = pixmap.data_mut().chunks_mut(row as usize);
+            for (pixels, data) in pixels.zip(rows) {
+                pixels.copy_from_slice(&data[..row as usize]);
+            }
+        }
+        readback.unmap();
+        Some(pixmap)
+    }
+
+    // makes the textures for renders of this size, kept for the next render
+    fn prepare_targets(&mut self, width: u32, height: u32) {
+        let current = self
+            .targets
+            .as_ref()
+            .map(|targets| (targets.width, targets.height));
+        if current == Some((width, height)) {
+            return;
+        }
+        let texture = |format, samples, usage| {
+            self.device.create_texture(&wgpu::TextureDescriptor {
+                label: None,
+                size: wgpu::Extent3d {
+                    width,
+                    height,
+                    depth_or_array_layers: 1,
+                },
+                mip_level_count: 1,
+                sample_count: samples,
+                dimension: wgpu::TextureDimension::D2,
+                format,
+                usage,
+            })
+        };
+        let attachment = wgpu::TextureUsages::RENDER_ATTACHMENT;
+        let samples = texture(COLOR_FORMAT, SAMPLES, attachment);
+        let color = texture(COLOR_FORMAT, 1, attachment | wgpu::TextureUsages::COPY_SRC);
+        let stencil = texture(STENCIL_FORMAT, SAMPLES, attachment);
+        self.targets = Some(Targets {
+            width,
+            height,
+            samples: samples.create_view(&Default::default()),
+            color_view: color.create_view(&Default::default()),
+            color,
+            stencil: stencil.create_view(&Default::default()),
+        });
+    }
+}
@@ -0,0 +1,75 @@
+//! The reloading SVG preview behind svgview, without its window.
+//!
+//! A [`Document`] is an SVG parsed from a file or from memory, a [`Viewport`] is how it is
+//! fitted, panned, zoomed and turned in an area of pixels, and a [`Renderer`] draws it
+//! there. A [`FileWatcher`] calls back when the file changes on disk, to load the
+//! document again:
+//!
+//! ```no_run
+//! use svgview_core::{fonts::Fonts, Document, FileWatcher, FitMode, Renderer, Viewport};
+//! use std::path::Path;
+//!
+//! let path = Path::new("/path/to/drawing.svg");
+//! let fonts = Fonts::default();
+//! let document = Document::from_file(path, &fonts)?;
+//! let viewport = Viewport::new(800, 600, 1.0, FitMode::Fit);
+//! let mut renderer = Renderer::new(svgview_core::Backend::Cpu);
+//! let pixmap = renderer.render(&document.tree, &viewport);
+//!
+//! let _watcher = FileWatcher::file(path, None, || {
+//!     println!("changed");
+//!     true
+//! })?;
+//! # Ok::<(), anyhow::Error>(())
+//! ```
+//!
+//! A [`Viewer`] puts these together into a preview pane for another application's window:
+//! the host forwards the pane's size and input and asks it for frames.
+//!
+//! ```no_run
+//! use svgview_core::{fonts::Fonts, Backend, Input, Viewer};
+//! use std::path::Path;
+//!
+//! let path = Path::new("/path/to/drawing.svg");
+//! let mut viewer = Viewer::open(path, Fonts::default(), Backend::Cpu, || {
+//!     // ask the host's event loop to repaint
+//! })?;
+//! viewer.resize(800, 600, 1.0);
+//! viewer.input(Input::Scroll(1.0, (400.0, 300.0)));
+//! if let Some(frame) = viewer.frame() {
+//!     // upload `frame.data()`, premultiplied RGBA, to the pane's texture
+//! }
+//! # Ok::<(), anyhow::Error>(())
+//! ```
+
+pub mod background;
+pub mod document;
+pub mod error;
+pub mod fit;
+pub mod fonts;
+#[cfg(feature = "gpu")]
+mod gpu;
+pub mod mask;
+pub mod render;
+pub mod viewer;
+pub mod viewport;
+pub mod watch;
+
+pub use document::Document;
+pub use fit::FitMode;
+pub use render::{Backend, Renderer};
+pub use viewer::{Input, Viewer};
+pub use viewport::Viewport;
+pub use watch::FileWatcher;
+
+use std::path::PathBuf;
+
+/// The file `name` in svgview's cache directory, `svgview` in `$XDG_CACHE_HOME`, or in
+/// `~/.cache` if that is unset.
+pub fn cache_path(name: &str) -> Option<PathBuf> {
+    let dir = std::env::var_os("XDG_CACHE_HOME")
+        .filter(|dir| !dir.is_empty())
+        .map(PathBuf::from)
+        .or_else(|| Some(PathBuf::from(std::env::var_os("HOME")?).join(".cache")))?;
+    Some(dir.join("svgview").join(name))
+}
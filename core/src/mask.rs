@@ -0,0 +1,75 @@
+//! Clipping the rendered SVG to the shapes operating systems use for app icons.
+
+use tiny_skia::{
+    ClipMask, Color, FillRule, Path, PathBuilder, Pixmap, PixmapPaint, Rect, Transform,
+};
+
+#[derive(Clone, Copy)]
+#[cfg_attr(feature = "clap", derive(clap::ValueEnum))]
+pub enum MaskShape {
+    Circle,
+    Rounded,
+}
+
+/// Fills everything in `pixmap` outside `shape` inscribed in `area` with `background`.
+/// `radius` is the rounded corner radius as a fraction of the shorter side.
+pub fn apply(pixmap: &mut Pixmap, shape: MaskShape, radius: f32, area: Rect, background: Color) {
+    let mut clip = ClipMask::new();
+    let clipped = outline(shape, radius, area).and_then(|path| {
+        clip.set_path(
+            pixmap.width(),
+            pixmap.height(),
+            &path,
+            FillRule::Winding,
+            true,
+        )
+    });
+    if clipped.is_none() {
+        return;
+    }
+
+    let content = pixmap.clone();
+    pixmap.fill(background);
+    pixmap.draw_pixmap(
+        0,
+        0,
+        content.as_ref(),
+        &PixmapPaint::default(),
+        Transform::identity(),
+        Some(&clip),
+    );
+}
+
+/// The outline of `shape` inscribed in `area`, as clipped to by `apply`.
+pub fn outline(shape: MaskShape, radius: f32, area: Rect) -> Option<Path> {
+    match shape {
+        MaskShape::Circle => PathBuilder::from_circle(
+            area.x() + area.width() / 2.0,
+            area.y() + area.height() / 2.0,
+            area.width().min(area.height()) / 2.0,
+        ),
+        MaskShape::Rounded => {
+            let radius = radius.clamp(0.0, 0.5) * area.width().min(area.height());
+            rounded_rect(area, radius)
+        }
+    }
+}
+
+fn rounded_rect(rect: Rect, r: f32) -> Option<Path> {
+    // control point distance approximating a quarter circle with a cubic
+    let k = r * (1.0 - 0.552_284_8);
+    let (left, top, right, bottom) = (rect.left(), rect.top(), rect.right(), rect.bottom());
+
+    let mut pb = PathBuilder::new();
+    pb.move_to(left + r, top);
+    pb.line_to(right - r, top);
+    pb.cubic_to(right - k, top, right, top + k, right, top + r);
+    pb.line_to(right, bottom - r);
+    pb.cubic_to(right, bottom - k, right - k, bottom, right - r, bottom);
+    pb.line_to(left + r, bottom);
+    pb.cubic_to(left + k, bottom, left, bottom - k, left, bottom - r);
+    pb.line_to(left, top + r);
+    pb.cubic_to(left, top + k, left + k, top, left + r, top);
+    pb.close();
+    pb.finish()
+}
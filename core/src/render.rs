@@ -0,0 +1,213 @@
+//! Drawing an SVG into a pixmap the way the viewer shows it.
+//!
+//! The window and the headless `export` subcommand both go through [`draw`], so an
+//! exported PNG matches what the viewer displays.
+
+use crate::background;
+use crate::fit::FitMode;
+use crate::mask::{self, MaskShape};
+use crate::viewport::Viewport;
+use log::warn;
+use tiny_skia::{Color, FilterQuality, Pixmap, PixmapPaint, Rect, Transform};
+use usvg::Tree;
+
+/// What renders the SVG.
+#[derive(Clone, Copy, PartialEq, Debug)]
+#[cfg_attr(feature = "clap", derive(clap::ValueEnum))]
+pub enum Backend {
+    /// resvg, on the CPU
+    Cpu,
+    /// The GPU, for documents it can draw; needs the `gpu` feature
+    Gpu,
+}
+
+pub struct Canvas {
+    pub fit_mode: FitMode,
+    /// Applied to the fitted SVG, e.g. the viewer's pan and zoom.
+    pub transform: Transform,
+    /// Fit the SVG to the pixmap with its sides swapped, for a `transform` that turns it by
+    /// a quarter.
+    pub turned: bool,
+    pub background: Color,
+    pub checkerboard: bool,
+    /// Clips the SVG to an icon shape inscribed in the given area.
+    pub mask: Option<(MaskShape, Rect)>,
+    pub mask_radius: f32,
+    /// Simulated device pixels per pixmap pixel.
+    pub density: f32,
+    /// Pixmap pixels per logical pixel, e.g. 2 on a HiDPI display.
+    pub scale_factor: f32,
+}
+
+/// Fills `pixmap` with the canvas background and draws `tree` on top.
+/// Returns `None` if the SVG could not be rasterized.
+pub fn draw(tree: &Tree, canvas: &Canvas, pixmap: &mut Pixmap) -> Option<()> {
+    draw_background(canvas, pixmap);
+    if canvas.density != 1.0 {
+        draw_at_density(tree, canvas, pixmap)?;
+    } else {
+        let fit_to = fit_to(
+            tree,
+            canvas,
+            pixmap.width(),
+            pixmap.height(),
+            canvas.scale_factor,
+        );
+        resvg::render(tree, fit_to, canvas.transform, pixmap.as_mut())?;
+    }
+    apply_mask(canvas, pixmap);
+    Some(())
+}
+
+/// Draws like [`draw`], with the backend it was made for.
+pub struct Renderer {
+    #[cfg(feature = "gpu")]
+    gpu: Option<crate::gpu::Gpu>,
+}
+
+impl Renderer {
+    pub fn new(backend: Backend) -> Self {
+        #[cfg(feature = "gpu")]
+        let gpu = match backend {
+            Backend::Gpu => crate::gpu::Gpu::new()
+                .map_err(|e| warn!("Rendering on the CPU, as the GPU cannot be used: {:#}", e))
+                .ok(),
+            Backend::Cpu => None,
+        };
+        #[cfg(not(feature = "gpu"))]
+        if backend == Backend::Gpu {
+            warn!("Rendering on the CPU, as the `gpu` feature is not enabled");
+        }
+        Self {
+            #[cfg(feature = "gpu")]
+            gpu,
+        }
+    }
+
+    /// `tree` as `viewport` shows it, over a transparent background.
+    pub fn render(&mut self, tree: &Tree, viewport: &Viewport) -> Option<Pixmap> {
+        let mut pixmap = Pixmap::new(viewport.width, viewport.height)?;
+        self.draw(tree, &viewport.canvas(tree), &mut pixmap)?;
+        Some(pixmap)
+    }
+
+    pub fn draw(&mut self, tree: &Tree, canvas: &Canvas, pixmap: &mut Pixmap) -> Option<()> {
+        #[cfg(feature = "gpu")]
+        if let Some(gpu) = &mut self.gpu {
+            let (width, height) = (pixmap.width(), pixmap.height());
+            let fit_to = fit_to(tree, canvas, width, height, canvas.scale_factor);
+            let layer = (canvas.density == 1.0 && crate::gpu::supports(tree))
+                .then(|| gpu.render(tree, fit_to, canvas.transform, width, height))
+                .flatten();
+            if let Some(layer) = layer {
+                draw_background(canvas, pixmap);
+                let paint = PixmapPaint::default();
+                pixmap.draw_pixmap(0, 0, layer.as_ref(), &paint, Transform::identity(), None);
+                apply_mask(canvas, pixmap);
+                return Some(());
+            }
+        }
+        draw(tree, canvas, pixmap)
+    }
+}
+
+fn draw_background(canvas: &Canvas, pixmap: &mut Pixmap) {
+    pixmap.fill(canvas.background);
+    if canvas.checkerboard {
+        background::draw_checkerboard(pixmap);
+    }
+}
+
+fn apply_mask(canvas: &Canvas, pixmap: &mut Pixmap) {
+    if let Some((shape, area)) = canvas.mask {
+        mask::apply(pixmap, shape, canvas.mask_radius, area, canvas.background);
+    }
+}
+
+/// Draws the part of `tree` that a canvas of `width` by `height` pixels shows with its top
+/// left corner at `origin` into `tile`, over a transparent background. Masks and densities
+/// other than 1 are not drawn.
+pub fn draw_tile(
+    tree: &Tree,
+    canvas: &Canvas,
+    size: (u32, u32),
+    origin: (i32, i32),
+    tile: &mut Pixmap,
+) -> Option<()> {
+    tile.fill(Color::TRANSPARENT);
+    draw_offset(tree, canvas, size, origin, tile)
+}
+
+/// Draws the same part of `tree` as [`draw_tile`] but over the canvas background, to render
+/// what [`draw`] would a band at a time. Masks and densities other than 1 are not drawn.
+pub fn draw_part(
+    tree: &Tree,
+    canvas: &Canvas,
+    size: (u32, u32),
+    origin: (i32, i32),
+    part: &mut Pixmap,
+) -> Option<()> {
+    draw_background(canvas, part);
+    draw_offset(tree, canvas, size, origin, part)
+}
+
+fn draw_offset(
+    tree: &Tree,
+    canvas: &Canvas,
+    (width, height): (u32, u32),
+    origin: (i32, i32),
+    pixmap: &mut Pixmap,
+) -> Option<()> {
+    let fit_to = fit_to(tree, canvas, width, height, canvas.scale_factor);
+    let transform =
+        Transform::from_translate(-origin.0 as f32, -origin.1 as f32).pre_concat(canvas.transform);
+    resvg::render(tree, fit_to, transform, pixmap.as_mut())
+}
+
+// rasterize with `density` device pixels per pixmap pixel, then scale the result to the pixmap
+fn draw_at_density(tree: &Tree, canvas: &Canvas, pixmap: &mut Pixmap) -> Option<()> {
+    let factor = canvas.density;
+    let width = ((pixmap.width() as f32 * factor).round() as u32).max(1);
+    let height = ((pixmap.height() as f32 * factor).round() as u32).max(1);
+    let mut raster = Pixmap::new(width, height)?;
+    let transform = Transform::from_scale(factor, factor)
+        .pre_concat(canvas.transform)
+        .pre_scale(1.0 / factor, 1.0 / factor);
+    let fit_to = fit_to(tree, canvas, width, height, canvas.scale_factor * factor);
+    resvg::render(tree, fit_to, transform, raster.as_mut())?;
+
+    // keep the simulated device pixels visible when scaling up
+    let quality = if factor < 1.0 {
+        FilterQuality::Nearest
+    } else {
+        FilterQuality::Bilinear
+    };
+    let paint = PixmapPaint {
+        quality,
+        ..Default::default()
+    };
+    pixmap.draw_pixmap(
+        0,
+        0,
+        raster.as_ref(),
+        &paint,
+        Transform::from_scale(
+            pixmap.width() as f32 / width as f32,
+            pixmap.height() as f32 / height as f32,
+        ),
+        None,
+    );
+    Some(())
+}
+
+fn fit_to(tree: &Tree, canvas: &Canvas, width: u32, height: u32, scale_factor: f32) -> usvg::FitTo {
+    let svg_size = tree.svg_node().size.to_screen_size();
+    let (width, height) = if canvas.turned {
+        (height, width)
+    } else {
+        (width, height)
+    };
+    canvas
+        .fit_mode
+        .fit_to(width, height, svg_size, scale_factor)
+}
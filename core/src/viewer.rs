@@ -0,0 +1,284 @@
+//! A viewer to embed as a preview pane in another application, e.g. one built with egui,
+//! iced or tauri.
+//!
+//! The host owns the window and the event loop: it forwards the pane's size and the input
+//! meant for it, and asks for a frame whenever it repaints. The viewer reloads the file
+//! when it is written, calling back so the host knows to repaint, and only renders again
+//! once the document or the view changed. Other threads of the host can send it new
+//! markup through a [`sender`](Viewer::sender).
+
+use crate::error::Failure;
+use crate::fit::FitMode;
+use crate::fonts::Fonts;
+use crate::render::{Backend, Renderer};
+use crate::viewport::{self, Viewport};
+use crate::{document, Document, FileWatcher};
+use anyhow::{Context, Result};
+use log::warn;
+use std::path::Path;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::mpsc::{self, Receiver, Sender};
+use std::sync::Arc;
+use tiny_skia::{Color, Pixmap, Transform};
+
+/// Input the host forwards to the pane, in pane pixels.
+#[derive(Clone, Copy, PartialEq, Debug)]
+pub enum Input {
+    /// The pointer moved by `(dx, dy)` with the button that pans held.
+    Drag(f32, f32),
+    /// The wheel turned by this many lines, positive away from the user, with the pointer
+    /// at the given position.
+    Scroll(f32, (f32, f32)),
+    ZoomIn,
+    ZoomOut,
+    /// Back to the SVG fitted to the pane.
+    ResetView,
+    RotateClockwise,
+    RotateCounterClockwise,
+    FlipHorizontal,
+    FlipVertical,
+    CycleFit,
+}
+
+pub struct Viewer {
+    document: Document,
+    fonts: Fonts,
+    viewport: Viewport,
+    renderer: Renderer,
+    background: Color,
+    watcher: Option<FileWatcher>,
+    // set on the watcher's thread when the file was written
+    changed: Arc<AtomicBool>,
+    // markup sent from other threads, and the end they send it from
+    updates: Receiver<Vec<u8>>,
+    sender: Sender<Vec<u8>>,
+    // the render of the current view, until the document or the view changes
+    frame: Option<Pixmap>,
+    // why the file on disk is not shown
+    error: Option<String>,
+}
+
+impl Viewer {
+    /// Shows the SVG at `path` and reloads it whenever it is written, calling `wake` on the
+    /// watcher's thread when it was, for the host to repaint.
+    pub fn open(
+        path: &Path,
+        fonts: Fonts,
+        backend: Backend,
+        wake: impl Fn() + Send + 'static,
+    ) -> Result<Self> {
+        let path = std::fs::canonicalize(path)
+            .with_context(|| Failure::Input(format!("could not open {}", path.display())))?;
+        let mut viewer = Self::new(Document::from_file(&path, &fonts)?, fonts, backend);
+        let changed = viewer.changed.clone();
+        let watcher = FileWatcher::file(&path, None, move || {
+            changed.store(true, Ordering::SeqCst);
+            wake();
+            true
+        });
+        viewer.watcher = Some(watcher.context("could not start the file system watcher")?);
+        Ok(viewer)
+    }
+
+    /// Shows `data`, which is not watched; [`load`](Self::load) replaces it.
+    pub fn from_data(data: Vec<u8>, fonts: Fonts, backend: Backend) -> Result<Self> {
+        let document = Document::from_data(data, "the SVG", &fonts)?;
+        Ok(Self::new(document, fonts, backend))
+    }
+
+    fn new(document: Document, fonts: Fonts, backend: Backend) -> Self {
+        let (sender, updates) = mpsc::channel();
+        Self {
+            document,
+            fonts,
+            viewport: Viewport::new(0, 0, 1.0, FitMode::Fit),
+            renderer: Renderer::new(backend),
+            background: Color::TRANSPARENT,
+            watcher: None,
+            changed: Arc::new(AtomicBool::new(false)),
+            updates,
+            sender,
+            frame: None,
+            error: None,
+        }
+    }
+
+    /// To be called when the pane is shown at a new size, with `scale_factor` pixels per
+    /// logical pixel.
+    pub fn resize(&mut self, width: u32, height: u32, scale_factor: f64) {
+        self.viewport.width = width;
+        self.viewport.height = height;
+        self.viewport.scale_factor = scale_factor;
+        self.frame = None;
+    }
+
+    pub fn input(&mut self, input: Input) {
+        let tree = &self.document.tree;
+        let viewport = &mut self.viewport;
+        let center = (viewport.width as f32 / 2.0, viewport.height as f32 / 2.0);
+        match input {
+            Input::Drag(dx, dy) => viewport.pan(dx, dy),
+            Input::Scroll(lines, at) => {
+                viewport.zoom_at(tree, viewport::WHEEL_ZOOM_STEP.powf(lines), at)
+            }
+            Input::ZoomIn => viewport.zoom_at(tree, viewport::ZOOM_STEP, center),
+            Input::ZoomOut => viewport.zoom_at(tree, 1.0 / viewport::ZOOM_STEP, center),
+            Input::ResetView => viewport.reset(),
+            Input::RotateClockwise => viewport.reorient(viewport::quarter_turn(1.0)),
+            Input::RotateCounterClockwise => viewport.reorient(viewport::quarter_turn(-1.0)),
+            Input::FlipHorizontal => viewport.reorient(Transform::from_scale(-1.0, 1.0)),
+            Input::FlipVertical => viewport.reorient(Transform::from_scale(1.0, -1.0)),
+            Input::CycleFit => viewport.fit_mode = viewport.fit_mode.next(),
+        }
+        self.frame = None;
+    }
+
+    /// The color behind the SVG, transparent by default so the host's background shows.
+    pub fn set_background(&mut self, color: Color) {
+        self.background = color;
+        self.frame = None;
+    }
+
+    /// Shows the markup `data` instead of the current document and returns its size, for
+    /// the host to lay out the pane again. If it is not a valid SVG, the current document
+    /// stays, [`error`](Self::error) says why and there is no size.
+    pub fn load(&mut self, data: Vec<u8>) -> Option<usvg::Size> {
+        self.frame = None;
+        match self.document.set_source(data, &self.fonts) {
+            Ok(()) => self.error = None,
+            Err(e) => self.error = Some(format!("{:#}", e)),
+        }
+        self.error
+            .is_none()
+            .then(|| self.document.tree.svg_node().size)
+    }
+
+    /// Where other threads send markup to show, e.g. a host's editor as the user types.
+    /// The viewer loads the latest of it on [`poll_reload`](Self::poll_reload) or the next
+    /// [`frame`](Self::frame), so the sending thread should have the host repaint.
+    pub fn sender(&self) -> Sender<Vec<u8>> {
+        self.sender.clone()
+    }
+
+    /// Loads what was sent through [`sender`](Self::sender) and reloads the file if it was
+    /// written since the last call, for hosts that step the viewer in a loop of their own
+    /// rather than repaint when woken, whose `wake` can then do nothing. Returns the size
+    /// of the document if it changed, for the host to lay out the pane again; an invalid
+    /// version leaves it as it was, with [`error`](Self::error) set.
+    /// [`frame`](Self::frame) does this too.
+    pub fn poll_reload(&mut self) -> Option<usvg::Size> {
+        // only the latest of what was sent is worth showing
+        let sent = self.updates.try_iter().last();
+        let loaded = sent.and_then(|data| self.load(data));
+        if !self.changed.swap(false, Ordering::SeqCst) {
+            return loaded;
+        }
+        self.reload().or(loaded)
+    }
+
+    /// The frame for the pane's current size, as premultiplied RGBA, after reloading the
+    /// file if it changed. `None` while the pane has no pixels or if the SVG could not be
+    /// rendered.
+    pub fn frame(&mut self) -> Option<&Pixmap> {
+        self.poll_reload();
+        if self.frame.is_none() {
+            let tree = &self.document.tree;
+            let mut canvas = self.viewport.canvas(tree);
+            canvas.background = self.background;
+            let mut pixmap = Pixmap::new(self.viewport.width, self.viewport.height)?;
+            self.renderer.draw(tree, &canvas, &mut pixmap)?;
+            self.frame = Some(pixmap);
+        }
+        self.frame.as_ref()
+    }
+
+    /// Copies the frame into `target`, a buffer of the pane's width times height RGBA
+    /// pixels with premultiplied alpha, such as a texture the host maps. Returns false,
+    /// leaving `target` as it is, if there is no frame or `target` has another size.
+    pub fn draw_into(&mut self, target: &mut [u8]) -> bool {
+        match self.frame() {
+            Some(frame) if frame.data().len() == target.len() => {
+                target.copy_from_slice(frame.data());
+                true
+            }
+            _ => false,
+        }
+    }
+
+    /// Why the latest version of the document is not shown, if it is not.
+    pub fn error(&self) -> Option<&str> {
+        self.error.as_deref()
+    }
+
+    pub fn document(&self) -> &Document {
+        &self.document
+    }
+
+    pub fn viewport(&self) -> &Viewport {
+        &self.viewport
+    }
+
+    /// The viewport, e.g. to pan to a position rather than by a distance.
+    pub fn viewport_mut(&mut self) -> &mut Viewport {
+        self.frame = None;
+        &mut self.viewport
+    }
+
+    // returns the size of the document if the file had another version of it
+    fn reload(&mut self) -> Option<usvg::Size> {
+        if let Some(watcher) = &self.watcher {
+            watcher.clear_pending();
+        }
+        let path = self.document.path.clone()?;
+        // the file may be gone for a moment while an editor replaces it
+        let source = match document::read_svg(&path) {
+            Ok(source) => source,
+            Err(e) => {
+                warn!("Could not read {}: {}", path.display(), e);
+                return None;
+            }
+        };
+        // written again as it was
+        if source == self.document.source && self.error.is_none() {
+            return None;
+        }
+        self.load(source)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn svg(width: u32) -> Vec<u8> {
+        format!(
+            r#"<svg xmlns="http://www.w3.org/2000/svg" width="{}" height="10"/>"#,
+            width
+        )
+        .into_bytes()
+    }
+
+    #[test]
+    fn poll_reload_loads_the_latest_markup_sent() {
+        let mut viewer = Viewer::from_data(svg(10), Fonts::default(), Backend::Cpu).unwrap();
+        let sender = viewer.sender();
+        std::thread::spawn(move || {
+            sender.send(svg(20)).unwrap();
+            sender.send(svg(30)).unwrap();
+        })
+        .join()
+        .unwrap();
+        let size = viewer.poll_reload().unwrap();
+        assert_eq!((size.width(), size.height()), (30.0, 10.0));
+        assert!(viewer.poll_reload().is_none());
+    }
+
+    #[test]
+    fn invalid_markup_sent_keeps_the_document() {
+        let mut viewer = Viewer::from_data(svg(10), Fonts::default(), Backend::Cpu).unwrap();
+        viewer.sender().send(b"<svg".to_vec()).unwrap();
+        assert!(viewer.poll_reload().is_none());
+        assert!(viewer.error().is_some());
+        assert_eq!(viewer.document().tree.svg_node().size.width(), 10.0);
+    }
+}
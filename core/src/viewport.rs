@@ -0,0 +1,218 @@
+//! Where an SVG is shown in an area of pixels: fitted to it, then panned, zoomed, turned
+//! and mirrored.
+
+use crate::fit::FitMode;
+use crate::render::Canvas;
+use tiny_skia::{Color, Rect, Transform};
+use usvg::{FitTo, ScreenSize, Tree};
+
+/// How much one line of the mouse wheel zooms.
+pub const WHEEL_ZOOM_STEP: f32 = 1.1;
+/// How much the zoom keys zoom.
+pub const ZOOM_STEP: f32 = 1.25;
+const MIN_SCALE: f32 = 0.01;
+const MAX_SCALE: f32 = 1000.0;
+// smallest width and height of an area to zoom into, in pixels
+const MIN_SELECTION: f32 = 4.0;
+
+#[derive(Clone, Copy, Debug)]
+pub struct Viewport {
+    pub width: u32,
+    pub height: u32,
+    /// Pixels per logical pixel, e.g. 2 on a HiDPI display.
+    pub scale_factor: f64,
+    pub fit_mode: FitMode,
+    /// Pan and zoom applied on top of the fitted and centered SVG, in pixels.
+    pub offset: (f32, f32),
+    pub scale: f32,
+    /// Quarter turns and mirroring, as a matrix without translation.
+    pub orientation: Transform,
+    /// Applied to the fitted SVG before everything else, in pixels.
+    pub transform: Transform,
+}
+
+impl Viewport {
+    pub fn new(width: u32, height: u32, scale_factor: f64, fit_mode: FitMode) -> Self {
+        Self {
+            width,
+            height,
+            scale_factor,
+            fit_mode,
+            offset: (0.0, 0.0),
+            scale: 1.0,
+            orientation: Transform::identity(),
+            transform: Transform::identity(),
+        }
+    }
+
+    /// How `tree` is rendered into the viewport, over a transparent background.
+    pub fn canvas(&self, tree: &Tree) -> Canvas {
+        Canvas {
+            fit_mode: self.fit_mode,
+            transform: self.view_transform(tree),
+            turned: self.turned(),
+            background: Color::TRANSPARENT,
+            checkerboard: false,
+            mask: None,
+            mask_radius: 0.0,
+            density: 1.0,
+            scale_factor: self.scale_factor as f32,
+        }
+    }
+
+    /// The scale at which `tree` is displayed relative to its intrinsic size.
+    pub fn zoom(&self, tree: &Tree) -> f64 {
+        let fit = match self.fitted_size(tree) {
+            Some(fitted) => fitted.width() as f64 / tree.svg_node().size.width(),
+            None => 1.0,
+        };
+        fit * self.scale as f64
+    }
+
+    pub fn fit_to(&self, tree: &Tree, width: u32, height: u32) -> FitTo {
+        let svg_size = tree.svg_node().size.to_screen_size();
+        self.fit_mode
+            .fit_to(width, height, svg_size, self.scale_factor as f32)
+    }
+
+    /// The size of `tree` in pixels before pan and zoom.
+    pub fn fitted_size(&self, tree: &Tree) -> Option<ScreenSize> {
+        let (width, height) = if self.turned() {
+            (self.height, self.width)
+        } else {
+            (self.width, self.height)
+        };
+        self.fit_to(tree, width, height)
+            .fit_to(tree.svg_node().size.to_screen_size())
+    }
+
+    /// Where the fitted `tree` is in the viewport.
+    pub fn document_area(&self, tree: &Tree) -> Option<Rect> {
+        let (x, y) = self.origin(tree);
+        self.fitted_size(tree).and_then(|size| {
+            let width = size.width() as f32 * self.scale;
+            let height = size.height() as f32 * self.scale;
+            if self.turned() {
+                // the same center, with the sides swapped
+                let (cx, cy) = (x + width / 2.0, y + height / 2.0);
+                Rect::from_xywh(cx - height / 2.0, cy - width / 2.0, height, width)
+            } else {
+                Rect::from_xywh(x, y, width, height)
+            }
+        })
+    }
+
+    /// The pan and zoom, then `transform`, applied to the fitted `tree`.
+    pub fn view_transform(&self, tree: &Tree) -> Transform {
+        let (x, y) = self.origin(tree);
+        let (width, height) = self
+            .fitted_size(tree)
+            .map(|size| (size.width() as f32, size.height() as f32))
+            .unwrap_or((0.0, 0.0));
+        Transform::from_translate(x, y)
+            .pre_scale(self.scale, self.scale)
+            // turned and mirrored around the center of the fitted SVG
+            .pre_translate(width / 2.0, height / 2.0)
+            .pre_concat(self.orientation)
+            .pre_translate(-width / 2.0, -height / 2.0)
+            .pre_concat(self.transform)
+    }
+
+    /// Maps the user units of `tree`, as in its viewBox, to pixels.
+    pub fn user_transform(&self, tree: &Tree) -> Transform {
+        let svg = tree.svg_node();
+        let scale = self.fitted_size(tree).map_or(1.0, |fitted| {
+            fitted.width() as f32 / svg.size.width() as f32
+        });
+        self.view_transform(tree)
+            .pre_scale(scale, scale)
+            .pre_concat(view_box_transform(&svg))
+    }
+
+    /// Whether the view is turned by an odd number of quarter turns, so the SVG is fitted
+    /// with its sides swapped.
+    pub fn turned(&self) -> bool {
+        self.orientation.sx == 0.0
+    }
+
+    /// Applies a quarter turn or mirroring on top of the current orientation.
+    pub fn reorient(&mut self, change: Transform) {
+        self.orientation = self.orientation.post_concat(change);
+    }
+
+    pub fn pan(&mut self, dx: f32, dy: f32) {
+        self.offset.0 += dx;
+        self.offset.1 += dy;
+    }
+
+    /// Centers the SVG in the viewport again, keeping the zoom.
+    pub fn center(&mut self) {
+        self.offset = (0.0, 0.0);
+    }
+
+    /// Back to the SVG fitted to the viewport.
+    pub fn reset(&mut self) {
+        self.offset = (0.0, 0.0);
+        self.scale = 1.0;
+    }
+
+    /// Pixel position of the top-left corner of `tree`: centered when unzoomed, then
+    /// panned.
+    pub fn origin(&self, tree: &Tree) -> (f32, f32) {
+        let (width, height) = self
+            .fitted_size(tree)
+            .map(|size| (size.width() as f32, size.height() as f32))
+            .unwrap_or((0.0, 0.0));
+        (
+            (self.width as f32 - width * self.scale) / 2.0 + self.offset.0,
+            (self.height as f32 - height * self.scale) / 2.0 + self.offset.1,
+        )
+    }
+
+    /// Zooms so that the area between the corners `a` and `b` fills the viewport. Returns
+    /// false for an area too small to be meant, such as that of a click.
+    pub fn zoom_to_area(&mut self, tree: &Tree, a: (f32, f32), b: (f32, f32)) -> bool {
+        let (width, height) = ((a.0 - b.0).abs(), (a.1 - b.1).abs());
+        if width < MIN_SELECTION || height < MIN_SELECTION {
+            return false;
+        }
+        let center = ((a.0 + b.0) / 2.0, (a.1 + b.1) / 2.0);
+        let factor = (self.width as f32 / width).min(self.height as f32 / height);
+        self.zoom_at(tree, factor, center);
+        self.pan(
+            self.width as f32 / 2.0 - center.0,
+            self.height as f32 / 2.0 - center.1,
+        );
+        true
+    }
+
+    /// Multiplies the zoom by `factor` while keeping the point `anchor` fixed.
+    pub fn zoom_at(&mut self, tree: &Tree, factor: f32, anchor: (f32, f32)) {
+        let scale = (self.scale * factor).clamp(MIN_SCALE, MAX_SCALE);
+        let factor = scale / self.scale;
+        let (x, y) = self.origin(tree);
+        let (x, y) = (
+            anchor.0 - (anchor.0 - x) * factor,
+            anchor.1 - (anchor.1 - y) * factor,
+        );
+        self.scale = scale;
+        // the centering part of the origin depends on the scale, so take it off again
+        let (centered_x, centered_y) = self.origin(tree);
+        self.offset.0 += x - centered_x;
+        self.offset.1 += y - centered_y;
+    }
+}
+
+/// A quarter turn clockwise for `direction` 1 and counterclockwise for -1, exactly rather
+/// than with the rounding errors of `from_rotate`, to [`reorient`](Viewport::reorient)
+/// with.
+pub fn quarter_turn(direction: f32) -> Transform {
+    Transform::from_row(0.0, direction, -direction, 0.0, 0.0, 0.0)
+}
+
+/// From the user units of `svg` to its size.
+pub fn view_box_transform(svg: &usvg::Svg) -> Transform {
+    let ts = usvg::utils::view_box_to_transform(svg.view_box.rect, svg.view_box.aspect, svg.size);
+    let (a, b, c, d, e, f) = (ts.a, ts.b, ts.c, ts.d, ts.e, ts.f);
+    Transform::from_row(a as f32, b as f32, c as f32, d as f32, e as f32, f as f32)
+}
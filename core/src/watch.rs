@@ -0,0 +1,296 @@
+//! Reloading when the shown file, or any SVG in a watched directory, changes on disk.
+//!
+//! The watcher calls back on a thread of its own, which usually only hands the change to
+//! the thread that owns the document, e.g. as an event of its event loop.
+//!
+//! Changes are picked up from the platform's file system notifications, or by polling
+//! on file systems that have none.
+//!
+//! Files are watched through their parent directory. Editors that save by writing a
+//! temporary file and renaming it over the original replace the watched inode, which
+//! would silently end a watch on the file itself.
+
+use crate::document::{is_svg, svgs_in};
+use log::warn;
+use notify::{raw_watcher, Op, RawEvent, RecommendedWatcher, RecursiveMode, Watcher};
+use std::collections::HashMap;
+use std::path::{Path, PathBuf};
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::mpsc::{channel, Receiver};
+use std::sync::{Arc, Mutex, Weak};
+use std::thread;
+use std::time::{Duration, SystemTime};
+
+// inotify reports when the writer closes the file, so half written files are not read;
+// the other backends only report writes
+#[cfg(target_os = "linux")]
+const FILE_WRITTEN: Op = Op::CLOSE_WRITE;
+#[cfg(not(target_os = "linux"))]
+const FILE_WRITTEN: Op = Op::WRITE;
+
+// quiet time after a file event before reloading, so a burst of writes reloads once
+const DEBOUNCE: Duration = Duration::from_millis(100);
+
+/// Called when a watched file was written; returns false once nothing listens anymore,
+/// which ends the watch.
+pub type OnChange = Box<dyn Fn() -> bool + Send>;
+
+// a change seen but not yet passed on, until the quiet time after it is over, and what
+// to call when one is seen
+#[derive(Default)]
+struct Settling {
+    active: AtomicBool,
+    wake: Mutex<Option<Box<dyn Fn() + Send>>>,
+}
+
+impl Settling {
+    fn start(&self) {
+        if !self.active.swap(true, Ordering::SeqCst) {
+            if let Some(wake) = &*self.wake.lock().unwrap() {
+                wake();
+            }
+        }
+    }
+}
+
+// what the watcher reloads on
+struct Targets {
+    dirs: Vec<PathBuf>,
+    // files whose changes trigger a reload, or `None` for every SVG in `dirs`
+    files: Option<Vec<PathBuf>>,
+}
+
+impl Targets {
+    fn is_watched(&self, path: &Path) -> bool {
+        match &self.files {
+            Some(files) => files.iter().any(|file| file == path),
+            None => is_svg(path),
+        }
+    }
+}
+
+pub struct FileWatcher {
+    // `None` when polling instead
+    watcher: Option<RecommendedWatcher>,
+    targets: Arc<Mutex<Targets>>,
+    // set while a reload is queued in the event loop
+    pending: Arc<AtomicBool>,
+    settling: Arc<Settling>,
+}
+
+impl FileWatcher {
+    /// Watches `file`, which has to be an absolute path, calling `on_change` when it is
+    /// written.
+    pub fn file(
+        file: &Path,
+        poll: Option<Duration>,
+        on_change: impl Fn() -> bool + Send + 'static,
+    ) -> notify::Result<Self> {
+        let mut watcher = Self::new(Some(vec![]), poll, Box::new(on_change))?;
+        watcher.set_files(vec![file.to_path_buf()])?;
+        Ok(watcher)
+    }
+
+    /// Watches every SVG directly inside `dir`, calling `on_change` when one is written.
+    pub fn dir(
+        dir: &Path,
+        poll: Option<Duration>,
+        on_change: impl Fn() -> bool + Send + 'static,
+    ) -> notify::Result<Self> {
+        let mut watcher = Self::new(None, poll, Box::new(on_change))?;
+        watcher.watch_dirs(vec![dir.to_path_buf()])?;
+        Ok(watcher)
+    }
+
+    // uses the platform's file system notifications, or checks the files every `poll`
+    fn new(
+        files: Option<Vec<PathBuf>>,
+        poll: Option<Duration>,
+        on_change: OnChange,
+    ) -> notify::Result<Self> {
+        let targets = Arc::new(Mutex::new(Targets {
+            dirs: vec![],
+            files,
+        }));
+        let pending = Arc::new(AtomicBool::new(false));
+        let settling = Arc::new(Settling::default());
+        let reload = Reload {
+            pending: pending.clone(),
+            settling: settling.clone(),
+            on_change,
+        };
+        let watcher = match poll {
+            Some(interval) => {
+                let targets = Arc::downgrade(&targets);
+                thread::spawn(move || poll_changes(interval, targets, reload));
+                None
+            }
+            None => {
+                let (tx, rx) = channel();
+                let watcher = raw_watcher(tx)?;
+                let targets = targets.clone();
+                thread::spawn(move || notify_changes(rx, targets, reload));
+                Some(watcher)
+            }
+        };
+        Ok(Self {
+            watcher,
+            targets,
+            pending,
+            settling,
+        })
+    }
+
+    /// Reloads on changes to exactly these absolute paths from now on.
+    pub fn set_files(&mut self, files: Vec<PathBuf>) -> notify::Result<()> {
+        let mut dirs: Vec<PathBuf> = files
+            .iter()
+            .filter_map(|file| file.parent())
+            .map(Path::to_path_buf)
+            .collect();
+        dirs.sort();
+        dirs.dedup();
+        self.targets.lock().unwrap().files = Some(files);
+        self.watch_dirs(dirs)
+    }
+
+    /// To be called when handling a reload, so the next change queues another one.
+    pub fn clear_pending(&self) {
+        self.pending.store(false, Ordering::SeqCst);
+    }
+
+    /// Whether a change was seen that was not handled yet: either the quiet time that has
+    /// to pass after it before reloading is not over, or its reload is queued.
+    pub fn pending(&self) -> bool {
+        self.settling.active.load(Ordering::SeqCst) || self.pending.load(Ordering::SeqCst)
+    }
+
+    /// Calls `wake` on the watcher's thread as soon as a change is seen, before the quiet
+    /// time after it, e.g. to show that a reload is coming.
+    pub fn wake_when_pending(&self, wake: impl Fn() + Send + 'static) {
+        *self.settling.wake.lock().unwrap() = Some(Box::new(wake));
+    }
+
+    // keeps watching the directories that can be watched if some of them cannot
+    fn watch_dirs(&mut self, dirs: Vec<PathBuf>) -> notify::Result<()> {
+        let mut targets = self.targets.lock().unwrap();
+        let watcher = match &mut self.watcher {
+            Some(watcher) => watcher,
+            None => {
+                targets.dirs = dirs;
+                return Ok(());
+            }
+        };
+        for dir in targets.dirs.iter().filter(|dir| !dirs.contains(dir)) {
+            let _ = watcher.unwatch(dir);
+        }
+        let mut result = Ok(());
+        let mut watched = vec![];
+        for dir in dirs {
+            if !targets.dirs.contains(&dir) {
+                if let Err(e) = watcher.watch(&dir, RecursiveMode::NonRecursive) {
+                    result = Err(e);
+                    continue;
+                }
+            }
+            watched.push(dir);
+        }
+        targets.dirs = watched;
+        result
+    }
+}
+
+// how the watcher's thread passes changes on
+struct Reload {
+    pending: Arc<AtomicBool>,
+    settling: Arc<Settling>,
+    on_change: OnChange,
+}
+
+impl Reload {
+    // queues a reload unless one is queued already, since that one will read the latest
+    // file anyway; false once nothing listens anymore
+    fn request(&self) -> bool {
+        // over before the reload is handled, which may well happen before this returns
+        self.settling.active.store(false, Ordering::SeqCst);
+        self.pending.swap(true, Ordering::SeqCst) || (self.on_change)()
+    }
+}
+
+fn notify_changes(rx: Receiver<RawEvent>, targets: Arc<Mutex<Targets>>, reload: Reload) {
+    let relevant = |event: &RawEvent| {
+        // a file renamed over the original, as saved by most editors, is complete as well
+        let written = event
+            .op
+            .as_ref()
+            .is_ok_and(|op| op.intersects(FILE_WRITTEN | Op::RENAME));
+        let watched = match &event.path {
+            Some(path) => targets.lock().unwrap().is_watched(path),
+            None => false,
+        };
+        written && watched
+    };
+    while let Ok(event) = rx.recv() {
+        if let Err(e) = &event.op {
+            warn!("watch error: {:?}", e);
+        }
+        if !relevant(&event) {
+            continue;
+        }
+        // wait for the burst of events of a single save to end
+        reload.settling.start();
+        while rx.recv_timeout(DEBOUNCE).is_ok() {}
+        if !reload.request() {
+            break;
+        }
+    }
+}
+
+// for file systems without change notifications, such as network shares: compares the
+// modification time and size of the watched files every `interval`, and reloads once
+// a changed file has stayed the same for one interval
+fn poll_changes(interval: Duration, targets: Weak<Mutex<Targets>>, reload: Reload) {
+    let mut stamps: HashMap<PathBuf, (SystemTime, u64)> = HashMap::new();
+    let mut first = true;
+    let mut changed = false;
+    // stops once the watcher is dropped
+    while let Some(targets) = targets.upgrade() {
+        let (paths, in_dir) = {
+            let targets = targets.lock().unwrap();
+            let paths = match &targets.files {
+                Some(files) => files.clone(),
+                None => targets.dirs.iter().flat_map(|dir| svgs_in(dir)).collect(),
+            };
+            (paths, targets.files.is_none())
+        };
+        drop(targets);
+
+        let mut changed_now = false;
+        for path in paths {
+            let metadata = std::fs::metadata(&path);
+            let stamp = match metadata.and_then(|m| Ok((m.modified()?, m.len()))) {
+                Ok(stamp) => stamp,
+                // keep the last stamp while the file is missing, as during an atomic save
+                Err(_) => continue,
+            };
+            match stamps.insert(path, stamp) {
+                Some(previous) => changed_now |= previous != stamp,
+                // files only show up later in a watched directory; in file mode they are
+                // simply new to the watch list
+                None => changed_now |= in_dir && !first,
+            }
+        }
+        first = false;
+
+        if changed_now {
+            changed = true;
+            reload.settling.start();
+        } else if changed {
+            changed = false;
+            if !reload.request() {
+                break;
+            }
+        }
+        thread::sleep(interval);
+    }
+}
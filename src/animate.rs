@@ -0,0 +1,516 @@
+//! Playback of SMIL animations, which usvg ignores: the animated attributes are written
+//! into the source markup for a point in time, which is then parsed as a still SVG.
+//!
+//! `<animate>`, `<set>` and `<animateTransform>` are supported with a `begin` offset,
+//! `dur`, `repeatCount`, `fill="freeze"`, `values` or `from`/`to`/`by`, `keyTimes` and
+//! `calcMode="discrete"`. Numbers, lists of numbers and colors are interpolated, other
+//! values change at the key times. Event-based `begin`, `additive="sum"` outside of
+//! transforms and CSS animations are not supported.
+
+use std::collections::HashMap;
+use std::ops::Range;
+use std::time::{Duration, Instant};
+
+/// Frames per second of playback.
+pub const FRAME_RATE: f64 = 30.0;
+
+const XLINK_NS: &str = "http://www.w3.org/1999/xlink";
+
+/// How long the animations of a document run.
+#[derive(Clone, Copy)]
+pub struct Span {
+    /// When the last animation ends, or ends its first repetition if it repeats forever.
+    pub end: f64,
+    /// Whether some animation repeats forever.
+    pub endless: bool,
+}
+
+/// The document time of a playing or paused animation.
+pub struct Clock {
+    start: Instant,
+    paused: Option<f64>,
+    // the document time of the frame shown
+    shown: f64,
+    span: Span,
+}
+
+impl Clock {
+    /// A running clock at time 0, if `source` is animated.
+    pub fn for_source(source: &[u8]) -> Option<Self> {
+        Some(Self {
+            start: Instant::now(),
+            paused: None,
+            shown: 0.0,
+            span: span(source)?,
+        })
+    }
+
+    /// The current document time in seconds.
+    pub fn time(&self) -> f64 {
+        self.paused
+            .unwrap_or_else(|| self.start.elapsed().as_secs_f64())
+    }
+
+    pub fn toggle_pause(&mut self) {
+        match self.paused.take() {
+            Some(time) => self.start = Instant::now() - Duration::from_secs_f64(time),
+            None => self.paused = Some(self.time()),
+        }
+    }
+
+    /// How long the animations run.
+    pub fn span(&self) -> Span {
+        self.span
+    }
+
+    /// The time of the shown frame, in seconds.
+    pub fn shown(&self) -> f64 {
+        self.shown
+    }
+
+    /// Pauses at `time`, in seconds.
+    pub fn seek(&mut self, time: f64) {
+        self.paused = Some(time.max(0.0));
+    }
+
+    /// Pauses `frames` frames after the shown one, or before it if negative.
+    pub fn step(&mut self, frames: i32) {
+        let frame = (self.shown * FRAME_RATE).round() + frames as f64;
+        self.seek(frame / FRAME_RATE);
+    }
+
+    /// When the frame after the shown one is due, unless paused or every animation ended.
+    pub fn next_frame(&self) -> Option<Instant> {
+        if self.paused.is_some() || (!self.span.endless && self.shown > self.span.end) {
+            return None;
+        }
+        Some(self.start + Duration::from_secs_f64(self.shown + 1.0 / FRAME_RATE))
+    }
+
+    /// The current document time, noted as that of the shown frame.
+    pub fn show_frame(&mut self) -> f64 {
+        self.shown = self.time();
+        self.shown
+    }
+}
+
+struct Timing {
+    begin: f64,
+    // the length of one repetition, infinite for a `<set>` without one
+    dur: f64,
+    repeat: f64,
+    freeze: bool,
+}
+
+impl Timing {
+    fn parse(node: roxmltree::Node) -> Option<Self> {
+        // the first offset of the `begin` list; others wait for events that never come
+        let begin = match node.attribute("begin") {
+            Some(begin) => begin.split(';').find_map(clock_value)?,
+            None => 0.0,
+        };
+        let dur = match node.attribute("dur").and_then(clock_value) {
+            Some(dur) if dur > 0.0 => dur,
+            _ if node.tag_name().name() == "set" => f64::INFINITY,
+            _ => return None,
+        };
+        let repeat = match (node.attribute("repeatCount"), node.attribute("repeatDur")) {
+            (Some("indefinite"), _) | (_, Some("indefinite")) => f64::INFINITY,
+            (Some(count), _) => count
+                .trim()
+                .parse()
+                .ok()
+                .filter(|count: &f64| *count > 0.0)?,
+            (None, _) => 1.0,
+        };
+        Some(Self {
+            begin,
+            dur,
+            repeat,
+            freeze: node.attribute("fill") == Some("freeze"),
+        })
+    }
+
+    // how far into its current repetition the animation is at `time`, from 0 to 1, or
+    // `None` while it has no effect
+    fn progress(&self, time: f64) -> Option<f64> {
+        if time < self.begin {
+            return None;
+        }
+        let elapsed = time - self.begin;
+        if elapsed >= self.dur * self.repeat {
+            // frozen where the last repetition stops, which may be part way through
+            let last = self.repeat.fract();
+            return self.freeze.then_some(if last == 0.0 { 1.0 } else { last });
+        }
+        Some(if self.dur.is_finite() {
+            (elapsed / self.dur).fract()
+        } else {
+            0.0
+        })
+    }
+
+    fn end(&self) -> f64 {
+        if !self.dur.is_finite() {
+            return self.begin;
+        }
+        let repetitions = if self.repeat.is_finite() {
+            self.repeat
+        } else {
+            1.0
+        };
+        self.begin + self.dur * repetitions
+    }
+}
+
+/// How long the animations of `source` run, or `None` if it has none.
+pub fn span(source: &[u8]) -> Option<Span> {
+    let text = std::str::from_utf8(source).ok()?;
+    let opts = roxmltree::ParsingOptions { allow_dtd: true };
+    let doc = roxmltree::Document::parse_with_options(text, opts).ok()?;
+    let timings: Vec<Timing> = doc
+        .descendants()
+        .filter(is_animation)
+        .filter_map(Timing::parse)
+        .collect();
+    if timings.is_empty() {
+        return None;
+    }
+    Some(Span {
+        end: timings.iter().map(Timing::end).fold(0.0, f64::max),
+        endless: timings.iter().any(|timing| timing.repeat.is_infinite()),
+    })
+}
+
+/// `source` with each animated attribute set to its value at `time`, in seconds, or
+/// `None` if nothing in it is animated.
+pub fn frame(source: &[u8], time: f64) -> Option<Vec<u8>> {
+    let text = std::str::from_utf8(source).ok()?;
+    let opts = roxmltree::ParsingOptions { allow_dtd: true };
+    let doc = roxmltree::Document::parse_with_options(text, opts).ok()?;
+
+    // the values of the animated attributes of each element, by the element's position;
+    // later animations win over earlier ones
+    let mut animated: HashMap<usize, (roxmltree::Node, Vec<(String, String)>)> = HashMap::new();
+    for node in doc.descendants().filter(is_animation) {
+        let target = match target(node) {
+            Some(target) => target,
+            None => continue,
+        };
+        let transform = node.tag_name().name() == "animateTransform";
+        let name = match node.attribute("attributeName") {
+            Some(name) => name,
+            None if transform => "transform",
+            None => continue,
+        };
+        let progress = match Timing::parse(node).and_then(|timing| timing.progress(time)) {
+            Some(progress) => progress,
+            None => continue,
+        };
+
+        let (_, values) = animated
+            .entry(target.range().start)
+            .or_insert_with(|| (target, vec![]));
+        let base = values
+            .iter()
+            .rev()
+            .find(|(animated, _)| animated == name)
+            .map(|(_, value)| value.clone())
+            .or_else(|| target.attribute(name).map(str::to_string));
+        let value = match node.tag_name().name() {
+            "set" => node.attribute("to").map(str::to_string),
+            // the base value is not a list of numbers, so a transform needs a `from`
+            "animateTransform" => value_at(node, None, progress).map(|value| {
+                let kind = node.attribute("type").unwrap_or("translate");
+                match &base {
+                    Some(base) if node.attribute("additive") == Some("sum") => {
+                        format!("{} {}({})", base, kind, value)
+                    }
+                    _ => format!("{}({})", kind, value),
+                }
+            }),
+            _ => value_at(node, base.as_deref(), progress),
+        };
+        if let Some(value) = value {
+            values.push((name.to_string(), value));
+        }
+    }
+    if animated.is_empty() {
+        return None;
+    }
+
+    let mut edits: Vec<(Range<usize>, String)> = vec![];
+    for (_, (element, values)) in animated {
+        // only the last value of each attribute counts
+        let mut written: Vec<&str> = vec![];
+        for (name, value) in values.iter().rev() {
+            if written.contains(&name.as_str()) {
+                continue;
+            }
+            written.push(name);
+            let existing = element
+                .attributes()
+                .iter()
+                .find(|a| a.name() == name && a.namespace().is_none());
+            match existing {
+                Some(attribute) => edits.push((attribute.value_range(), escape(value))),
+                None => {
+                    // right after the tag name
+                    let start = element.range().start + 1;
+                    let name_length = text[start..]
+                        .find(|c: char| c.is_whitespace() || c == '/' || c == '>')
+                        .unwrap_or(0);
+                    let at = start + name_length;
+                    edits.push((at..at, format!(" {}=\"{}\"", name, escape(value))));
+                }
+            }
+        }
+    }
+    edits.sort_by_key(|(range, _)| std::cmp::Reverse(range.start));
+    let mut text = text.to_string();
+    for (range, replacement) in edits {
+        text.replace_range(range, &replacement);
+    }
+    Some(text.into_bytes())
+}
+
+fn is_animation(node: &roxmltree::Node) -> bool {
+    matches!(
+        node.tag_name().name(),
+        "animate" | "set" | "animateTransform"
+    )
+}
+
+// the element an animation applies to: the one its `href` names, or else its parent
+fn target<'a, 'input>(node: roxmltree::Node<'a, 'input>) -> Option<roxmltree::Node<'a, 'input>> {
+    let href = node
+        .attribute("href")
+        .or_else(|| node.attribute((XLINK_NS, "href")));
+    match href {
+        Some(href) => {
+            let id = href.strip_prefix('#')?;
+            let root = node.document().root();
+            root.descendants().find(|n| n.attribute("id") == Some(id))
+        }
+        None => node.parent_element(),
+    }
+}
+
+// the value of an `<animate>` or `<animateTransform>` at `progress` through a repetition,
+// where `base` is the attribute's value without this animation
+fn value_at(node: roxmltree::Node, base: Option<&str>, progress: f64) -> Option<String> {
+    let values: Vec<String> = match node.attribute("values") {
+        Some(values) => values
+            .split(';')
+            .map(str::trim)
+            .filter(|value| !value.is_empty())
+            .map(str::to_string)
+            .collect(),
+        None => {
+            let from = node.attribute("from").or(base);
+            match (from, node.attribute("to"), node.attribute("by")) {
+                (Some(from), Some(to), _) => vec![from.to_string(), to.to_string()],
+                (Some(from), None, Some(by)) => {
+                    let sum: Vec<String> = numbers(from)?
+                        .iter()
+                        .zip(numbers(by)?)
+                        .map(|(from, by)| (from + by).to_string())
+                        .collect();
+                    vec![from.to_string(), sum.join(" ")]
+                }
+                _ => return None,
+            }
+        }
+    };
+    let last = values.len().checked_sub(1)?;
+    if last == 0 {
+        return values.into_iter().next();
+    }
+
+    let discrete = node.attribute("calcMode") == Some("discrete");
+    let key_times: Vec<f64> = node
+        .attribute("keyTimes")
+        .map(|times| {
+            times
+                .split(';')
+                .filter_map(|t| t.trim().parse().ok())
+                .collect()
+        })
+        .filter(|times: &Vec<f64>| times.len() == values.len())
+        .unwrap_or_else(|| {
+            // evenly spaced; discrete values each take an equal share of the time
+            let steps = if discrete { values.len() } else { last };
+            (0..values.len()).map(|i| i as f64 / steps as f64).collect()
+        });
+    let index = key_times
+        .iter()
+        .rposition(|time| *time <= progress)
+        .unwrap_or(0);
+    if discrete || index == last {
+        return Some(values[index].clone());
+    }
+    let (start, end) = (key_times[index], key_times[index + 1]);
+    let local = if end > start {
+        (progress - start) / (end - start)
+    } else {
+        1.0
+    };
+    Some(interpolate(&values[index], &values[index + 1], local))
+}
+
+fn interpolate(from: &str, to: &str, t: f64) -> String {
+    if let (Some(from), Some(to)) = (numbers(from), numbers(to)) {
+        if from.len() == to.len() {
+            let values: Vec<String> = from
+                .iter()
+                .zip(to)
+                .map(|(from, to)| (from + (to - from) * t).to_string())
+                .collect();
+            return values.join(" ");
+        }
+    }
+    let parsed: (Result<svgtypes::Color, _>, Result<svgtypes::Color, _>) =
+        (from.trim().parse(), to.trim().parse());
+    if let (Ok(from), Ok(to)) = parsed {
+        let channel = |from: u8, to: u8| (from as f64 + (to as f64 - from as f64) * t).round();
+        return format!(
+            "rgb({},{},{})",
+            channel(from.red, to.red),
+            channel(from.green, to.green),
+            channel(from.blue, to.blue)
+        );
+    }
+    // anything else changes at the end of the interval
+    from.to_string()
+}
+
+// the numbers of a value such as `0.5` or `90 12 12`
+fn numbers(value: &str) -> Option<Vec<f64>> {
+    let numbers: Option<Vec<f64>> = value
+        .split(|c: char| c.is_whitespace() || c == ',')
+        .filter(|part| !part.is_empty())
+        .map(|part| part.parse().ok())
+        .collect();
+    numbers.filter(|numbers| !numbers.is_empty())
+}
+
+/// Seconds in a SMIL clock value: `2s`, `500ms`, `1.5min`, `1h`, `01:30`, `0:01:30` or `2`.
+pub fn clock_value(value: &str) -> Option<f64> {
+    let value = value.trim();
+    let (number, unit) = if let Some(ms) = value.strip_suffix("ms") {
+        (ms, 0.001)
+    } else if let Some(min) = value.strip_suffix("min") {
+        (min, 60.0)
+    } else if let Some(s) = value.strip_suffix('s') {
+        (s, 1.0)
+    } else if let Some(h) = value.strip_suffix('h') {
+        (h, 3600.0)
+    } else if value.contains(':') {
+        return value.split(':').try_fold(0.0, |total, part| {
+            Some(total * 60.0 + part.parse::<f64>().ok()?)
+        });
+    } else {
+        (value, 1.0)
+    };
+    let seconds = number.trim().parse::<f64>().ok()? * unit;
+    seconds.is_finite().then_some(seconds)
+}
+
+fn escape(value: &str) -> String {
+    value
+        .replace('&', "&amp;")
+        .replace('<', "&lt;")
+        .replace('"', "&quot;")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // the first animation element of `markup`, parsed by `read`
+    fn with_animation<T>(markup: &str, read: impl FnOnce(roxmltree::Node) -> T) -> T {
+        let doc = roxmltree::Document::parse(markup).unwrap();
+        read(doc.descendants().find(is_animation).unwrap())
+    }
+
+    fn value(markup: &str, progress: f64) -> Option<String> {
+        with_animation(markup, |node| value_at(node, None, progress))
+    }
+
+    #[test]
+    fn clock_values() {
+        assert_eq!(clock_value("500ms"), Some(0.5));
+        assert_eq!(clock_value("1.5min"), Some(90.0));
+        assert_eq!(clock_value(" 2s "), Some(2.0));
+        assert_eq!(clock_value("1h"), Some(3600.0));
+        assert_eq!(clock_value("01:30"), Some(90.0));
+        assert_eq!(clock_value("0:01:30"), Some(90.0));
+        assert_eq!(clock_value("2"), Some(2.0));
+        for bad in ["", "fast", "1:x", "2 days", "1e999s"] {
+            assert_eq!(clock_value(bad), None, "{}", bad);
+        }
+    }
+
+    #[test]
+    fn from_to_and_values_are_interpolated() {
+        assert_eq!(
+            value(r#"<animate from="0" to="10"/>"#, 0.25).unwrap(),
+            "2.5"
+        );
+        assert_eq!(
+            value(r#"<animate from="0 0" to="10 20"/>"#, 0.5).unwrap(),
+            "5 10"
+        );
+        assert_eq!(value(r#"<animate from="2" by="4"/>"#, 0.5).unwrap(), "4");
+        let colors = r#"<animate from="red" to="blue"/>"#;
+        assert_eq!(value(colors, 0.5).unwrap(), "rgb(128,0,128)");
+        let values = r#"<animate values="0;10;20"/>"#;
+        assert_eq!(value(values, 0.75).unwrap(), "15");
+        assert_eq!(value(values, 1.0).unwrap(), "20");
+        // the base value stands in for a missing `from`
+        let to = with_animation(r#"<animate to="10"/>"#, |node| {
+            value_at(node, Some("4"), 0.5)
+        });
+        assert_eq!(to.unwrap(), "7");
+    }
+
+    #[test]
+    fn key_times_and_discrete_values() {
+        let key_times = r#"<animate values="0;10;20" keyTimes="0;0.8;1"/>"#;
+        assert_eq!(value(key_times, 0.4).unwrap(), "5");
+        assert_eq!(value(key_times, 0.9).unwrap(), "15");
+        // each value of a discrete animation takes an equal share of the time
+        let discrete = r#"<animate values="a;b;c" calcMode="discrete"/>"#;
+        assert_eq!(value(discrete, 0.2).unwrap(), "a");
+        assert_eq!(value(discrete, 0.5).unwrap(), "b");
+        assert_eq!(value(discrete, 0.9).unwrap(), "c");
+        // values that are neither numbers nor colors change at the end of the interval
+        assert_eq!(value(r#"<animate values="a;b"/>"#, 0.9).unwrap(), "a");
+    }
+
+    #[test]
+    fn fractional_repeats_freeze_part_way() {
+        let markup = r#"<animate begin="1s" dur="2s" repeatCount="1.5" fill="freeze"/>"#;
+        let timing = with_animation(markup, Timing::parse).unwrap();
+        assert_eq!(timing.progress(0.5), None);
+        assert_eq!(timing.progress(2.0), Some(0.5));
+        assert_eq!(timing.progress(3.5), Some(0.25));
+        assert_eq!(timing.progress(10.0), Some(0.5));
+        assert_eq!(timing.end(), 4.0);
+        let removed = r#"<animate dur="2s" repeatCount="1.5"/>"#;
+        let timing = with_animation(removed, Timing::parse).unwrap();
+        assert_eq!(timing.progress(10.0), None);
+    }
+
+    #[test]
+    fn frames_set_existing_and_missing_attributes() {
+        let source = r#"<svg><rect width="1"><animate attributeName="width" from="0" to="10" dur="1s"/><animate attributeName="height" from="4" to="8" dur="1s"/></rect></svg>"#;
+        let output = String::from_utf8(frame(source.as_bytes(), 0.5).unwrap()).unwrap();
+        assert!(
+            output.starts_with(r#"<svg><rect height="6" width="5">"#),
+            "{}",
+            output
+        );
+        assert_eq!(frame(b"<svg><rect/></svg>", 0.5), None);
+    }
+}
@@ -0,0 +1,663 @@
+//! Command line arguments.
+
+use crate::animate;
+use crate::background;
+use crate::color::Profile;
+use crate::config::Config;
+use crate::document::svgs_in;
+use crate::error::Failure;
+use crate::fit::FitMode;
+use crate::fonts::Fonts;
+use crate::keymap::Keymap;
+use crate::mask::MaskShape;
+use crate::output::OutputColor;
+use crate::recolor;
+use crate::render::Backend;
+use anyhow::Context;
+use clap::parser::ValueSource;
+use clap::{CommandFactory, FromArgMatches, Parser, Subcommand};
+use std::path::{Path, PathBuf};
+use std::time::Duration;
+
+/// A minimal, reloading SVG viewer a la feh.
+#[derive(Parser)]
+#[command(
+    version,
+    args_conflicts_with_subcommands = true,
+    override_usage = "svgview [OPTIONS] [INPUT]...\n       \
+                      svgview [OPTIONS] --svg <MARKUP>\n       \
+                      svgview [OPTIONS] --watch-dir <DIR>\n       \
+                      svgview export [OPTIONS] <INPUT> <OUTPUT>"
+)]
+pub struct Args {
+    #[command(subcommand)]
+    pub command: Option<Command>,
+
+    /// SVG files, glob patterns or directories of SVGs to show and reload on change, an
+    /// http(s) URL to fetch again on an interval, or `-` (the default) to read a single SVG
+    /// from stdin
+    pub input: Vec<String>,
+
+    /// Show the SVGs listed in this file, one path per line, relative to the file, in that
+    /// order; blank lines and lines starting with `#` are skipped
+    #[arg(long, value_name = "FILE", conflicts_with_all = ["input", "svg", "watch_dir"])]
+    pub manifest: Option<PathBuf>,
+
+    /// Show SVG markup given on the command line
+    #[arg(long, value_name = "MARKUP", conflicts_with_all = ["input", "watch_dir"])]
+    pub svg: Option<String>,
+
+    /// Take commands such as `{"load": "file.svg"}`, one JSON object per line, on a Unix
+    /// socket created at this path
+    #[arg(long, value_name = "SOCKET")]
+    pub control: Option<PathBuf>,
+
+    /// Show the file in the window of an svgview started with this option before, if one
+    /// is still open, rather than in a new one
+    #[arg(long)]
+    pub single_instance: bool,
+
+    /// Open a new window even if the config file sets `single-instance`
+    #[arg(long, overrides_with = "single_instance")]
+    no_single_instance: bool,
+
+    /// Hide the window when it is closed and keep watching, until an invocation with
+    /// `--single-instance` shows it again; `Escape` still quits
+    #[arg(long)]
+    pub hide_on_close: bool,
+
+    /// Quit when the window is closed even if the config file sets `hide-on-close`
+    #[arg(long, overrides_with = "hide_on_close")]
+    no_hide_on_close: bool,
+
+    /// Show an icon in the system tray that shows the window when clicked, with a menu to
+    /// reopen the last file, pause watching and quit; needs the `tray` feature
+    #[arg(long)]
+    pub tray: bool,
+
+    /// Show no tray icon even if the config file sets `tray`
+    #[arg(long, overrides_with = "tray")]
+    no_tray: bool,
+
+    /// Show the most recently modified SVG in a directory and follow new ones
+    #[arg(
+        long,
+        value_name = "DIR",
+        conflicts_with = "input",
+        help_heading = "Watching"
+    )]
+    pub watch_dir: Option<PathBuf>,
+
+    /// Read defaults from this file instead of ~/.config/svgview/config.toml
+    #[arg(long, value_name = "FILE")]
+    pub config: Option<PathBuf>,
+
+    /// Check for changes every this many seconds instead of relying on file system
+    /// notifications, which network shares and container volumes often lack
+    #[arg(
+        long,
+        value_name = "SECONDS",
+        value_parser = parse_interval,
+        num_args = 0..=1,
+        require_equals = true,
+        default_missing_value = "0.5",
+        global = true,
+        help_heading = "Watching"
+    )]
+    pub poll: Option<Duration>,
+
+    /// Raise the window whenever the file is reloaded
+    #[arg(long, help_heading = "Watching")]
+    pub focus_follows_file: bool,
+
+    /// Leave the window where it is on reloads even if the config file sets
+    /// `focus-follows-file`
+    #[arg(long, overrides_with = "focus_follows_file", help_heading = "Watching")]
+    no_focus_follows_file: bool,
+
+    /// Briefly highlight the pixels changed by a reload
+    #[arg(long, help_heading = "Watching")]
+    pub flash_changes: bool,
+
+    /// Do not highlight changed pixels even if the config file sets `flash-changes`
+    #[arg(long, overrides_with = "flash_changes", help_heading = "Watching")]
+    no_flash_changes: bool,
+
+    /// Load the system's fonts even if the config file sets `skip-system-fonts`
+    #[arg(
+        long = "no-skip-system-fonts",
+        overrides_with = "skip_system",
+        global = true,
+        help_heading = "Rendering"
+    )]
+    no_skip_system: bool,
+
+    /// Disable antialiasing while zoomed in past this zoom level
+    #[arg(long, value_name = "ZOOM", help_heading = "Rendering")]
+    pub antialias_off_below: Option<f64>,
+
+    /// Clip the SVG to an app icon shape
+    #[arg(long, value_enum, help_heading = "Rendering")]
+    pub mask: Option<MaskShape>,
+
+    /// Corner radius of the rounded mask, as a fraction of the shorter side
+    #[arg(
+        long,
+        value_name = "FRACTION",
+        default_value_t = 0.2,
+        help_heading = "Rendering"
+    )]
+    pub mask_radius: f32,
+
+    /// Apply an affine matrix to the fitted SVG, in the order of SVG's matrix()
+    #[arg(
+        long,
+        value_name = "A,B,C,D,E,F",
+        value_parser = parse_transform,
+        default_value = "1,0,0,1,0,0",
+        hide_default_value = true,
+        help_heading = "Rendering"
+    )]
+    pub transform: tiny_skia::Transform,
+
+    /// Replace solid fill and stroke colors, e.g. `#000000=#ffffff,red=lime`
+    #[arg(long, value_name = "FROM=TO,...", value_parser = parse_recolor, help_heading = "Rendering")]
+    pub recolor: Option<recolor::ColorMap>,
+
+    /// Color behind the SVG, e.g. `black`, `#808080` or `transparent`
+    #[arg(
+        long,
+        value_name = "COLOR",
+        value_parser = parse_background,
+        default_value = "white",
+        help_heading = "Rendering"
+    )]
+    pub background: tiny_skia::Color,
+
+    /// Preview the SVG as on a display with this pixel ratio
+    #[arg(long, value_name = "RATIO", value_parser = parse_pixel_ratio, help_heading = "Rendering")]
+    pub pixel_ratio: Option<f64>,
+
+    /// Color space of the display: `srgb`, `display-p3` or an ICC profile file [default: srgb]
+    #[arg(
+        long,
+        value_name = "PROFILE",
+        value_parser = parse_color_profile,
+        help_heading = "Rendering"
+    )]
+    pub color_profile: Option<Profile>,
+
+    /// How the SVG is sized to the window at startup [default: fit]
+    #[arg(long, value_enum, env = "SVGVIEW_FIT", help_heading = "Rendering")]
+    pub fit: Option<FitMode>,
+
+    /// Start at this zoom of the SVG's intrinsic size, e.g. 2 for 200%, rather than as
+    /// `--fit` sizes it
+    #[arg(
+        long,
+        value_name = "ZOOM",
+        value_parser = parse_pixel_ratio,
+        env = "SVGVIEW_ZOOM",
+        help_heading = "Rendering"
+    )]
+    pub zoom: Option<f64>,
+
+    /// Keep the zoom when showing another file or a pasted SVG, only centering it, rather
+    /// than fitting it to the window again
+    #[arg(long, help_heading = "Rendering")]
+    pub center: bool,
+
+    /// What renders the SVG in the window
+    #[arg(long, value_enum, default_value_t = Backend::Cpu, help_heading = "Rendering")]
+    pub backend: Backend,
+
+    /// Give up on a render in the window that takes longer than this many seconds once the
+    /// view or the document changes again, keeping the last frame
+    #[arg(
+        long,
+        value_name = "SECONDS",
+        value_parser = parse_interval,
+        default_value = "10",
+        help_heading = "Rendering"
+    )]
+    pub render_timeout: Duration,
+
+    /// Render at this fraction of the resolution while the view is dragged or zoomed with
+    /// the wheel, then at the full one once it rests; 1 always renders at full resolution
+    #[arg(
+        long,
+        value_name = "FRACTION",
+        value_parser = parse_fraction,
+        default_value_t = 0.5,
+        help_heading = "Rendering"
+    )]
+    pub interactive_density: f32,
+
+    #[command(flatten)]
+    pub fonts: Fonts,
+
+    /// Keys for the viewer's actions, from the config file
+    #[arg(skip)]
+    pub keymap: Keymap,
+
+    /// Only show the n-th top-level group, counting from 0
+    #[arg(long, value_name = "N", help_heading = "Rendering")]
+    pub page: Option<usize>,
+
+    /// Advance to the next file every this many seconds, looping at the end
+    #[arg(long, value_name = "SECONDS", value_parser = parse_interval, help_heading = "Window")]
+    pub slideshow: Option<Duration>,
+
+    /// Start with the window maximized
+    #[arg(long, help_heading = "Window")]
+    pub maximized: bool,
+
+    /// Start in borderless fullscreen on the current monitor
+    #[arg(long, help_heading = "Window")]
+    pub fullscreen: bool,
+
+    /// Keep the window above other windows
+    #[arg(long, help_heading = "Window")]
+    pub always_on_top: bool,
+
+    /// Open the window without a title bar and borders; drag with the right mouse button
+    /// to move it
+    #[arg(long, help_heading = "Window")]
+    pub borderless: bool,
+
+    /// Treat the display as having this many pixels per logical pixel, instead of asking
+    /// the window system
+    #[arg(long, value_name = "FACTOR", value_parser = parse_pixel_ratio, help_heading = "Window")]
+    pub override_scale: Option<f64>,
+
+    /// Start at this percentage of the monitor size (10-100)
+    #[arg(long, value_name = "N", value_parser = parse_size_percent, help_heading = "Window")]
+    pub size_percent: Option<f64>,
+
+    /// Write premultiplied RGBA8 pixels to stdout and exit
+    #[arg(long, help_heading = "Output")]
+    pub raw: bool,
+
+    /// Write the normalized SVG to a file and exit
+    #[arg(long, value_name = "FILE.svg", help_heading = "Output")]
+    pub output: Option<PathBuf>,
+
+    /// Print startup timings to stderr
+    #[arg(long, help_heading = "Output")]
+    pub profile: bool,
+}
+
+#[derive(Subcommand)]
+pub enum Command {
+    /// Rasterize an SVG to a PNG, or convert it to a PDF, without opening a window
+    Export(ExportArgs),
+    /// Render the animation of an SVG to an animated GIF or PNG without opening a window
+    Record(RecordArgs),
+    /// Rasterize an SVG to square PNGs of several sizes, as for app icons
+    ExportIcons(ExportIconsArgs),
+    /// Serve the render as a PNG, and the SVG, over HTTP, rendering again on changes
+    Serve(ServeArgs),
+}
+
+#[derive(clap::Args)]
+pub struct ExportArgs {
+    /// SVG file to export
+    pub input: PathBuf,
+
+    /// PNG or PDF file to write
+    pub output: PathBuf,
+
+    /// Width of the image; the height follows the aspect ratio unless given too
+    #[arg(long)]
+    pub width: Option<u32>,
+
+    /// Height of the image; the width follows the aspect ratio unless given too
+    #[arg(long)]
+    pub height: Option<u32>,
+
+    /// Scale the SVG's intrinsic size by this factor, if no width or height is given
+    #[arg(long, conflicts_with_all = ["width", "height"])]
+    pub zoom: Option<f32>,
+
+    /// Make the PNG this ratio of width to height, e.g. `16:9`, with the SVG fitted inside
+    /// and centered on the background; the width or height gives its size
+    #[arg(long, value_name = "W:H", value_parser = parse_aspect)]
+    pub aspect: Option<(u32, u32)>,
+
+    /// Channels of the PNG; without alpha, what the background leaves transparent is
+    /// composited over white
+    #[arg(long, value_enum, default_value_t = OutputColor::Rgba)]
+    pub output_color: OutputColor,
+
+    /// Color behind the SVG, e.g. `white` or `#808080`
+    #[arg(long, value_name = "COLOR", value_parser = parse_background, default_value = "transparent")]
+    pub background: tiny_skia::Color,
+
+    /// Clip the SVG to an app icon shape
+    #[arg(long, value_enum)]
+    pub mask: Option<MaskShape>,
+
+    /// Corner radius of the rounded mask, as a fraction of the shorter side
+    #[arg(long, value_name = "FRACTION", default_value_t = 0.2)]
+    pub mask_radius: f32,
+
+    /// Apply an affine matrix to the fitted SVG, in the order of SVG's matrix()
+    #[arg(
+        long,
+        value_name = "A,B,C,D,E,F",
+        value_parser = parse_transform,
+        default_value = "1,0,0,1,0,0",
+        hide_default_value = true
+    )]
+    pub transform: tiny_skia::Transform,
+}
+
+#[derive(clap::Args)]
+pub struct RecordArgs {
+    /// SVG file to render
+    pub input: PathBuf,
+
+    /// GIF or animated PNG (`.png` or `.apng`) file to write
+    pub output: PathBuf,
+
+    /// Length of the recording, e.g. `2s` or `500ms`; by default until the last animation
+    /// ends, or ends its first repetition if it repeats forever
+    #[arg(long, value_name = "TIME", value_parser = parse_clock_value)]
+    pub duration: Option<f64>,
+
+    /// Frames per second
+    #[arg(long, default_value_t = 30, value_parser = clap::value_parser!(u16).range(1..=100))]
+    pub fps: u16,
+
+    /// Width of the frames; the height follows the aspect ratio unless given too
+    #[arg(long)]
+    pub width: Option<u32>,
+
+    /// Height of the frames; the width follows the aspect ratio unless given too
+    #[arg(long)]
+    pub height: Option<u32>,
+
+    /// Scale the SVG's intrinsic size by this factor, if no width or height is given
+    #[arg(long, conflicts_with_all = ["width", "height"])]
+    pub zoom: Option<f32>,
+
+    /// Color behind the SVG, e.g. `white` or `#808080`
+    #[arg(long, value_name = "COLOR", value_parser = parse_background, default_value = "transparent")]
+    pub background: tiny_skia::Color,
+}
+
+#[derive(clap::Args)]
+pub struct ExportIconsArgs {
+    /// SVG file to rasterize
+    pub input: PathBuf,
+
+    /// Widths of the icons in pixels, e.g. `16,32,48`
+    #[arg(
+        long,
+        required = true,
+        value_delimiter = ',',
+        value_parser = clap::value_parser!(u32).range(1..)
+    )]
+    pub sizes: Vec<u32>,
+
+    /// Directory to write <name>-<size>x<size>.png to, created if missing
+    #[arg(long, value_name = "DIR", default_value = ".")]
+    pub out_dir: PathBuf,
+
+    /// Color behind the SVG, e.g. `white` or `#808080`
+    #[arg(long, value_name = "COLOR", value_parser = parse_background, default_value = "transparent")]
+    pub background: tiny_skia::Color,
+
+    /// Clip the icons to an app icon shape
+    #[arg(long, value_enum)]
+    pub mask: Option<MaskShape>,
+
+    /// Corner radius of the rounded mask, as a fraction of the shorter side
+    #[arg(long, value_name = "FRACTION", default_value_t = 0.2)]
+    pub mask_radius: f32,
+}
+
+#[derive(clap::Args)]
+pub struct ServeArgs {
+    /// SVG file to serve
+    pub input: PathBuf,
+
+    /// Port to listen on
+    #[arg(long, default_value_t = 7878)]
+    pub port: u16,
+
+    /// Address to listen on; all interfaces by default, so that others can connect
+    #[arg(long, default_value = "0.0.0.0")]
+    pub address: String,
+
+    /// Width of the render; the height follows the aspect ratio unless given too
+    #[arg(long)]
+    pub width: Option<u32>,
+
+    /// Height of the render; the width follows the aspect ratio unless given too
+    #[arg(long)]
+    pub height: Option<u32>,
+
+    /// Scale the SVG's intrinsic size by this factor, if no width or height is given
+    #[arg(long, conflicts_with_all = ["width", "height"])]
+    pub zoom: Option<f32>,
+
+    /// Color behind the SVG, e.g. `white` or `#808080`
+    #[arg(long, value_name = "COLOR", value_parser = parse_background, default_value = "transparent")]
+    pub background: tiny_skia::Color,
+}
+
+impl Args {
+    /// Parses the command line, and takes what it leaves unset from the config file, then
+    /// from the `SVGVIEW_*` environment variables, then the defaults.
+    pub fn load() -> anyhow::Result<Self> {
+        let matches = Self::command().get_matches();
+        let mut args = Self::from_arg_matches(&matches).unwrap_or_else(|e| e.exit());
+        let config = Config::load(args.config.as_deref())?;
+        args.background = layer(&matches, "background", args.background, config.background);
+        args.fit = layer(&matches, "fit", args.fit, config.fit.map(Some));
+        args.single_instance = flag(&matches, "single_instance", config.single_instance);
+        args.hide_on_close = flag(&matches, "hide_on_close", config.hide_on_close);
+        args.tray = flag(&matches, "tray", config.tray);
+        args.color_profile = args.color_profile.or(config.color_profile);
+        // the config file's directories come first, as the command line adds to them
+        args.fonts.dirs = config
+            .font_dirs
+            .into_iter()
+            .chain(args.fonts.dirs)
+            .collect();
+        args.fonts.skip_system = flag(&matches, "skip_system", config.skip_system_fonts);
+        args.keymap = Keymap::with(config.keys);
+        args.poll = args.poll.or(config.watch.poll);
+        args.flash_changes = flag(&matches, "flash_changes", config.watch.flash_changes);
+        let focus_follows_file = config.watch.focus_follows_file;
+        args.focus_follows_file = flag(&matches, "focus_follows_file", focus_follows_file);
+        Ok(args)
+    }
+
+    /// The files of the `--manifest`, or else the input files in command line order, with
+    /// glob patterns expanded and directories replaced by the SVGs inside them in
+    /// alphabetical order.
+    pub fn playlist(&self) -> anyhow::Result<Vec<PathBuf>> {
+        if let Some(manifest) = &self.manifest {
+            let text = std::fs::read_to_string(manifest).with_context(|| {
+                Failure::Input(format!("could not read {}", manifest.display()))
+            })?;
+            let dir = manifest.parent().unwrap_or(Path::new(""));
+            return Ok(manifest_paths(&text, dir));
+        }
+        Ok(self
+            .input
+            .iter()
+            .flat_map(|input| match glob::glob(input) {
+                Ok(paths) if input.contains(['*', '?', '[']) => {
+                    paths.filter_map(|path| path.ok()).collect()
+                }
+                _ if Path::new(input).is_dir() => svgs_in(Path::new(input)),
+                _ => vec![PathBuf::from(input)],
+            })
+            .collect())
+    }
+}
+
+// the paths listed in a manifest, one per line, relative to its directory `dir`
+fn manifest_paths(text: &str, dir: &Path) -> Vec<PathBuf> {
+    text.lines()
+        .map(str::trim)
+        .filter(|line| !line.is_empty() && !line.starts_with('#'))
+        .map(|line| dir.join(line))
+        .collect()
+}
+
+// the value parsed for the option `id` if it was given on the command line, or else that
+// of the config file, if any; clap already took the environment and the default otherwise
+fn layer<T>(matches: &clap::ArgMatches, id: &str, parsed: T, config: Option<T>) -> T {
+    match matches.value_source(id) {
+        Some(ValueSource::CommandLine) => parsed,
+        _ => config.unwrap_or(parsed),
+    }
+}
+
+// whether the flag `id` is set: on if given on the command line, off if its `--no-`
+// counterpart is, and else as the config file sets it
+fn flag(matches: &clap::ArgMatches, id: &str, config: bool) -> bool {
+    let given = |id: &str| matches.value_source(id) == Some(ValueSource::CommandLine);
+    if given(id) {
+        true
+    } else if given(&format!("no_{}", id)) {
+        false
+    } else {
+        config
+    }
+}
+
+// six comma separated numbers in the order of SVG's matrix(a,b,c,d,e,f)
+fn parse_transform(value: &str) -> Result<tiny_skia::Transform, String> {
+    let values = value
+        .split(',')
+        .map(|v| v.trim().parse::<f32>().ok().filter(|v| v.is_finite()))
+        .collect::<Option<Vec<_>>>();
+    match values.as_deref() {
+        Some(&[a, b, c, d, e, f]) => Ok(tiny_skia::Transform::from_row(a, b, c, d, e, f)),
+        _ => Err("expected six numbers a,b,c,d,e,f".to_string()),
+    }
+}
+
+fn parse_recolor(value: &str) -> Result<recolor::ColorMap, String> {
+    recolor::parse_map(value).ok_or_else(|| "expected from=to[,from=to...]".to_string())
+}
+
+fn parse_background(value: &str) -> Result<tiny_skia::Color, String> {
+    background::parse_color(value).ok_or_else(|| format!("`{}` is not a color", value))
+}
+
+fn parse_color_profile(value: &str) -> Result<Profile, String> {
+    Ok(Profile::parse(value))
+}
+
+fn parse_pixel_ratio(value: &str) -> Result<f64, String> {
+    match value.parse::<f64>() {
+        Ok(ratio) if ratio > 0.0 => Ok(ratio),
+        _ => Err("must be a positive number".to_string()),
+    }
+}
+
+fn parse_fraction(value: &str) -> Result<f32, String> {
+    match value.parse::<f32>() {
+        Ok(fraction) if fraction > 0.0 && fraction <= 1.0 => Ok(fraction),
+        _ => Err("must be a number above 0 and at most 1".to_string()),
+    }
+}
+
+fn parse_interval(value: &str) -> Result<Duration, String> {
+    match value.parse::<f64>() {
+        Ok(seconds) if seconds > 0.0 && seconds.is_finite() => Ok(Duration::from_secs_f64(seconds)),
+        _ => Err("must be a positive number of seconds".to_string()),
+    }
+}
+
+fn parse_aspect(value: &str) -> Result<(u32, u32), String> {
+    let ratio = value.split_once(':').and_then(|(width, height)| {
+        let (width, height) = (width.trim().parse().ok()?, height.trim().parse().ok()?);
+        (width > 0 && height > 0).then_some((width, height))
+    });
+    ratio.ok_or_else(|| "expected a ratio of two positive whole numbers such as `16:9`".into())
+}
+
+fn parse_clock_value(value: &str) -> Result<f64, String> {
+    match animate::clock_value(value) {
+        Some(seconds) if seconds > 0.0 => Ok(seconds),
+        _ => Err("expected a positive time such as `2s` or `500ms`".to_string()),
+    }
+}
+
+fn parse_size_percent(value: &str) -> Result<f64, String> {
+    value
+        .parse::<f64>()
+        .map(|percent| percent.clamp(10.0, 100.0))
+        .map_err(|err| err.to_string())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn manifest_paths_are_relative_to_the_manifest() {
+        let text = "# review set\nb.svg\n\n  sub/a.svg  \n/abs/c.svg\n";
+        assert_eq!(
+            manifest_paths(text, Path::new("/set")),
+            [
+                PathBuf::from("/set/b.svg"),
+                PathBuf::from("/set/sub/a.svg"),
+                PathBuf::from("/abs/c.svg"),
+            ]
+        );
+    }
+
+    #[test]
+    fn flags_override_the_config_file_either_way() {
+        let flag_of = |argv: &[&str], id: &str, config: bool| {
+            let matches = Args::command().try_get_matches_from(argv).unwrap();
+            flag(&matches, id, config)
+        };
+        assert!(flag_of(&["svgview"], "flash_changes", true));
+        assert!(!flag_of(
+            &["svgview", "--no-flash-changes"],
+            "flash_changes",
+            true
+        ));
+        assert!(flag_of(
+            &["svgview", "--single-instance"],
+            "single_instance",
+            false
+        ));
+        // the last of a flag and its counterpart wins
+        let argv = ["svgview", "--skip-system-fonts", "--no-skip-system-fonts"];
+        assert!(!flag_of(&argv, "skip_system", true));
+        let argv = ["svgview", "--no-hide-on-close", "--hide-on-close"];
+        assert!(flag_of(&argv, "hide_on_close", false));
+        assert!(!flag_of(&["svgview", "--no-tray"], "tray", true));
+    }
+
+    #[test]
+    fn transforms_take_six_numbers() {
+        let ts = parse_transform("1, 0.5,-2,1e1,3,4").unwrap();
+        assert_eq!(
+            ts,
+            tiny_skia::Transform::from_row(1.0, 0.5, -2.0, 10.0, 3.0, 4.0)
+        );
+        assert!(parse_transform("1,0,0,1,0").is_err());
+        assert!(parse_transform("1,0,0,1,0,0,0").is_err());
+        assert!(parse_transform("1,0,0,1,0,x").is_err());
+        assert!(parse_transform("1,0,0,1,0,inf").is_err());
+    }
+
+    #[test]
+    fn manifest_next_to_the_working_directory() {
+        assert_eq!(
+            manifest_paths("a.svg\r\n", Path::new("")),
+            [PathBuf::from("a.svg")]
+        );
+    }
+}
@@ -0,0 +1,72 @@
+//! Converting renders from sRGB, the color space of SVG, to the color space of the display
+//! for `--color-profile`, so that colors on a wide-gamut display look as in a browser that
+//! manages colors instead of more saturated.
+
+use crate::error::Failure;
+use anyhow::{Context, Result};
+use log::warn;
+use moxcms::{ColorProfile, Layout, Transform8BitExecutor, TransformOptions};
+use std::path::PathBuf;
+use std::sync::Arc;
+
+/// The color space of a display.
+#[derive(Clone, Debug)]
+pub enum Profile {
+    Srgb,
+    DisplayP3,
+    /// An ICC profile, e.g. the one the operating system uses for the display.
+    File(PathBuf),
+}
+
+impl Profile {
+    /// `srgb` and `display-p3` name the built-in profiles; anything else is a file.
+    pub fn parse(text: &str) -> Self {
+        match text.to_ascii_lowercase().as_str() {
+            "srgb" => Profile::Srgb,
+            "display-p3" => Profile::DisplayP3,
+            _ => Profile::File(PathBuf::from(text)),
+        }
+    }
+}
+
+/// Converts sRGB pixels to the color space of a `Profile`.
+pub struct Converter {
+    // None for sRGB, which needs no conversion
+    transform: Option<Arc<Transform8BitExecutor>>,
+}
+
+impl Converter {
+    pub fn new(profile: &Profile) -> Result<Self> {
+        let display = match profile {
+            Profile::Srgb => return Ok(Self { transform: None }),
+            Profile::DisplayP3 => ColorProfile::new_display_p3(),
+            Profile::File(path) => {
+                let data = std::fs::read(path).with_context(|| {
+                    Failure::Input(format!("could not read {}", path.display()))
+                })?;
+                ColorProfile::new_from_slice(&data).map_err(|e| {
+                    Failure::Input(format!("{} is not an ICC profile: {}", path.display(), e))
+                })?
+            }
+        };
+        let options = TransformOptions::default();
+        let transform = ColorProfile::new_srgb()
+            .create_transform_8bit(Layout::Rgba, &display, Layout::Rgba, options)
+            .map_err(|e| Failure::Input(format!("unsupported color profile: {}", e)))?;
+        Ok(Self {
+            transform: Some(transform),
+        })
+    }
+
+    /// Writes the RGBA `pixels` to `output`, of the same length, in the display's colors.
+    pub fn convert(&self, pixels: &[u8], output: &mut [u8]) {
+        let transform = match &self.transform {
+            Some(transform) => transform,
+            None => return output.copy_from_slice(pixels),
+        };
+        if let Err(e) = transform.transform(pixels, output) {
+            warn!("Could not convert the colors for the display: {}", e);
+            output.copy_from_slice(pixels);
+        }
+    }
+}
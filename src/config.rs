@@ -0,0 +1,118 @@
+//! Defaults from a configuration file, which command line options override.
+//!
+//! The file is `svgview/config.toml` in `$XDG_CONFIG_HOME`, or in `~/.config` if that is
+//! unset, unless `--config` names another one. A missing default file is not an error.
+
+use crate::background;
+use crate::color::Profile;
+use crate::error::Failure;
+use crate::fit::FitMode;
+use crate::keymap::{Action, Chord};
+use anyhow::{Context, Result};
+use serde::de::{Deserializer, Error};
+use serde::Deserialize;
+use std::collections::HashMap;
+use std::path::{Path, PathBuf};
+use std::time::Duration;
+
+#[derive(Deserialize, Default)]
+#[serde(default, deny_unknown_fields, rename_all = "kebab-case")]
+pub struct Config {
+    /// Color behind the SVG in the window, as for `--background`.
+    #[serde(deserialize_with = "color")]
+    pub background: Option<tiny_skia::Color>,
+    /// How the SVG is sized to the window at startup, as for `--fit`.
+    pub fit: Option<FitMode>,
+    /// Color space of the display, as for `--color-profile`; a file is relative to the
+    /// directory of the config file.
+    #[serde(deserialize_with = "color_profile")]
+    pub color_profile: Option<Profile>,
+    /// Directories to load fonts from in addition to the system fonts, relative to the
+    /// directory of the config file.
+    pub font_dirs: Vec<PathBuf>,
+    /// Only load the fonts of `font_dirs`, as for `--skip-system-fonts`.
+    pub skip_system_fonts: bool,
+    /// Open files in the running viewer, as for `--single-instance`.
+    pub single_instance: bool,
+    /// Hide the window instead of quitting when it is closed, as for `--hide-on-close`.
+    pub hide_on_close: bool,
+    /// Show a tray icon, as for `--tray`.
+    pub tray: bool,
+    pub watch: Watch,
+    /// Keys for each action, replacing its default keys.
+    pub keys: HashMap<Action, Vec<Chord>>,
+}
+
+#[derive(Deserialize, Default)]
+#[serde(default, deny_unknown_fields, rename_all = "kebab-case")]
+pub struct Watch {
+    /// Seconds between checks for changes, as for `--poll`.
+    #[serde(deserialize_with = "interval")]
+    pub poll: Option<Duration>,
+    pub flash_changes: bool,
+    pub focus_follows_file: bool,
+}
+
+impl Config {
+    /// Reads `path`, or the default file if `None`.
+    pub fn load(path: Option<&Path>) -> Result<Self> {
+        let (path, required) = match path {
+            Some(path) => (path.to_path_buf(), true),
+            None => match default_path() {
+                Some(path) => (path, false),
+                None => return Ok(Self::default()),
+            },
+        };
+        let text = match std::fs::read_to_string(&path) {
+            Ok(text) => text,
+            Err(e) if !required && e.kind() == std::io::ErrorKind::NotFound => {
+                return Ok(Self::default())
+            }
+            Err(e) => {
+                return Err(e)
+                    .with_context(|| Failure::Input(format!("could not read {}", path.display())))
+            }
+        };
+        let mut config: Self = toml::from_str(&text)
+            .with_context(|| format!("invalid config file {}", path.display()))?;
+        if let Some(dir) = path.parent() {
+            config.font_dirs = config
+                .font_dirs
+                .iter()
+                .map(|font_dir| dir.join(font_dir))
+                .collect();
+            if let Some(Profile::File(file)) = &mut config.color_profile {
+                *file = dir.join(&file);
+            }
+        }
+        Ok(config)
+    }
+}
+
+fn default_path() -> Option<PathBuf> {
+    let dir = std::env::var_os("XDG_CONFIG_HOME")
+        .filter(|dir| !dir.is_empty())
+        .map(PathBuf::from)
+        .or_else(|| Some(PathBuf::from(std::env::var_os("HOME")?).join(".config")))?;
+    Some(dir.join("svgview").join("config.toml"))
+}
+
+fn color<'de, D: Deserializer<'de>>(deserializer: D) -> Result<Option<tiny_skia::Color>, D::Error> {
+    let value = String::deserialize(deserializer)?;
+    background::parse_color(&value)
+        .map(Some)
+        .ok_or_else(|| D::Error::custom(format!("`{}` is not a color", value)))
+}
+
+fn color_profile<'de, D: Deserializer<'de>>(deserializer: D) -> Result<Option<Profile>, D::Error> {
+    Ok(Some(Profile::parse(&String::deserialize(deserializer)?)))
+}
+
+fn interval<'de, D: Deserializer<'de>>(deserializer: D) -> Result<Option<Duration>, D::Error> {
+    let seconds = f64::deserialize(deserializer)?;
+    if seconds > 0.0 && seconds.is_finite() {
+        Ok(Some(Duration::from_secs_f64(seconds)))
+    } else {
+        Err(D::Error::custom("must be a positive number of seconds"))
+    }
+}
@@ -0,0 +1,264 @@
+//! `--control <socket>`: commands from editors and scripts over a Unix domain socket, one
+//! JSON object per line, such as `{"load": "drawing.svg"}`, `{"zoom": 2.0}`,
+//! `{"export": "out.png"}` or `{"quit": true}`. Each command is answered with a line of
+//! its own, `{"ok": true}` or `{"error": "<why>"}`, once the viewer has carried it out.
+//!
+//! Relative paths are taken from the viewer's working directory, not the client's.
+//!
+//! With `--single-instance`, the first viewer listens on a socket of its own as well, and
+//! later invocations [`send`] it the file to show instead of opening another window.
+
+use crate::UserEvent;
+use serde::{Deserialize, Serialize};
+use std::path::{Path, PathBuf};
+use std::sync::mpsc::Sender;
+use winit::event_loop::EventLoopProxy;
+
+#[derive(Debug, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum Command {
+    /// Show this file, or the SVGs of this directory, instead of the current document.
+    Load(PathBuf),
+    /// Load, then raise the window, for a file opened from another invocation.
+    Open(PathBuf),
+    /// Zoom to this many times the intrinsic size, as the title shows it.
+    Zoom(f32),
+    /// Write the shown frame as a PNG, or the document as a PDF, by the extension.
+    Export(PathBuf),
+    #[serde(with = "quit")]
+    Quit,
+}
+
+/// What the viewer answers a command with: nothing, or why it could not carry it out.
+pub type Reply = Result<(), String>;
+
+/// Accepts connections on a new socket at `path` on a thread of its own, forwarding their
+/// commands to the event loop.
+#[cfg(unix)]
+pub fn listen(path: &Path, evp: EventLoopProxy<UserEvent>) -> anyhow::Result<()> {
+    use anyhow::{bail, Context};
+    use std::os::unix::net::{UnixListener, UnixStream};
+
+    // a socket left behind by a viewer that did not exit cleanly is replaced, one that
+    // is still answered is not
+    if path.exists() {
+        if UnixStream::connect(path).is_ok() {
+            bail!("{} is in use by another viewer", path.display());
+        }
+        std::fs::remove_file(path)
+            .with_context(|| format!("could not replace {}", path.display()))?;
+    }
+    let listener = UnixListener::bind(path)
+        .with_context(|| format!("could not listen on {}", path.display()))?;
+    std::thread::spawn(move || {
+        for stream in listener.incoming().flatten() {
+            let evp = evp.clone();
+            std::thread::spawn(move || serve(stream, evp));
+        }
+    });
+    Ok(())
+}
+
+#[cfg(not(unix))]
+pub fn listen(_path: &Path, _evp: EventLoopProxy<UserEvent>) -> anyhow::Result<()> {
+    anyhow::bail!("--control needs Unix domain sockets, which this platform does not have")
+}
+
+// answers the commands of one connection until it is closed or the event loop is gone
+#[cfg(unix)]
+fn serve(stream: std::os::unix::net::UnixStream, evp: EventLoopProxy<UserEvent>) {
+    use std::io::{BufRead, BufReader, Write};
+
+    let mut writer = match stream.try_clone() {
+        Ok(writer) => writer,
+        Err(_) => return,
+    };
+    for line in BufReader::new(stream).lines() {
+        let line = match line {
+            Ok(line) if line.trim().is_empty() => continue,
+            Ok(line) => line,
+            Err(_) => break,
+        };
+        let reply = match parse(&line) {
+            // answered first, as the viewer exits without waiting for this thread
+            Ok(Command::Quit) => {
+                let _ = writeln!(writer, "{}", encode(&Ok(())));
+                let _ = evp.send_event(UserEvent::Control(Command::Quit, None));
+                break;
+            }
+            Ok(command) => {
+                let (tx, rx) = std::sync::mpsc::channel();
+                if evp
+                    .send_event(UserEvent::Control(command, Some(tx)))
+                    .is_err()
+                {
+                    break;
+                }
+                match rx.recv() {
+                    Ok(reply) => reply,
+                    Err(_) => break,
+                }
+            }
+            Err(e) => Err(e),
+        };
+        if writeln!(writer, "{}", encode(&reply)).is_err() {
+            break;
+        }
+    }
+}
+
+/// Where the viewer of `--single-instance` listens: in `$XDG_RUNTIME_DIR`, or in the cache
+/// directory if that is unset.
+pub fn instance_socket() -> Option<PathBuf> {
+    match std::env::var_os("XDG_RUNTIME_DIR").filter(|dir| !dir.is_empty()) {
+        Some(dir) => Some(PathBuf::from(dir).join("svgview.sock")),
+        None => svgview_core::cache_path("instance.sock"),
+    }
+}
+
+/// Sends `command` to the viewer listening on `socket` and waits for its answer. `None` if
+/// no viewer listens there.
+#[cfg(unix)]
+pub fn send(socket: &Path, command: &Command) -> Option<Reply> {
+    use std::io::{BufRead, BufReader, Write};
+
+    let mut stream = std::os::unix::net::UnixStream::connect(socket).ok()?;
+    let mut line = String::new();
+    let sent = writeln!(stream, "{}", encode_command(command))
+        .and_then(|()| BufReader::new(&stream).read_line(&mut line));
+    Some(match sent {
+        Ok(_) => parse_reply(&line),
+        Err(e) => Err(format!(
+            "could not reach the viewer on {}: {}",
+            socket.display(),
+            e
+        )),
+    })
+}
+
+#[cfg(not(unix))]
+pub fn send(_socket: &Path, _command: &Command) -> Option<Reply> {
+    None
+}
+
+/// Sends `reply` back to the connection a command came from, if it still waits for one.
+pub fn answer(client: Option<Sender<Reply>>, reply: Reply) {
+    if let Some(client) = client {
+        let _ = client.send(reply);
+    }
+}
+
+fn parse(line: &str) -> Result<Command, String> {
+    match serde_json::from_str(line) {
+        Ok(Command::Zoom(zoom)) if !(zoom.is_finite() && zoom > 0.0) => {
+            Err("`zoom` takes a positive number".into())
+        }
+        Ok(command) => Ok(command),
+        Err(e) => Err(format!("not a command: {}", e)),
+    }
+}
+
+fn encode(reply: &Reply) -> String {
+    let answer = match reply {
+        Ok(()) => Answer::Ok(true),
+        Err(e) => Answer::Error(e.clone()),
+    };
+    serde_json::to_string(&answer).expect("an answer is always valid JSON")
+}
+
+fn encode_command(command: &Command) -> String {
+    serde_json::to_string(command).expect("a command is always valid JSON")
+}
+
+// a reply as `encode` writes it
+#[cfg(unix)]
+fn parse_reply(line: &str) -> Reply {
+    match serde_json::from_str(line.trim()) {
+        Ok(Answer::Ok(true)) => Ok(()),
+        Ok(Answer::Error(e)) => Err(e),
+        _ => Err(format!("unexpected answer `{}`", line.trim())),
+    }
+}
+
+// a reply on the wire: `{"ok": true}` or `{"error": "<why>"}`
+#[derive(Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+enum Answer {
+    Ok(bool),
+    Error(String),
+}
+
+// `quit` is written `{"quit": true}`, like the other commands an object with one member
+mod quit {
+    use serde::{de::Error, Deserialize, Deserializer, Serializer};
+
+    pub fn serialize<S: Serializer>(serializer: S) -> Result<S::Ok, S::Error> {
+        serializer.serialize_bool(true)
+    }
+
+    pub fn deserialize<'de, D: Deserializer<'de>>(deserializer: D) -> Result<(), D::Error> {
+        match bool::deserialize(deserializer)? {
+            true => Ok(()),
+            false => Err(D::Error::custom("`quit` takes `true`")),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn commands_are_parsed() {
+        assert!(matches!(
+            parse(r#"{"load": "a b.svg"}"#),
+            Ok(Command::Load(path)) if path == Path::new("a b.svg")
+        ));
+        assert!(matches!(parse(r#" {"zoom": 2} "#), Ok(Command::Zoom(zoom)) if zoom == 2.0));
+        assert!(matches!(parse(r#"{"quit": true}"#), Ok(Command::Quit)));
+    }
+
+    #[test]
+    fn malformed_commands_are_refused() {
+        for line in [
+            r#"{"quit": false}"#,
+            r#"{"zoom": -1}"#,
+            r#"{"zoom": "2"}"#,
+            r#"{"load": "a.svg", "zoom": 2}"#,
+            r#"{"rotate": 90}"#,
+            r#"{}"#,
+            "load a.svg",
+        ] {
+            assert!(parse(line).is_err(), "{}", line);
+        }
+    }
+
+    #[test]
+    fn commands_survive_the_round_trip() {
+        let commands = [
+            Command::Load("quote\"and\\slash\n.svg".into()),
+            Command::Open("ünï.svg".into()),
+            Command::Zoom(0.25),
+            Command::Export("out.png".into()),
+            Command::Quit,
+        ];
+        for command in commands {
+            let line = encode_command(&command);
+            assert_eq!(
+                format!("{:?}", parse(&line).unwrap()),
+                format!("{:?}", command),
+                "{}",
+                line
+            );
+        }
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn replies_survive_the_round_trip() {
+        for reply in [Ok(()), Err("no such file: \"a.svg\"".to_string())] {
+            assert_eq!(parse_reply(&format!("{}\n", encode(&reply))), reply);
+        }
+        assert!(parse_reply(r#"{"ok": false}"#).is_err());
+    }
+}
@@ -0,0 +1,94 @@
+//! Inspection of paint server and effect definitions in the source SVG.
+//!
+//! usvg removes unused definitions and broken references while converting a
+//! document, so the report is built from the original markup instead of the
+//! converted `usvg::Tree`.
+
+use std::collections::{BTreeMap, HashSet};
+
+const DEF_ELEMENTS: &[&str] = &[
+    "linearGradient",
+    "radialGradient",
+    "pattern",
+    "filter",
+    "clipPath",
+    "mask",
+];
+
+const LINK_ATTRIBUTES: &[&str] = &["fill", "stroke", "filter", "clip-path", "mask", "style"];
+
+/// Lists every definition with its reference count, followed by references
+/// that point at ids which don't exist in the document.
+pub fn usage_report(data: &[u8]) -> Vec<String> {
+    let text = match std::str::from_utf8(data) {
+        Ok(text) => text,
+        Err(_) => return vec!["defs: source is not UTF-8 text".to_string()],
+    };
+    let opts = roxmltree::ParsingOptions { allow_dtd: true };
+    let doc = match roxmltree::Document::parse_with_options(text, opts) {
+        Ok(doc) => doc,
+        Err(e) => return vec![format!("defs: {}", e)],
+    };
+
+    let mut ids = HashSet::new();
+    let mut defined = vec![];
+    let mut references: BTreeMap<&str, usize> = BTreeMap::new();
+    for node in doc.descendants().filter(|n| n.is_element()) {
+        if let Some(id) = node.attribute("id") {
+            ids.insert(id);
+            let tag = node.tag_name().name();
+            if DEF_ELEMENTS.contains(&tag) {
+                defined.push((tag, id));
+            }
+        }
+        for attr in node.attributes() {
+            if LINK_ATTRIBUTES.contains(&attr.name()) {
+                for id in url_references(attr.value()) {
+                    *references.entry(id).or_default() += 1;
+                }
+            } else if attr.name() == "href" {
+                if let Some(id) = attr.value().strip_prefix('#') {
+                    *references.entry(id).or_default() += 1;
+                }
+            }
+        }
+    }
+
+    let dangling: Vec<&str> = references
+        .keys()
+        .copied()
+        .filter(|id| !ids.contains(id))
+        .collect();
+    let unused = defined
+        .iter()
+        .filter(|(_, id)| !references.contains_key(id))
+        .count();
+
+    let mut lines = vec![format!(
+        "defs: {} defined, {} unused, {} dangling",
+        defined.len(),
+        unused,
+        dangling.len()
+    )];
+    for (tag, id) in defined {
+        match references.get(id) {
+            Some(count) => lines.push(format!("  {} #{}: {} reference(s)", tag, id, count)),
+            None => lines.push(format!("  {} #{}: unused", tag, id)),
+        }
+    }
+    for id in dangling {
+        lines.push(format!("  dangling reference to #{}", id));
+    }
+    lines
+}
+
+// ids referenced as `url(#id)` in a presentation attribute or inline style
+fn url_references(value: &str) -> impl Iterator<Item = &str> {
+    value.split("url(").skip(1).filter_map(|rest| {
+        rest.split(')')
+            .next()?
+            .trim()
+            .trim_matches(|c| c == '"' || c == '\'')
+            .strip_prefix('#')
+    })
+}
@@ -0,0 +1,28 @@
+//! Highlighting the pixels that changed between two renders.
+
+use tiny_skia::Pixmap;
+
+/// Returns a copy of `after` with every pixel that differs from `before`
+/// tinted magenta, or `None` if the renders are identical or not comparable.
+pub fn highlight_changes(before: &Pixmap, after: &Pixmap) -> Option<Pixmap> {
+    if before.width() != after.width() || before.height() != after.height() {
+        return None;
+    }
+    let mut highlighted = after.clone();
+    let mut changed = false;
+    for (old, new) in before
+        .data()
+        .chunks_exact(4)
+        .zip(highlighted.data_mut().chunks_exact_mut(4))
+    {
+        if old != new {
+            changed = true;
+            // mix with opaque magenta, staying valid premultiplied RGBA
+            new[0] = ((new[0] as u16 + 255) / 2) as u8;
+            new[1] /= 2;
+            new[2] = ((new[2] as u16 + 255) / 2) as u8;
+            new[3] = ((new[3] as u16 + 255) / 2) as u8;
+        }
+    }
+    changed.then_some(highlighted)
+}
@@ -0,0 +1,218 @@
+//! SVGs given as an `http://` or `https://` URL in place of a file, fetched with ureq, over
+//! rustls and through the proxy of `ALL_PROXY`, `HTTPS_PROXY` or `HTTP_PROXY`, if set.
+//!
+//! The URL is fetched again on an interval, as a conditional request with the `ETag` and
+//! `Last-Modified` of the previous answer, so an unchanged document costs the server a
+//! `304 Not Modified`; a new version is sent to the event loop like a reloaded file.
+
+use crate::UserEvent;
+use anyhow::{Context, Result};
+use log::warn;
+use std::time::Duration;
+use winit::event_loop::EventLoopProxy;
+
+/// How often a URL is fetched again when `--poll` does not say.
+pub const DEFAULT_INTERVAL: Duration = Duration::from_secs(5);
+// how long one fetch may take, so a stalled server does not stop the polling
+const TIMEOUT: Duration = Duration::from_secs(30);
+// the largest document fetched, far above ureq's default of 10 MB
+const BODY_LIMIT: u64 = 1 << 30;
+
+pub fn is_url(input: &str) -> bool {
+    input.starts_with("http://") || input.starts_with("https://")
+}
+
+/// The part of `url` to show as its file name in the title.
+pub fn file_name(url: &str) -> &str {
+    let path = url.split(['?', '#']).next().unwrap_or(url);
+    path.trim_end_matches('/')
+        .rsplit('/')
+        .next()
+        .unwrap_or(path)
+}
+
+/// A fetched URL and what its server said about the version fetched.
+pub struct Remote {
+    url: String,
+    agent: ureq::Agent,
+    etag: Option<String>,
+    last_modified: Option<String>,
+    // for servers that send neither header
+    body: Vec<u8>,
+}
+
+struct Response {
+    status: u16,
+    etag: Option<String>,
+    last_modified: Option<String>,
+    body: Vec<u8>,
+}
+
+impl Remote {
+    /// Fetches `url` for the first time, returning its body.
+    pub fn fetch(url: &str) -> Result<(Self, Vec<u8>)> {
+        let agent = ureq::Agent::config_builder()
+            .timeout_global(Some(TIMEOUT))
+            .build()
+            .into();
+        let mut remote = Self {
+            url: url.to_string(),
+            agent,
+            etag: None,
+            last_modified: None,
+            body: vec![],
+        };
+        let body = remote.refetch()?.unwrap_or_default();
+        Ok((remote, body))
+    }
+
+    /// The new version of the document, or `None` if it did not change since the last
+    /// fetch.
+    fn refetch(&mut self) -> Result<Option<Vec<u8>>> {
+        let response = self.request()?;
+        if response.status == 304 || response.body == self.body {
+            return Ok(None);
+        }
+        self.etag = response.etag;
+        self.last_modified = response.last_modified;
+        self.body = response.body.clone();
+        Ok(Some(response.body))
+    }
+
+    // redirects are followed and error statuses are failures
+    fn request(&self) -> Result<Response> {
+        let mut request = self.agent.get(&self.url);
+        if let Some(etag) = &self.etag {
+            request = request.header("If-None-Match", etag);
+        }
+        if let Some(last_modified) = &self.last_modified {
+            request = request.header("If-Modified-Since", last_modified);
+        }
+        let mut response = request.call()?;
+        let header = |name| {
+            let value = response.headers().get(name)?.to_str().ok()?;
+            Some(value.to_string())
+        };
+        let (etag, last_modified) = (header("etag"), header("last-modified"));
+        let body = response
+            .body_mut()
+            .with_config()
+            .limit(BODY_LIMIT)
+            .read_to_vec()
+            .context("could not read the response")?;
+        Ok(Response {
+            status: response.status().as_u16(),
+            etag,
+            last_modified,
+            body,
+        })
+    }
+}
+
+/// Fetches the URL of `remote` every `interval` on a thread of its own, sending each new
+/// version to the event loop. Failures are logged and the next fetch tried, as the server
+/// may be back by then.
+pub fn poll(mut remote: Remote, interval: Duration, evp: EventLoopProxy<UserEvent>) {
+    std::thread::spawn(move || loop {
+        std::thread::sleep(interval);
+        match remote.refetch() {
+            Ok(Some(body)) => {
+                if evp.send_event(UserEvent::Fetched(body)).is_err() {
+                    break;
+                }
+            }
+            Ok(None) => {}
+            Err(e) => warn!("Could not fetch {}: {:#}", remote.url, e),
+        }
+    });
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::io::{BufRead, BufReader, Write};
+    use std::net::TcpListener;
+
+    // serves one connection for each of `answers`, which makes the response from the
+    // request's head, with its header names in lowercase; the URL of the server
+    fn serve(answers: Vec<fn(&str) -> String>) -> String {
+        let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+        let url = format!("http://{}/drawing.svg", listener.local_addr().unwrap());
+        std::thread::spawn(move || {
+            for (answer, stream) in answers.into_iter().zip(listener.incoming()) {
+                let mut stream = stream.unwrap();
+                let mut head = String::new();
+                let mut reader = BufReader::new(&stream);
+                while reader.read_line(&mut head).unwrap() > 2 {}
+                let response = answer(&head.to_ascii_lowercase());
+                stream.write_all(response.as_bytes()).unwrap();
+            }
+        });
+        url
+    }
+
+    // a response that closes the connection, so each request comes on a new one
+    fn response(status: &str, headers: &str, body: &str) -> String {
+        format!(
+            "HTTP/1.1 {}\r\n{}Content-Length: {}\r\nConnection: close\r\n\r\n{}",
+            status,
+            headers,
+            body.len(),
+            body
+        )
+    }
+
+    #[test]
+    fn refetches_are_conditional() {
+        let url = serve(vec![
+            |_| response("200 OK", "ETag: \"v1\"\r\n", "<svg/>"),
+            |head| match head.contains("if-none-match: \"v1\"") {
+                true => response("304 Not Modified", "", ""),
+                false => response("200 OK", "", "<svg>changed</svg>"),
+            },
+        ]);
+        let (mut remote, body) = Remote::fetch(&url).unwrap();
+        assert_eq!(body, b"<svg/>");
+        assert_eq!(remote.refetch().unwrap(), None);
+    }
+
+    #[test]
+    fn refetches_without_validators_compare_the_body() {
+        let url = serve(vec![
+            |_| response("200 OK", "", "<svg/>"),
+            |_| response("200 OK", "", "<svg/>"),
+            |_| response("200 OK", "", "<svg>changed</svg>"),
+        ]);
+        let (mut remote, _) = Remote::fetch(&url).unwrap();
+        assert_eq!(remote.refetch().unwrap(), None);
+        assert_eq!(remote.refetch().unwrap().unwrap(), b"<svg>changed</svg>");
+    }
+
+    #[test]
+    fn redirects_are_followed() {
+        let url = serve(vec![
+            |_| response("302 Found", "Location: /moved.svg\r\n", ""),
+            |head| match head.starts_with("get /moved.svg ") {
+                true => response("200 OK", "", "<svg/>"),
+                false => response("404 Not Found", "", ""),
+            },
+        ]);
+        assert_eq!(Remote::fetch(&url).unwrap().1, b"<svg/>");
+    }
+
+    #[test]
+    fn error_statuses_are_failures() {
+        let url = serve(vec![|_| response("404 Not Found", "", "gone")]);
+        assert!(Remote::fetch(&url).is_err());
+    }
+
+    #[test]
+    fn file_names_of_urls() {
+        assert!(is_url("https://example.com/a.svg") && !is_url("a.svg"));
+        assert_eq!(
+            file_name("https://example.com/icons/a.svg?v=2#top"),
+            "a.svg"
+        );
+        assert_eq!(file_name("https://example.com/icons/"), "icons");
+    }
+}
@@ -0,0 +1,214 @@
+//! Finding the element under the mouse for the inspect mode, or by its id.
+//!
+//! Elements are hit-tested by their bounding boxes in the converted `usvg::Tree`. usvg
+//! turns shapes into paths and drops the original tag names, so those are looked up by id
+//! in the source markup.
+
+use usvg::{Node, NodeExt, NodeKind, Paint, PathBbox, PathSegment, Tree};
+
+/// The element under a point and what is shown about it.
+pub struct Hit {
+    pub node: Node,
+    /// In the SVG's user units.
+    pub bbox: PathBbox,
+    pub lines: Vec<String>,
+}
+
+/// The topmost drawn element whose bounding box contains `(x, y)`, in user units.
+pub fn hit_test(tree: &Tree, source: &[u8], x: f64, y: f64) -> Option<Hit> {
+    let (node, bbox) = drawn(tree)
+        .filter(|node| matches!(*node.borrow(), NodeKind::Path(_) | NodeKind::Image(_)))
+        .filter_map(|node| node.calculate_bbox().map(|bbox| (node, bbox)))
+        .filter(|(_, bbox)| {
+            bbox.left() <= x && x <= bbox.right() && bbox.top() <= y && y <= bbox.bottom()
+        })
+        .last()?;
+    Some(describe(node, bbox, source))
+}
+
+/// The drawn element with `id`, if it has a size.
+pub fn find(tree: &Tree, source: &[u8], id: &str) -> Option<Hit> {
+    let node = tree.node_by_id(id)?;
+    let bbox = node.calculate_bbox()?;
+    Some(describe(node, bbox, source))
+}
+
+/// The ids of the drawn elements that kept theirs in `tree`, in document order.
+pub fn ids(tree: &Tree) -> Vec<String> {
+    drawn(tree)
+        .map(|node| node.id().to_string())
+        .filter(|id| !id.is_empty())
+        .collect()
+}
+
+// every node but those of clip paths, masks and patterns
+fn drawn(tree: &Tree) -> impl Iterator<Item = Node> {
+    tree.root().descendants().filter(|node| {
+        !node
+            .ancestors()
+            .any(|a| matches!(*a.borrow(), NodeKind::Defs))
+    })
+}
+
+fn describe(node: Node, bbox: PathBbox, source: &[u8]) -> Hit {
+    let kind = match *node.borrow() {
+        NodeKind::Image(_) => "image",
+        NodeKind::Group(_) => "g",
+        _ => "path",
+    };
+    let id = node.id().to_string();
+    let mut lines = vec![if id.is_empty() {
+        kind.to_string()
+    } else {
+        element(source, &id, kind)
+    }];
+    // text, for one, becomes paths without ids inside the group that has the id
+    if id.is_empty() {
+        let named = node.ancestors().skip(1).find(|a| !a.id().is_empty());
+        if let Some(named) = named {
+            lines.push(format!("in {}", element(source, &named.id(), "g")));
+        }
+    }
+    lines.push(format!(
+        "x {:.1}  y {:.1}  width {:.1}  height {:.1}",
+        bbox.x(),
+        bbox.y(),
+        bbox.width(),
+        bbox.height()
+    ));
+    Hit { node, bbox, lines }
+}
+
+/// Where the element of `hit` comes from: the line it starts on, its ancestors and its
+/// markup in the source, or, if it has no id there, its ancestors in the tree and the
+/// attributes usvg resolved for it.
+pub fn markup(hit: &Hit, source: &[u8]) -> String {
+    let id = hit.node.id().to_string();
+    let in_source = with_source_element(source, &id, |element| {
+        let mut ancestry: Vec<String> = element
+            .ancestors()
+            .filter(|a| a.is_element())
+            .map(|a| match a.attribute("id") {
+                Some(id) => format!("{}#{}", a.tag_name().name(), id),
+                None => a.tag_name().name().to_string(),
+            })
+            .collect();
+        ancestry.reverse();
+        let document = element.document();
+        let line = document.text_pos_at(element.range().start).row;
+        let markup = &document.input_text()[element.range()];
+        format!("line {}: {}\n{}", line, ancestry.join(" > "), markup)
+    });
+    if let Some(text) = in_source {
+        return text;
+    }
+
+    let mut ancestry: Vec<String> = hit
+        .node
+        .ancestors()
+        .map(|a| {
+            let kind = match *a.borrow() {
+                NodeKind::Svg(_) => "svg",
+                NodeKind::Group(_) => "g",
+                NodeKind::Image(_) => "image",
+                _ => "path",
+            };
+            match &*a.id() {
+                "" => kind.to_string(),
+                id => format!("{}#{}", kind, id),
+            }
+        })
+        .collect();
+    ancestry.reverse();
+    format!("{}\n{}", ancestry.join(" > "), resolved(&hit.node))
+}
+
+// the element as usvg resolved it: shapes as path data, styles as plain attributes
+fn resolved(node: &Node) -> String {
+    let path = match &*node.borrow() {
+        NodeKind::Path(path) => path.clone(),
+        NodeKind::Image(image) => {
+            let view = image.view_box.rect;
+            return format!(
+                "<image x=\"{}\" y=\"{}\" width=\"{}\" height=\"{}\"/>",
+                view.x(),
+                view.y(),
+                view.width(),
+                view.height()
+            );
+        }
+        _ => return String::new(),
+    };
+    let mut d = vec![];
+    for segment in path.data.iter() {
+        d.push(match *segment {
+            PathSegment::MoveTo { x, y } => format!("M {} {}", x, y),
+            PathSegment::LineTo { x, y } => format!("L {} {}", x, y),
+            PathSegment::CurveTo {
+                x1,
+                y1,
+                x2,
+                y2,
+                x,
+                y,
+            } => {
+                format!("C {} {} {} {} {} {}", x1, y1, x2, y2, x, y)
+            }
+            PathSegment::ClosePath => "Z".to_string(),
+        });
+    }
+    let mut attributes = vec![format!("d=\"{}\"", d.join(" "))];
+    match &path.fill {
+        Some(fill) => {
+            attributes.push(format!("fill=\"{}\"", paint(&fill.paint)));
+            if fill.opacity.value() != 1.0 {
+                attributes.push(format!("fill-opacity=\"{}\"", fill.opacity.value()));
+            }
+        }
+        None => attributes.push("fill=\"none\"".to_string()),
+    }
+    if let Some(stroke) = &path.stroke {
+        attributes.push(format!("stroke=\"{}\"", paint(&stroke.paint)));
+        attributes.push(format!("stroke-width=\"{}\"", stroke.width.value()));
+        if stroke.opacity.value() != 1.0 {
+            attributes.push(format!("stroke-opacity=\"{}\"", stroke.opacity.value()));
+        }
+    }
+    let ts = node.abs_transform();
+    if !ts.is_default() {
+        attributes.push(format!(
+            "transform=\"matrix({} {} {} {} {} {})\"",
+            ts.a, ts.b, ts.c, ts.d, ts.e, ts.f
+        ));
+    }
+    format!("<path {}/>", attributes.join(" "))
+}
+
+fn paint(paint: &Paint) -> String {
+    match paint {
+        Paint::Color(c) => format!("#{:02x}{:02x}{:02x}", c.red, c.green, c.blue),
+        Paint::Link(id) => format!("url(#{})", id),
+    }
+}
+
+// the start tag of the source element with `id`, or `kind` with the id if it is not found
+fn element(source: &[u8], id: &str, kind: &str) -> String {
+    let tag = with_source_element(source, id, |node| node.tag_name().name().to_string());
+    format!("<{} id=\"{}\">", tag.as_deref().unwrap_or(kind), id)
+}
+
+// `f` of the element with `id` in the source, if it can be parsed and has one
+fn with_source_element<T>(
+    source: &[u8],
+    id: &str,
+    f: impl FnOnce(roxmltree::Node) -> T,
+) -> Option<T> {
+    if id.is_empty() {
+        return None;
+    }
+    let text = std::str::from_utf8(source).ok()?;
+    let opts = roxmltree::ParsingOptions { allow_dtd: true };
+    let doc = roxmltree::Document::parse_with_options(text, opts).ok()?;
+    let element = doc.descendants().find(|n| n.attribute("id") == Some(id))?;
+    Some(f(element))
+}
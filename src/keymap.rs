@@ -0,0 +1,388 @@
+//! Which keys trigger which viewer actions, remappable in the `[keys]` table of the config
+//! file, e.g. `pan-left = ["h"]`.
+//!
+//! A key is written as winit names it (`PageDown`, `Escape`, `Plus`), or as a letter or
+//! digit, optionally prefixed by `Ctrl+`, `Alt+` and `Shift+`. Ctrl and Alt have to match
+//! exactly; Shift is only checked when it is part of the binding, so that keys which need
+//! it on some layouts, such as `+`, still work without naming it, unless the key is also
+//! bound with Shift.
+
+use serde::de::{Deserializer, Error, IntoDeserializer};
+use serde::Deserialize;
+use std::collections::HashMap;
+use std::fmt;
+use winit::event::VirtualKeyCode;
+use winit_input_helper::WinitInputHelper;
+
+#[derive(Clone, Copy, PartialEq, Eq, Hash, Debug, Deserialize)]
+#[serde(rename_all = "kebab-case")]
+pub enum Action {
+    Quit,
+    Reparse,
+    ZoomIn,
+    ZoomOut,
+    ResetView,
+    CenterContent,
+    ActualSize,
+    PanLeft,
+    PanRight,
+    PanUp,
+    PanDown,
+    RotateClockwise,
+    RotateCounterClockwise,
+    FlipHorizontal,
+    FlipVertical,
+    ToggleCheckerboard,
+    CycleBackground,
+    CycleFit,
+    NextPage,
+    PreviousPage,
+    NextFile,
+    PreviousFile,
+    Copy,
+    Paste,
+    Save,
+    ExportPdf,
+    ToggleDefs,
+    ToggleFullscreen,
+    ToggleAlwaysOnTop,
+    RecentFiles,
+    TogglePixelGrid,
+    ToggleRulers,
+    ToggleMinimap,
+    ToggleInspect,
+    ToggleLayers,
+    ToggleWireframe,
+    ToggleAnimation,
+    StepForward,
+    StepBackward,
+    PauseWatching,
+    CommandPalette,
+}
+
+impl Action {
+    /// Every action, in the order the command palette lists them.
+    pub const ALL: &'static [Action] = {
+        use Action::*;
+        &[
+            Quit,
+            Reparse,
+            ZoomIn,
+            ZoomOut,
+            ResetView,
+            CenterContent,
+            ActualSize,
+            PanLeft,
+            PanRight,
+            PanUp,
+            PanDown,
+            RotateClockwise,
+            RotateCounterClockwise,
+            FlipHorizontal,
+            FlipVertical,
+            ToggleCheckerboard,
+            CycleBackground,
+            CycleFit,
+            NextPage,
+            PreviousPage,
+            NextFile,
+            PreviousFile,
+            Copy,
+            Paste,
+            Save,
+            ExportPdf,
+            ToggleDefs,
+            ToggleFullscreen,
+            ToggleAlwaysOnTop,
+            RecentFiles,
+            TogglePixelGrid,
+            ToggleRulers,
+            ToggleMinimap,
+            ToggleInspect,
+            ToggleLayers,
+            ToggleWireframe,
+            ToggleAnimation,
+            StepForward,
+            StepBackward,
+            PauseWatching,
+            CommandPalette,
+        ]
+    };
+
+    /// The name as a phrase, e.g. `Toggle pixel grid` for `TogglePixelGrid`.
+    pub fn label(self) -> String {
+        let mut label = String::new();
+        for c in format!("{:?}", self).chars() {
+            if c.is_ascii_uppercase() && !label.is_empty() {
+                label.push(' ');
+                label.push(c.to_ascii_lowercase());
+            } else {
+                label.push(c);
+            }
+        }
+        label
+    }
+}
+
+#[derive(Clone, Copy, PartialEq, Debug)]
+pub struct Chord {
+    key: VirtualKeyCode,
+    ctrl: bool,
+    alt: bool,
+    shift: bool,
+}
+
+impl Chord {
+    const fn key(key: VirtualKeyCode) -> Self {
+        Self {
+            key,
+            ctrl: false,
+            alt: false,
+            shift: false,
+        }
+    }
+
+    const fn ctrl(key: VirtualKeyCode) -> Self {
+        Self {
+            ctrl: true,
+            ..Self::key(key)
+        }
+    }
+
+    const fn shift(key: VirtualKeyCode) -> Self {
+        Self {
+            shift: true,
+            ..Self::key(key)
+        }
+    }
+
+    fn pressed(&self, input: &WinitInputHelper) -> bool {
+        input.key_pressed(self.key)
+            && input.held_control() == self.ctrl
+            && input.held_alt() == self.alt
+            && (input.held_shift() || !self.shift)
+    }
+
+    fn parse(text: &str) -> Option<Self> {
+        let mut parts: Vec<&str> = text.split('+').map(str::trim).collect();
+        let name = parts.pop()?;
+        let mut chord = Self::key(key_code(name)?);
+        for modifier in parts {
+            match modifier.to_ascii_lowercase().as_str() {
+                "ctrl" | "control" => chord.ctrl = true,
+                "alt" => chord.alt = true,
+                "shift" => chord.shift = true,
+                _ => return None,
+            }
+        }
+        Some(chord)
+    }
+}
+
+impl fmt::Display for Chord {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        for (held, name) in [
+            (self.ctrl, "Ctrl+"),
+            (self.alt, "Alt+"),
+            (self.shift, "Shift+"),
+        ] {
+            if held {
+                f.write_str(name)?;
+            }
+        }
+        // the reverse of `key_code`
+        let name = format!("{:?}", self.key);
+        match name.strip_prefix("Key") {
+            Some(digit) if !digit.is_empty() => f.write_str(digit),
+            _ => f.write_str(&name),
+        }
+    }
+}
+
+impl<'de> Deserialize<'de> for Chord {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        let text = String::deserialize(deserializer)?;
+        Self::parse(&text).ok_or_else(|| D::Error::custom(format!("`{}` is not a key", text)))
+    }
+}
+
+// single letters and digits are accepted as typed, everything else by winit's name
+fn key_code(name: &str) -> Option<VirtualKeyCode> {
+    let name = match name.chars().collect::<Vec<_>>()[..] {
+        [c] if c.is_ascii_alphabetic() => c.to_ascii_uppercase().to_string(),
+        [c] if c.is_ascii_digit() => format!("Key{}", c),
+        _ => name.to_string(),
+    };
+    let deserializer: serde::de::value::StringDeserializer<serde::de::value::Error> =
+        name.into_deserializer();
+    VirtualKeyCode::deserialize(deserializer).ok()
+}
+
+const DEFAULT_BINDINGS: &[(Action, &[Chord])] = {
+    use Action::*;
+    use VirtualKeyCode as K;
+    &[
+        (Quit, &[Chord::key(K::Escape)]),
+        (Reparse, &[Chord::key(K::F5)]),
+        (
+            ZoomIn,
+            &[
+                Chord::key(K::Plus),
+                Chord::key(K::Equals),
+                Chord::key(K::NumpadAdd),
+            ],
+        ),
+        (
+            ZoomOut,
+            &[Chord::key(K::Minus), Chord::key(K::NumpadSubtract)],
+        ),
+        (ResetView, &[Chord::key(K::Key0), Chord::key(K::Numpad0)]),
+        (CenterContent, &[Chord::key(K::Home)]),
+        (ActualSize, &[Chord::key(K::Key1), Chord::key(K::Numpad1)]),
+        (RotateClockwise, &[Chord::key(K::R)]),
+        (RotateCounterClockwise, &[Chord::shift(K::R)]),
+        (FlipHorizontal, &[Chord::key(K::H)]),
+        (FlipVertical, &[Chord::key(K::V)]),
+        (ToggleCheckerboard, &[Chord::key(K::C)]),
+        (CycleBackground, &[Chord::key(K::B)]),
+        (CycleFit, &[Chord::key(K::F)]),
+        (NextPage, &[Chord::key(K::Right)]),
+        (PreviousPage, &[Chord::key(K::Left)]),
+        (NextFile, &[Chord::key(K::PageDown), Chord::key(K::Down)]),
+        (PreviousFile, &[Chord::key(K::PageUp), Chord::key(K::Up)]),
+        (Copy, &[Chord::ctrl(K::C)]),
+        (Paste, &[Chord::ctrl(K::V)]),
+        (Save, &[Chord::key(K::S)]),
+        (ExportPdf, &[Chord::key(K::P)]),
+        (ToggleDefs, &[Chord::key(K::D)]),
+        (ToggleFullscreen, &[Chord::key(K::F11)]),
+        (ToggleAlwaysOnTop, &[Chord::key(K::T)]),
+        (RecentFiles, &[Chord::ctrl(K::R)]),
+        (TogglePixelGrid, &[Chord::key(K::G)]),
+        (ToggleRulers, &[Chord::key(K::U)]),
+        (ToggleMinimap, &[Chord::key(K::M)]),
+        (ToggleInspect, &[Chord::key(K::I)]),
+        (ToggleLayers, &[Chord::key(K::L)]),
+        (ToggleWireframe, &[Chord::key(K::W)]),
+        (ToggleAnimation, &[Chord::key(K::Space)]),
+        (StepForward, &[Chord::key(K::Period)]),
+        (StepBackward, &[Chord::key(K::Comma)]),
+        (PauseWatching, &[Chord::key(K::Pause)]),
+        (CommandPalette, &[Chord::ctrl(K::P)]),
+    ]
+};
+
+#[derive(Clone)]
+pub struct Keymap {
+    bindings: Vec<(Chord, Action)>,
+}
+
+impl Default for Keymap {
+    fn default() -> Self {
+        let bindings = DEFAULT_BINDINGS
+            .iter()
+            .flat_map(|(action, chords)| chords.iter().map(move |chord| (*chord, *action)))
+            .collect();
+        Self { bindings }
+    }
+}
+
+impl Keymap {
+    /// The default bindings, with those of each action in `keys` replaced. Keys given to
+    /// an action are taken from the actions they were bound to by default.
+    pub fn with(keys: HashMap<Action, Vec<Chord>>) -> Self {
+        let mut keymap = Self::default();
+        keymap.bindings.retain(|(chord, action)| {
+            !keys.contains_key(action) && !keys.values().flatten().any(|key| key == chord)
+        });
+        for (action, chords) in keys {
+            keymap
+                .bindings
+                .extend(chords.into_iter().map(|chord| (chord, action)));
+        }
+        keymap
+    }
+
+    /// The keys bound to `action`.
+    pub fn keys(&self, action: Action) -> Vec<Chord> {
+        self.bindings
+            .iter()
+            .filter(|(_, bound)| *bound == action)
+            .map(|(chord, _)| *chord)
+            .collect()
+    }
+
+    /// The actions whose keys were pressed since the last update of `input`. A binding
+    /// that names Shift wins over one for the same key without it.
+    pub fn pressed(&self, input: &WinitInputHelper) -> Vec<Action> {
+        let pressed: Vec<&(Chord, Action)> = self
+            .bindings
+            .iter()
+            .filter(|(chord, _)| chord.pressed(input))
+            .collect();
+        let mut actions: Vec<Action> = pressed
+            .iter()
+            .filter(|(chord, _)| {
+                let shifted = |(other, _): &&(Chord, Action)| other.shift && other.key == chord.key;
+                chord.shift || !pressed.iter().any(shifted)
+            })
+            .map(|(_, action)| *action)
+            .collect();
+        actions.dedup();
+        actions
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use VirtualKeyCode as K;
+
+    #[test]
+    fn key_codes_by_letter_digit_or_winit_name() {
+        assert_eq!(key_code("h"), Some(K::H));
+        assert_eq!(key_code("H"), Some(K::H));
+        assert_eq!(key_code("0"), Some(K::Key0));
+        assert_eq!(key_code("PageDown"), Some(K::PageDown));
+        assert_eq!(key_code("pagedown"), None);
+        assert_eq!(key_code(""), None);
+    }
+
+    #[test]
+    fn chords_with_modifiers() {
+        assert_eq!(Chord::parse("q"), Some(Chord::key(K::Q)));
+        assert_eq!(Chord::parse("Ctrl+P"), Some(Chord::ctrl(K::P)));
+        assert_eq!(Chord::parse("control + p"), Some(Chord::ctrl(K::P)));
+        let all = Chord {
+            alt: true,
+            shift: true,
+            ..Chord::ctrl(K::F5)
+        };
+        assert_eq!(Chord::parse("Shift+Alt+Ctrl+F5"), Some(all));
+        assert_eq!(Chord::parse("Super+q"), None);
+        assert_eq!(Chord::parse("Ctrl+"), None);
+    }
+
+    #[test]
+    fn chords_display_as_they_parse() {
+        for text in ["Ctrl+Alt+Shift+PageDown", "Shift+R", "1"] {
+            assert_eq!(Chord::parse(text).unwrap().to_string(), text);
+        }
+    }
+
+    #[test]
+    fn rebinding_replaces_the_default_keys() {
+        let keys = HashMap::from([
+            (Action::PanLeft, vec![Chord::key(K::H)]),
+            (Action::Quit, vec![Chord::key(K::Q)]),
+            (Action::ToggleDefs, vec![]),
+        ]);
+        let keymap = Keymap::with(keys);
+        assert_eq!(keymap.keys(Action::PanLeft), [Chord::key(K::H)]);
+        // Escape no longer quits, and H no longer flips
+        assert_eq!(keymap.keys(Action::Quit), [Chord::key(K::Q)]);
+        assert_eq!(keymap.keys(Action::FlipHorizontal), []);
+        assert_eq!(keymap.keys(Action::ToggleDefs), []);
+        assert_eq!(keymap.keys(Action::Reparse), [Chord::key(K::F5)]);
+    }
+}
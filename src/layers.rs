@@ -0,0 +1,46 @@
+//! The top-level groups of a document, which Inkscape uses as layers.
+
+use std::collections::HashMap;
+use usvg::{Node, NodeExt, NodeKind, Tree};
+
+const INKSCAPE_NS: &str = "http://www.inkscape.org/namespaces/inkscape";
+
+/// The top-level groups of `tree`, in document order.
+pub fn groups(tree: &Tree) -> Vec<Node> {
+    tree.root()
+        .children()
+        .filter(|node| matches!(*node.borrow(), NodeKind::Group(_)))
+        .collect()
+}
+
+/// A name for each of `groups`: its Inkscape layer name in `source`, its id, or its
+/// position if it has neither.
+pub fn names(groups: &[Node], source: &[u8]) -> Vec<String> {
+    // usvg drops the labels, so they are looked up by id in the markup
+    let opts = roxmltree::ParsingOptions { allow_dtd: true };
+    let text = std::str::from_utf8(source).unwrap_or_default();
+    let doc = roxmltree::Document::parse_with_options(text, opts).ok();
+    let labels: HashMap<&str, &str> = doc
+        .iter()
+        .flat_map(|doc| doc.descendants())
+        .filter_map(|node| {
+            Some((
+                node.attribute("id")?,
+                node.attribute((INKSCAPE_NS, "label"))?,
+            ))
+        })
+        .collect();
+
+    groups
+        .iter()
+        .enumerate()
+        .map(|(i, group)| {
+            let id = group.id();
+            match labels.get(&*id) {
+                Some(label) => label.to_string(),
+                None if !id.is_empty() => id.to_string(),
+                None => format!("layer {}", i + 1),
+            }
+        })
+        .collect()
+}
@@ -1,62 +1,342 @@
-use anyhow::Result;
+mod animate;
+mod cli;
+mod color;
+mod config;
+mod control;
+mod defs;
+mod diff;
+mod fetch;
+mod inspect;
+mod keymap;
+mod layers;
+mod minimap;
+mod output;
+mod overlay;
+mod palette;
+mod pyramid;
+mod recent;
+mod recolor;
+mod record;
+mod resources;
+mod serve;
+mod stream;
+mod tiles;
+#[cfg(feature = "tray")]
+mod tray;
+mod wireframe;
+mod worker;
+
+use svgview_core::{background, document, error, fit, fonts, mask, render, viewport, watch};
+
+use anyhow::{Context, Result};
+use cli::{Args, Command, ExportArgs, ExportIconsArgs, RecordArgs};
+use document::{newest_svg, read_svg, svgs_in, Document};
+use error::Failure;
+use fonts::Fonts;
 use pixels::{Pixels, SurfaceTexture};
 
-use std::io::Read;
+use std::collections::BTreeSet;
+use std::io::Write;
 use std::path::{Path, PathBuf};
 use winit::dpi::PhysicalSize;
-use winit::event::{Event, VirtualKeyCode};
+use winit::event::{Event, VirtualKeyCode, WindowEvent};
 use winit::event_loop::{ControlFlow, EventLoop, EventLoopProxy};
-use winit::window::WindowBuilder;
+use winit::window::{Fullscreen, WindowBuilder};
 use winit_input_helper::WinitInputHelper;
 
 use log::warn;
-use notify::{Op, ReadDirectoryChangesWatcher, RecursiveMode, Watcher, raw_watcher};
-use pixels::wgpu::Color;
-use std::sync::mpsc::channel;
 use std::thread;
+use std::time::{Duration, Instant};
 
+use fit::FitMode;
+use keymap::{Action, Keymap};
+use mask::MaskShape;
 use tiny_skia::Pixmap;
-use usvg::{Options, Tree};
+use usvg::{Options, ShapeRendering, TextRendering, Tree};
+use viewport::{quarter_turn, view_box_transform, Viewport, WHEEL_ZOOM_STEP, ZOOM_STEP};
+
+// sent to the event loop by the background threads
+#[derive(Debug)]
+enum UserEvent {
+    // the watched file, or a file in the watched directory, was written
+    FileChanged,
+    // a change was seen that is reloaded once the writes to the file stop
+    ReloadPending,
+    // a new document arrived on stdin
+    Document(Vec<u8>),
+    // a new version of the shown URL was fetched
+    Fetched(Vec<u8>),
+    // the render worker finished the job of this generation
+    Rendered(u64, Pixmap),
+    // the render worker finished a tile
+    Tile(tiles::Zoom, (i32, i32), Pixmap),
+    // the render worker finished a level of the preview pyramid of this document version
+    Level(u64, Pixmap),
+    // a command arrived on the control socket, with where to answer it, if anywhere
+    Control(control::Command, Option<std::sync::mpsc::Sender<control::Reply>>),
+    // chosen on the tray icon or in its menu
+    #[cfg(feature = "tray")]
+    Tray(tray::Choice),
+}
+
+const FLASH_DURATION: Duration = Duration::from_millis(400);
+const NOTICE_DURATION: Duration = Duration::from_millis(1500);
+// quiet time after the window was resized before rendering at the new size; until then
+// the last render is scaled to it
+const RESIZE_DEBOUNCE: Duration = Duration::from_millis(150);
+// quiet time after the view was last dragged or zoomed with the wheel before rendering at
+// full density again
+const MOVE_DEBOUNCE: Duration = Duration::from_millis(200);
+// window pixels per SVG pixel from which the pixel grid is drawn
+const PIXEL_GRID_ZOOM: f32 = 8.0;
+// how far the pan keys move the view, in window pixels
+const PAN_STEP: f32 = 40.0;
+// least space around an element found with the palette, in window pixels
+const FIND_MARGIN: f32 = 8.0;
+// pick the entries of the recent files and layer lists
+const DIGIT_KEYS: [VirtualKeyCode; recent::LIMIT] = {
+    use VirtualKeyCode::*;
+    [Key1, Key2, Key3, Key4, Key5, Key6, Key7, Key8, Key9]
+};
+// how close to the bottom edge a press seeks on the timeline of an animation
+const TIMELINE_GRAB: f32 = 12.0;
 
 struct State {
+    keymap: Keymap,
     file: Option<PathBuf>,
-    _watcher: Option<ReadDirectoryChangesWatcher>,
+    // the URL shown, fetched again on an interval, until a file is dropped
+    url: Option<String>,
+    // every file given on the command line and the index of the shown one
+    playlist: Vec<PathBuf>,
+    position: usize,
+    // interval between files and when the next one is shown
+    slideshow: Option<(Duration, Instant)>,
+    // directory whose most recently modified SVG is shown
+    watch_dir: Option<PathBuf>,
+    watcher: Option<watch::FileWatcher>,
+    // for starting a watcher once a file is dropped onto a window showing stdin
+    poll: Option<Duration>,
+    evp: EventLoopProxy<UserEvent>,
+    // kept for the lifetime of the window, since on X11 copied data is only served while
+    // the clipboard handle is alive
+    clipboard: Option<arboard::Clipboard>,
     options: Options,
+    // where fonts are loaded from, once there is text to draw
+    fonts: Fonts,
+    fonts_loaded: bool,
+    // the last render of the SVG, with `pixels` adding the overlays over it
+    render: Pixmap,
+    // the user transform `render` was drawn with, and that of the latest job
+    render_transform: tiny_skia::Transform,
+    submitted_transform: tiny_skia::Transform,
+    // when to render at the window's new size, while it is being resized
+    resize_settles: Option<Instant>,
+    // when to render at full density again, while the view is being moved, and the
+    // fraction of it to render at until then
+    view_settles: Option<Instant>,
+    interactive_density: f32,
     pixels: Pixmap,
+    worker: worker::Worker,
+    backend: render::Backend,
+    // of the last job given to the renderer
+    generation: u64,
+    // whether `svg_data` changed since it was last given to the renderer
+    tree_changed: bool,
+    // counts the changes given to the renderer, to tell which document a tile is of
+    tree_version: u64,
+    tiles: tiles::Cache,
+    pyramid: pyramid::Pyramid,
+    // the frame before a reload, to highlight the changes in once its render arrives
+    changed_from: Option<Pixmap>,
+    svg_source: Vec<u8>,
+    // the time of the SMIL animations of the source, if it has any
+    animation: Option<animate::Clock>,
+    // whether the left mouse button was pressed on the timeline and is still held
+    scrubbing: bool,
+    // the minimap while it is shown, and whether the left mouse button was pressed on it
+    // and is still held
+    minimap: Option<minimap::Minimap>,
+    navigating: bool,
     svg_data: Tree,
 
-    width: u32,
-    height: u32,
+    // zoom factor above which the SVG is reparsed with crisp (non-antialiased) rendering
+    antialias_off_below: Option<f64>,
+    crisp: bool,
+    show_defs: bool,
+    // the recently opened files while they are listed over the render
+    recent_menu: Option<Vec<PathBuf>>,
+    // the command palette while it is open, drawn over everything else
+    palette: Option<palette::Palette>,
+    // whether the layers are listed over the render
+    layers_menu: bool,
+    // the names of the top-level groups and the indices of those not shown
+    layer_names: Vec<String>,
+    hidden_layers: BTreeSet<usize>,
+    mask: Option<MaskShape>,
+    mask_radius: f32,
+    pixel_grid: bool,
+    rulers: bool,
+    wireframe: bool,
+    // the zoom, in pixels per user unit, the wireframe of the current tree is drawn for
+    wireframe_scale: Option<f32>,
+    // the mouse position while it is over the window, in window pixels
+    cursor: Option<(f32, f32)>,
+    inspecting: bool,
+    // the element under the mouse while inspecting
+    inspected: Option<inspect::Hit>,
+    // corners of the area being selected to zoom into, in window pixels
+    selection: Option<((f32, f32), (f32, f32))>,
+    // whether another file keeps the zoom, only centered, rather than being fitted again
+    keep_zoom: bool,
+    flash_changes: bool,
+    // frame shown instead of the render until the deadline: the render with the pixels
+    // changed by the last reload tinted, or with a notice such as a saved file's name
+    flash: Option<(Pixmap, Instant)>,
+    // simulated device pixel ratio, relative to the window's actual scale factor
+    pixel_ratio: Option<f64>,
+    // index of the only top-level group shown, when paging through layers
+    page: Option<usize>,
+    page_count: usize,
+    recolor: recolor::ColorMap,
+    // the window's size, and the fit, pan and zoom of the SVG in it
+    viewport: Viewport,
+    // also the clear color of the pixel buffer, so the letterbox blends in
+    background: tiny_skia::Color,
+    checkerboard: bool,
+    // why the file on disk is not shown, drawn over the last good render
+    error: Option<String>,
+    // when the shown document was last replaced by a new version from disk or stdin
+    reloaded: Option<Instant>,
+    // while paused, changes to the file and new versions of the URL wait until resumed
+    watching_paused: bool,
+    missed_change: bool,
+    missed_fetch: Option<Vec<u8>>,
 }
 
-fn main() -> Result<()> {
+fn main() {
     // INFRA
     pretty_env_logger::init();
+    if let Err(e) = run() {
+        exit(e);
+    }
+}
+
+// prints `error` with its causes and exits with the code of its `Failure`, or 1
+fn exit(error: anyhow::Error) -> ! {
+    eprintln!("svgview: {:#}", error);
+    let code = error.downcast_ref::<Failure>().map_or(1, Failure::exit_code);
+    std::process::exit(code)
+}
+
+// options that do not go together, which exit with the code of clap's usage errors
+fn usage(message: String) -> anyhow::Error {
+    Failure::Usage(message).into()
+}
 
+fn run() -> Result<()> {
     // CLI
-    let args: Vec<String> = std::env::args().collect();
-    if args.len() > 2 {
-        println!("Usage:\n\tsvgview <path-to-svg>");
-        std::process::exit(0);
-    }
-    let raw_svg = if args.len() == 1 || args[1] == "-"{
-	RawSVG::from_stdin()
-	    .expect("Failed to read SVG from stdin!")
-    } else {
-	let svg_path = std::fs::canonicalize(&args[1])
-	    .expect("Failed to interpret path as file!");
-	RawSVG::from_file(&svg_path)
-	    .expect("Failed to read SVG from file!")
+    let mut args = Args::load()?;
+    match &args.command {
+        Some(Command::Export(export_args)) => return export(export_args, &args.fonts),
+        Some(Command::Record(record_args)) => return record(record_args, &args.fonts),
+        Some(Command::ExportIcons(icon_args)) => return export_icons(icon_args, &args.fonts),
+        Some(Command::Serve(serve_args)) => return serve::run(serve_args, &args.fonts, args.poll),
+        None => {}
+    }
+    // hand the file to the viewer already open, if there is one
+    let instance_socket = (args.single_instance || args.hide_on_close)
+        .then(control::instance_socket)
+        .flatten();
+    if let (Some(socket), [input]) = (&instance_socket, &args.input[..]) {
+        let path = (input != "-" && !fetch::is_url(input))
+            .then(|| std::fs::canonicalize(input).ok())
+            .flatten();
+        let reply = path.and_then(|path| control::send(socket, &control::Command::Open(path)));
+        if let Some(reply) = reply {
+            return reply.map_err(anyhow::Error::msg);
+        }
+    }
+    if let Some(dir) = &mut args.watch_dir {
+	*dir = std::fs::canonicalize(&dir)
+	    .with_context(|| Failure::Input(format!("could not open {}", dir.display())))?;
+    }
+    let mut playlist = vec![];
+    let mut stdin_documents = None;
+    let mut remote = None;
+    let raw_svg = match (&args.svg, &args.watch_dir, &args.input[..]) {
+	(Some(markup), _, _) => Document::from_data(markup.as_bytes().to_vec(), "--svg", &args.fonts)?,
+	(None, Some(dir), _) => {
+	    let newest = newest_svg(dir).ok_or_else(|| {
+		Failure::Input(format!("no SVG files found in {}", dir.display()))
+	    })?;
+	    Document::from_file(&newest, &args.fonts)?
+	}
+	(None, None, [] | [_])
+	    if args.manifest.is_none() && args.input.iter().all(|input| input == "-") =>
+	{
+	    // keep reading after the first document, so a script can keep sending frames
+	    let mut documents = stream::Documents::new(std::io::BufReader::new(std::io::stdin()));
+	    let first = documents
+		.next()
+		.unwrap_or_else(|| Err(std::io::ErrorKind::UnexpectedEof.into()))
+		.with_context(|| Failure::Input("could not read an SVG from stdin".into()))?;
+	    stdin_documents = Some(documents);
+	    Document::from_data(first, "stdin", &args.fonts)?
+	}
+	(None, None, [url]) if fetch::is_url(url) => {
+	    let (fetched, source) = fetch::Remote::fetch(url)
+		.with_context(|| Failure::Input(format!("could not fetch {}", url)))?;
+	    remote = Some(fetched);
+	    Document::from_data(source, url, &args.fonts)?
+	}
+	(None, None, inputs) if inputs.iter().any(|input| fetch::is_url(input)) => {
+	    return Err(Failure::Input("a URL can only be shown on its own".into()).into());
+	}
+	(None, None, _) => {
+	    playlist = args.playlist()?.iter()
+		.map(|path| {
+		    std::fs::canonicalize(path).with_context(|| {
+			Failure::Input(format!("could not open {}", path.display()))
+		    })
+		})
+		.collect::<Result<_>>()?;
+	    let svg_path = playlist.first()
+		.ok_or_else(|| Failure::Input("no SVG files match the given patterns".into()))?;
+	    Document::from_file(svg_path, &args.fonts)?
+	}
     };
+    recolor::apply(&raw_svg.tree, args.recolor.as_deref().unwrap_or_default());
+    if args.profile {
+        eprintln!("profile: font loading {:.1?}", raw_svg.font_time);
+        eprintln!("profile: parsing {:.1?}", raw_svg.parse_time);
+    }
+    if args.raw {
+        return write_raw(&raw_svg, &args);
+    }
+    if let Some(output) = &args.output {
+        return write_output(&raw_svg, output);
+    }
+
     // DISPLAY WINDOW
-    let event_loop = EventLoop::<()>::with_user_event();
+    let event_loop = EventLoop::<UserEvent>::with_user_event();
     let mut input = WinitInputHelper::new();
     let window = {
-        WindowBuilder::new()
+        let mut builder = WindowBuilder::new()
             .with_title("svgview")
             .with_resizable(true)
-            .build(&event_loop)
-            .unwrap()
+            .with_maximized(args.maximized)
+            .with_fullscreen(args.fullscreen.then_some(Fullscreen::Borderless(None)))
+            .with_always_on_top(args.always_on_top)
+            .with_decorations(!args.borderless);
+        let monitor = event_loop.primary_monitor();
+        if let (Some(percent), Some(monitor)) = (args.size_percent, monitor) {
+            let size = monitor.size();
+            builder = builder.with_inner_size(PhysicalSize::new(
+                (size.width as f64 * percent / 100.0) as u32,
+                (size.height as f64 * percent / 100.0) as u32,
+            ));
+        }
+        builder.build(&event_loop).context("could not open a window")?
     };
 
 
@@ -69,21 +349,116 @@ fn main() -> Result<()> {
 	    &window);
         Pixels::new(window_size.width, window_size.height, surface_texture)?
     };
-    pixels.set_clear_color(Color::WHITE);
+    pixels.set_clear_color(clear_color(args.background));
+    let profile = args.color_profile.clone().unwrap_or(color::Profile::Srgb);
+    let colors = color::Converter::new(&profile)?;
 
     // APPLICATION STATE
     let evp = event_loop.create_proxy();
-    let mut state = State::new(raw_svg, window.inner_size(), evp);
+    if let Some(socket) = &args.control {
+        control::listen(socket, evp.clone())?;
+    }
+    if let Some(socket) = &instance_socket {
+        if let Some(dir) = socket.parent() {
+            let _ = std::fs::create_dir_all(dir);
+        }
+        // another viewer may have started listening since
+        if let Err(e) = control::listen(socket, evp.clone()) {
+            warn!("Not taking files from other invocations: {:#}", e);
+        }
+    }
+    if let Some(documents) = stdin_documents {
+        let evp = evp.clone();
+        thread::spawn(move || {
+            for document in documents {
+                match document {
+                    Ok(document) => {
+                        if evp.send_event(UserEvent::Document(document)).is_err() {
+                            break;
+                        }
+                    }
+                    Err(e) => {
+                        warn!("stdin error: {:?}", e);
+                        break;
+                    }
+                }
+            }
+        });
+    }
+    #[cfg(feature = "tray")]
+    let tray = match args.tray.then(|| tray::spawn(evp.clone())) {
+        Some(Err(e)) => {
+            warn!("Could not show the tray icon: {}", e);
+            None
+        }
+        tray => tray.and_then(Result::ok),
+    };
+    #[cfg(not(feature = "tray"))]
+    if args.tray {
+        warn!("Showing no tray icon, as the `tray` feature is not enabled");
+    }
+    if let Some(remote) = remote {
+        fetch::poll(remote, args.poll.unwrap_or(fetch::DEFAULT_INTERVAL), evp.clone());
+    }
+    let scale_factor = args.override_scale.unwrap_or_else(|| window.scale_factor());
+    let mut state =
+        State::new(raw_svg, playlist, &args, window.inner_size(), scale_factor, evp)?;
+    if let Some(zoom) = args.zoom {
+        state.zoom_to(zoom as f32);
+    }
+    let mut title = state.title();
+    window.set_title(&title);
 
     // INTERFACE EVENT LOOP
+    // winit cannot be asked for the window level
+    let mut always_on_top = args.always_on_top;
+    // whether the next dropped file starts a new drop rather than joining the previous one
+    let mut new_drop = true;
+    #[cfg(feature = "tray")]
+    let mut tray_paused = false;
     event_loop.run(move |event, _, control_flow| {
-        *control_flow = ControlFlow::Wait;
+        if state.flash_expired() {
+            window.request_redraw();
+        }
+        if state.slide_due() {
+            window.request_redraw();
+        }
+        if state.frame_due() {
+            window.request_redraw();
+        }
+        if state.resize_settled() {
+            window.request_redraw();
+        }
+        if state.view_settled() {
+            window.request_redraw();
+        }
+        // the title shows the zoom and how long ago the SVG was reloaded
+        if state.title() != title {
+            title = state.title();
+            window.set_title(&title);
+        }
+        // and the tray menu whether watching is paused
+        #[cfg(feature = "tray")]
+        if state.watching_paused != tray_paused {
+            tray_paused = state.watching_paused;
+            if let Some(tray) = &tray {
+                tray.update(|tray| tray.paused = tray_paused);
+            }
+        }
+        *control_flow = match state.deadline() {
+            Some(deadline) => ControlFlow::WaitUntil(deadline),
+            None => ControlFlow::Wait,
+        };
 
         // Draw the current frame
         if let Event::RedrawRequested(_) = event {
             // rasterize the SVG and copy the data to the pixel buffer
             let pixel_buffer = pixels.get_frame();
-            pixel_buffer.copy_from_slice(state.pixels.data());
+            colors.convert(state.frame(), pixel_buffer);
+            if let Some((start, end)) = state.selection {
+                let (width, height) = (state.viewport.width, state.viewport.height);
+                overlay::draw_selection(pixel_buffer, width, height, start, end);
+            }
 
             if pixels
                 .render()
@@ -95,131 +470,2122 @@ fn main() -> Result<()> {
             }
         }
 
-        if let Event::UserEvent(_) = event {
-            state.handle_file_change();
+        // renders are only passed on, nothing else handles them
+        let event = match event {
+            Event::UserEvent(UserEvent::Rendered(generation, render)) => {
+                if state.rendered(generation, render) {
+                    window.request_redraw();
+                }
+                return;
+            }
+            Event::UserEvent(UserEvent::Tile(zoom, tile, render)) => {
+                if state.rendered_tile(zoom, tile, render) {
+                    window.request_redraw();
+                }
+                return;
+            }
+            Event::UserEvent(UserEvent::Control(control::Command::Quit, _)) => {
+                *control_flow = ControlFlow::Exit;
+                return;
+            }
+            Event::UserEvent(UserEvent::Control(command, client)) => {
+                if matches!(command, control::Command::Open(_)) {
+                    window.set_visible(true);
+                    window.focus_window();
+                }
+                control::answer(client, state.control(command));
+                window.request_redraw();
+                return;
+            }
+            #[cfg(feature = "tray")]
+            Event::UserEvent(UserEvent::Tray(choice)) => {
+                match choice {
+                    tray::Choice::Show => {
+                        window.set_visible(true);
+                        window.focus_window();
+                    }
+                    tray::Choice::ReopenLast => {
+                        state.reopen_last();
+                        window.set_visible(true);
+                    }
+                    tray::Choice::PauseWatching => state.toggle_watching(),
+                    tray::Choice::Quit => *control_flow = ControlFlow::Exit,
+                }
+                window.request_redraw();
+                return;
+            }
+            Event::UserEvent(UserEvent::ReloadPending) => {
+                state.compose();
+                window.request_redraw();
+                return;
+            }
+            // only shown in place of renders that are not done yet
+            Event::UserEvent(UserEvent::Level(version, level)) => {
+                if version == state.tree_version {
+                    state.pyramid.insert(version, level);
+                }
+                return;
+            }
+            event => event,
+        };
+
+        if let Event::UserEvent(UserEvent::Document(document)) = &event {
+            state.reload(document.clone(), "stdin");
+            window.request_redraw();
+        }
+        if let Event::UserEvent(UserEvent::Fetched(source)) = &event {
+            state.fetched(source.clone());
+            window.request_redraw();
+        }
+
+        // Show files dropped onto the window; the files of one drop arrive one at a time
+        match &event {
+            Event::WindowEvent {
+                event: WindowEvent::HoveredFile(_),
+                ..
+            } => new_drop = true,
+            Event::WindowEvent {
+                event: WindowEvent::DroppedFile(path),
+                ..
+            } => {
+                state.drop_file(path, new_drop);
+                new_drop = false;
+                window.request_redraw();
+            }
+            _ => {}
+        }
+
+        if let Event::WindowEvent {
+            event: WindowEvent::CursorLeft { .. },
+            ..
+        } = event
+        {
+            state.cursor = None;
+            if state.inspect() {
+                window.request_redraw();
+            }
+        }
+
+        if let Event::UserEvent(UserEvent::FileChanged) = event {
+            if state.handle_file_change() && args.focus_follows_file {
+                window.focus_window();
+            }
             window.request_redraw();
         }
 
         // Handle input events
         if input.update(&event) {
-            // Close events
-            if input.key_pressed(VirtualKeyCode::Escape) || input.quit() {
+            if input.quit() {
+                // watching goes on in the background, until a later invocation shows the
+                // window again
+                if args.hide_on_close {
+                    window.set_visible(false);
+                    return;
+                }
                 *control_flow = ControlFlow::Exit;
                 return;
             }
 
-            // Resize the window
-            if let Some(size) = input.window_resized() {
+            // the mouse's last position is kept after it leaves, so only follow it when moving
+            if input.mouse_diff() != (0.0, 0.0) {
+                state.cursor = input.mouse();
+                if state.inspect() {
+                    window.request_redraw();
+                }
+            }
+
+            // Print where the inspected element comes from when it is clicked
+            if input.mouse_pressed(0) && !input.held_shift() {
+                if let Some(hit) = &state.inspected {
+                    println!("{}\n", inspect::markup(hit, &state.svg_source));
+                }
+            }
+
+            // Seek by pressing or dragging on the timeline of an animation
+            if input.mouse_pressed(0) && state.animation.is_some() {
+                state.scrubbing = input
+                    .mouse()
+                    .is_some_and(|(_, y)| y >= state.viewport.height as f32 - TIMELINE_GRAB);
+            }
+            if input.mouse_released(0) {
+                state.scrubbing = false;
+            }
+            if let (true, Some((x, _))) = (state.scrubbing, input.mouse()) {
+                let fraction = (x / state.viewport.width as f32).clamp(0.0, 1.0) as f64;
+                state.seek_animation(|clock| clock.seek(fraction * clock.span().end));
+                window.request_redraw();
+            }
+
+            // Center the view on the point pressed on the minimap, following the mouse
+            // while the button is held
+            if input.mouse_pressed(0) && !input.held_shift() && !state.scrubbing {
+                state.navigating = input.mouse().is_some_and(|mouse| state.on_minimap(mouse));
+            }
+            if input.mouse_released(0) {
+                state.navigating = false;
+            }
+            if let (true, Some(mouse)) = (state.navigating, input.mouse()) {
+                state.moving();
+                state.center_on_minimap(mouse);
+                window.request_redraw();
+            }
+
+            // Select an area to zoom into by dragging with Shift held
+            if input.mouse_pressed(0) && input.held_shift() {
+                state.selection = input.mouse().map(|mouse| (mouse, mouse));
+            }
+            if let (Some((_, end)), Some(mouse)) = (&mut state.selection, input.mouse()) {
+                if *end != mouse {
+                    *end = mouse;
+                    window.request_redraw();
+                }
+            }
+            if input.mouse_released(0) {
+                if let Some((start, end)) = state.selection.take() {
+                    state.zoom_to_area(start, end);
+                    window.request_redraw();
+                }
+            }
+
+            // Pan by dragging with the left mouse button
+            let (dx, dy) = input.mouse_diff();
+            if input.mouse_held(0)
+                && state.selection.is_none()
+                && !state.scrubbing
+                && !state.navigating
+                && (dx != 0.0 || dy != 0.0)
+            {
+                state.moving();
+                state.pan(dx, dy);
+                window.request_redraw();
+            }
+
+            // Move a window without a title bar by dragging it with the right mouse button
+            if args.borderless && input.mouse_pressed(1) {
+                if let Err(e) = window.drag_window() {
+                    warn!("Could not move the window: {}", e);
+                }
+            }
+
+            // Zoom with the mouse wheel, keeping the point under the cursor in place
+            let scroll = input.scroll_diff();
+            if scroll != 0.0 {
+                let anchor = input.mouse().unwrap_or((
+                    state.viewport.width as f32 / 2.0,
+                    state.viewport.height as f32 / 2.0,
+                ));
+                state.moving();
+                state.zoom_at(WHEEL_ZOOM_STEP.powf(scroll), anchor);
+                window.request_redraw();
+            }
+
+            // Keyboard zoom is around the center of the window
+            let center = (state.viewport.width as f32 / 2.0, state.viewport.height as f32 / 2.0);
+            let mut actions = state.keymap.pressed(&input);
+            // while the palette is open, keys type into it instead of triggering their
+            // actions, until an entry is run or Escape closes it
+            if let Some(palette) = &mut state.palette {
+                let command = palette.update(&input);
+                let closed = command.is_some()
+                    || input.key_pressed(VirtualKeyCode::Escape)
+                    || actions.contains(&Action::CommandPalette);
+                actions.clear();
+                if closed {
+                    state.palette = None;
+                }
+                match command {
+                    Some(palette::Command::Action(action)) => actions.push(action),
+                    Some(palette::Command::Open(file)) => state.open_playlist(vec![file]),
+                    Some(palette::Command::Find(id)) => state.find_element(&id),
+                    None => {}
+                }
+                state.rasterize_svg();
+                window.request_redraw();
+            } else if state.recent_menu.is_some() {
+                // while the recent files are listed, the digit keys open one of them and
+                // Escape closes the list rather than the window
+                let digit = DIGIT_KEYS.iter().position(|key| input.key_pressed(*key));
+                if let Some(index) = digit {
+                    state.open_recent(index);
+                    window.request_redraw();
+                    actions.clear();
+                } else if input.key_pressed(VirtualKeyCode::Escape) {
+                    actions = vec![Action::RecentFiles];
+                }
+            } else if state.layers_menu {
+                // likewise the digit keys show or hide a layer while the layers are listed
+                let digit = DIGIT_KEYS.iter().position(|key| input.key_pressed(*key));
+                if let Some(index) = digit {
+                    state.toggle_layer(index);
+                    window.request_redraw();
+                    actions.clear();
+                } else if input.key_pressed(VirtualKeyCode::Escape) {
+                    actions = vec![Action::ToggleLayers];
+                }
+            }
+            for action in actions {
+                match action {
+                    Action::Quit => {
+                        *control_flow = ControlFlow::Exit;
+                        return;
+                    }
+                    // Reparse with the current options
+                    Action::Reparse => {
+                        state.reparse();
+                        state.rasterize_svg();
+                    }
+                    Action::ZoomIn => state.zoom_at(ZOOM_STEP, center),
+                    Action::ZoomOut => state.zoom_at(1.0 / ZOOM_STEP, center),
+                    Action::ResetView => state.reset_view(),
+                    Action::CenterContent => state.center_content(),
+                    Action::ActualSize => state.zoom_to(1.0),
+                    // moving the view left moves the SVG right
+                    Action::PanLeft => state.pan(PAN_STEP, 0.0),
+                    Action::PanRight => state.pan(-PAN_STEP, 0.0),
+                    Action::PanUp => state.pan(0.0, PAN_STEP),
+                    Action::PanDown => state.pan(0.0, -PAN_STEP),
+                    Action::RotateClockwise => state.reorient(quarter_turn(1.0)),
+                    Action::RotateCounterClockwise => state.reorient(quarter_turn(-1.0)),
+                    Action::FlipHorizontal => {
+                        state.reorient(tiny_skia::Transform::from_scale(-1.0, 1.0))
+                    }
+                    Action::FlipVertical => {
+                        state.reorient(tiny_skia::Transform::from_scale(1.0, -1.0))
+                    }
+                    // Toggle the checkerboard behind transparent regions
+                    Action::ToggleCheckerboard => {
+                        state.checkerboard = !state.checkerboard;
+                        state.rasterize_svg();
+                    }
+                    // Cycle white, black, gray, checkerboard and transparent backdrops
+                    Action::CycleBackground => {
+                        (state.background, state.checkerboard) =
+                            background::next(state.background, state.checkerboard);
+                        pixels.set_clear_color(clear_color(state.background));
+                        state.rasterize_svg();
+                    }
+                    // Cycle fit, fill, fit width, fit height and actual size
+                    Action::CycleFit => state.cycle_fit_mode(),
+                    // Page through the top-level groups
+                    Action::NextPage => state.turn_page(true),
+                    Action::PreviousPage => state.turn_page(false),
+                    // Flip between the files given on the command line
+                    Action::NextFile | Action::PreviousFile => {
+                        state.turn_file(action == Action::NextFile)
+                    }
+                    Action::Copy => state.copy_image(),
+                    // Show SVG markup from the clipboard
+                    Action::Paste => state.paste(),
+                    Action::Save => state.save_png(),
+                    Action::ExportPdf => state.save_pdf(),
+                    // Toggle the definitions usage overlay
+                    Action::ToggleDefs => {
+                        state.show_defs = !state.show_defs;
+                        state.rasterize_svg();
+                    }
+                    // the resize event that follows resizes the pixel buffer
+                    Action::ToggleFullscreen => match window.fullscreen() {
+                        Some(_) => window.set_fullscreen(None),
+                        None => window.set_fullscreen(Some(Fullscreen::Borderless(None))),
+                    },
+                    Action::RecentFiles => state.toggle_recent_menu(),
+                    Action::ToggleLayers => state.toggle_layers_menu(),
+                    Action::TogglePixelGrid => {
+                        state.pixel_grid = !state.pixel_grid;
+                        state.rasterize_svg();
+                    }
+                    // Outline the element under the mouse and show its id and bounds
+                    Action::ToggleInspect => {
+                        state.inspecting = !state.inspecting;
+                        state.reparse();
+                        if !state.inspect() {
+                            state.rasterize_svg();
+                        }
+                    }
+                    // Draw only the outlines of the paths, to be drawn again in full when
+                    // turned off
+                    Action::ToggleWireframe => {
+                        state.wireframe = !state.wireframe;
+                        state.reparse();
+                        state.rasterize_svg();
+                    }
+                    Action::ToggleAnimation => {
+                        if let Some(clock) = &mut state.animation {
+                            clock.toggle_pause();
+                        }
+                    }
+                    Action::StepForward => state.seek_animation(|clock| clock.step(1)),
+                    Action::StepBackward => state.seek_animation(|clock| clock.step(-1)),
+                    Action::ToggleRulers => {
+                        state.rulers = !state.rulers;
+                        state.rasterize_svg();
+                    }
+                    Action::ToggleMinimap => state.toggle_minimap(),
+                    Action::ToggleAlwaysOnTop => {
+                        always_on_top = !always_on_top;
+                        window.set_always_on_top(always_on_top);
+                    }
+                    Action::PauseWatching => state.toggle_watching(),
+                    Action::CommandPalette => {
+                        let ids = inspect::ids(&state.svg_data);
+                        let palette = palette::Palette::new(&state.keymap, recent::load(), ids);
+                        state.palette = Some(palette);
+                        state.rasterize_svg();
+                    }
+                }
+                window.request_redraw();
+            }
+
+            // Follow the window to a monitor with another scale factor
+            if let Some(scale_factor) = input.scale_factor_changed() {
+                if args.override_scale.is_none() {
+                    state.viewport.scale_factor = scale_factor;
+                }
+            }
+
+            // Resize the window, or re-rasterize for the new scale factor
+            let resized = input.window_resized().or_else(|| {
+                input
+                    .scale_factor_changed()
+                    .map(|_| window.inner_size())
+            });
+            if let Some(size) = resized {
                 // resize pixel buffer, resize surface buffer, resize SVG buffer, then redraw
                 pixels.resize_buffer(size.width, size.height);
                 pixels.resize_surface(size.width, size.height);
-                state.resize(size.width, size.height);
+                if let Err(e) = state.resize(size.width, size.height) {
+                    exit(e);
+                }
                 window.request_redraw();
             }
         }
     });
 }
 
-struct RawSVG{
-    original_path: Option<PathBuf>,
-    document: usvg::Tree,
-    opts: Options
-}
+// render at the SVG's intrinsic size and dump the pixels, reporting the dimensions on stderr
+fn write_raw(svg: &Document, args: &Args) -> Result<()> {
+    let size = svg.tree.svg_node().size.to_screen_size();
+    let mut pixmap = Pixmap::new(size.width(), size.height())
+        .ok_or_else(|| Failure::Render("could not allocate memory for rendering".into()))?;
+    let start = Instant::now();
+    resvg::render(
+        &svg.tree,
+        usvg::FitTo::Original,
+        args.transform,
+        pixmap.as_mut(),
+    )
+    .ok_or_else(|| Failure::Render("could not rasterize the SVG".into()))?;
+    if args.profile {
+        eprintln!("profile: first render {:.1?}", start.elapsed());
+    }
 
-impl RawSVG{
-    pub fn from_file(file_path: &Path) -> Result<Self>{
-	// let file_data = std::fs::read(&file).expect("Could not read input file!");
-	let mut svg = std::fs::File::open(file_path)
-	    .expect("Failed to open input file for reading!");
+    std::io::stdout()
+        .lock()
+        .write_all(pixmap.data())
+        .with_context(|| Failure::Render("could not write the pixels to stdout".into()))?;
+    eprintln!("{}x{}", pixmap.width(), pixmap.height());
+    Ok(())
+}
 
-	let mut opts = usvg::Options {
-            resources_dir: Some(file_path.to_path_buf()),
-            ..Default::default()
+// rasterize without a window, through the same renderer as the viewer, or convert to a PDF
+fn export(args: &ExportArgs, fonts: &Fonts) -> Result<()> {
+    let extension = args.output.extension().and_then(|ext| ext.to_str());
+    let pdf = match extension.map(str::to_ascii_lowercase).as_deref() {
+        Some("png") => false,
+        Some("pdf") => true,
+        _ => return Err(usage(format!("unsupported export format: {}", args.output.display()))),
+    };
+    if pdf && args.aspect.is_some() {
+        return Err(usage("--aspect only applies to PNG exports".into()));
+    }
+    if pdf && args.output_color != output::OutputColor::Rgba {
+        return Err(usage("--output-color only applies to PNG exports".into()));
+    }
+    if args.aspect.is_some() && args.width.is_some() && args.height.is_some() {
+        return Err(usage("--aspect takes the width or the height, not both".into()));
+    }
+    let svg = Document::from_file(&args.input, fonts)?;
+    let fit_to = export_fit_to(args.width, args.height, args.zoom);
+    let mask = args.mask.map(|mask| (mask, args.mask_radius));
+    let written = if pdf {
+        let document = convert_to_pdf(&svg.tree, fit_to, args.transform, args.background, mask)?;
+        std::fs::write(&args.output, document).map_err(anyhow::Error::from)
+    } else {
+        let size = match args.aspect {
+            Some(aspect) => aspect_box(&svg.tree, fit_to, aspect)?,
+            None => still_size(&svg.tree, fit_to)?,
         };
-        opts.fontdb.load_system_fonts();
-	let mut file_data = vec![];
-	svg.read_to_end(&mut file_data)?;
-	let document = usvg::Tree::from_data(&file_data, &opts.to_ref())?;
-	Ok(Self{original_path: Some(file_path.to_path_buf()), document, opts})
+        let pixels = size.width() as u64 * size.height() as u64;
+        if mask.is_none() && pixels > BAND_PIXELS {
+            export_banded(&svg.tree, size, args)
+        } else {
+            let canvas = centered_canvas(&svg.tree, size, args.transform, args.background, mask)?;
+            let pixmap = rasterize_canvas(&svg.tree, size, &canvas)?;
+            output::write_png(&args.output, &pixmap, args.output_color)
+        }
+    };
+    written.with_context(|| Failure::Render(format!("could not write {}", args.output.display())))
+}
+
+// renders the frames of an SVG's animation and writes them to an animated image
+fn record(args: &RecordArgs, fonts: &Fonts) -> Result<()> {
+    let format = record::Format::for_path(&args.output).ok_or_else(|| {
+        usage(format!("unsupported recording format: {}", args.output.display()))
+    })?;
+    let svg = Document::from_file(&args.input, fonts)?;
+    let duration = args
+        .duration
+        .or_else(|| animate::span(&svg.source).map(|span| span.end))
+        .filter(|duration| *duration > 0.0)
+        .ok_or_else(|| {
+            Failure::Input(format!(
+                "{} is not animated; give a --duration to record it anyway",
+                args.input.display()
+            ))
+        })?;
+    let fit_to = export_fit_to(args.width, args.height, args.zoom);
+
+    let count = (duration * args.fps as f64).ceil().max(1.0) as usize;
+    let mut frames = Vec::with_capacity(count);
+    for i in 0..count {
+        let time = i as f64 / args.fps as f64;
+        let source = animate::frame(&svg.source, time);
+        let source = source.as_deref().unwrap_or(&svg.source);
+        let frame = usvg::Tree::from_data(source, &svg.options.to_ref())
+            .with_context(|| {
+                let input = args.input.display();
+                Failure::Parse(format!("{} is not a valid SVG at {:.2}s", input, time))
+            })?;
+        frames.push(rasterize_still(&frame, fit_to, args.background, None)?);
     }
-    pub fn from_stdin() -> Result<Self>{
-	let mut opts = usvg::Options::default();
-        opts.fontdb.load_system_fonts();
-	let mut file_data = vec![];
-	std::io::stdin().read_to_end(&mut file_data)?;
-	let document = usvg::Tree::from_data(&file_data, &opts.to_ref())?;
-	Ok(Self{original_path: None, document, opts})
+    record::write(&args.output, format, &frames, args.fps)
+        .with_context(|| Failure::Render(format!("could not write {}", args.output.display())))
+}
+
+// rasterizes an SVG once for each icon size, centered in a square if it is not one
+fn export_icons(args: &ExportIconsArgs, fonts: &Fonts) -> Result<()> {
+    let svg = Document::from_file(&args.input, fonts)?;
+    let name = args.input.file_stem().map_or("icon".into(), |stem| stem.to_string_lossy());
+    std::fs::create_dir_all(&args.out_dir).with_context(|| {
+        Failure::Render(format!("could not create {}", args.out_dir.display()))
+    })?;
+    let mask = args.mask.map(|mask| (mask, args.mask_radius));
+    for &size in &args.sizes {
+        let square = usvg::ScreenSize::new(size, size)
+            .ok_or_else(|| Failure::Render("invalid icon size".into()))?;
+        let pixmap = rasterize_centered(&svg.tree, square, args.background, mask)?;
+        let path = args.out_dir.join(format!("{}-{}x{}.png", name, size, size));
+        pixmap
+            .save_png(&path)
+            .with_context(|| Failure::Render(format!("could not write {}", path.display())))?;
     }
+    Ok(())
+}
+
+fn export_fit_to(width: Option<u32>, height: Option<u32>, zoom: Option<f32>) -> usvg::FitTo {
+    match (width, height, zoom) {
+        (Some(width), Some(height), _) => usvg::FitTo::Size(width, height),
+        (Some(width), None, _) => usvg::FitTo::Width(width),
+        (None, Some(height), _) => usvg::FitTo::Height(height),
+        (None, None, Some(zoom)) => usvg::FitTo::Zoom(zoom),
+        (None, None, None) => usvg::FitTo::Original,
+    }
+}
+
+// the size of `aspect`, a ratio of width to height, that is as wide as `fit_to` makes
+// `tree`, or as high when only a height is given, and else just holds it
+fn aspect_box(tree: &Tree, fit_to: usvg::FitTo, aspect: (u32, u32)) -> Result<usvg::ScreenSize> {
+    let ratio = aspect.0 as f64 / aspect.1 as f64;
+    let invalid = || Failure::Render("invalid export size".into());
+    let fitted = fit_to.fit_to(tree.svg_node().size.to_screen_size()).ok_or_else(invalid)?;
+    let (width, height) = (fitted.width() as f64, fitted.height() as f64);
+    let (width, height) = match fit_to {
+        usvg::FitTo::Width(_) => (width, width / ratio),
+        usvg::FitTo::Height(_) => (height * ratio, height),
+        _ => (width.max(height * ratio), height.max(width / ratio)),
+    };
+    let size = usvg::ScreenSize::new(width.round().max(1.0) as u32, height.round().max(1.0) as u32);
+    size.ok_or_else(|| invalid().into())
+}
+
+// exports larger than this many pixels are rendered and written a band of rows at a time,
+// which keeps about this many pixels in memory
+const BAND_PIXELS: u64 = 4096 * 4096;
+
+// writes `tree` fitted into a PNG of `size` like `rasterize_centered`, one band at a time
+fn export_banded(tree: &Tree, size: usvg::ScreenSize, args: &ExportArgs) -> Result<()> {
+    let canvas = centered_canvas(tree, size, args.transform, args.background, None)?;
+    let (width, height) = (size.width(), size.height());
+    let band_height = (BAND_PIXELS / width as u64).clamp(1, height as u64) as u32;
+    let draw = |band: &mut Pixmap, top: u32| {
+        render::draw_part(tree, &canvas, (width, height), (0, top as i32), band)
+            .ok_or_else(|| Failure::Render("could not rasterize the SVG".into()).into())
+    };
+    let size = (width, height);
+    output::write_png_banded(&args.output, size, band_height, args.output_color, draw)
+}
+
+// the size `fit_to` gives `tree` on its own, for `export` and `record`
+fn still_size(tree: &Tree, fit_to: usvg::FitTo) -> Result<usvg::ScreenSize> {
+    let size = fit_to.fit_to(tree.svg_node().size.to_screen_size());
+    size.ok_or_else(|| Failure::Render("invalid export size".into()).into())
+}
+
+// renders `tree` on its own, at the size `fit_to` gives it, for `export` and `record`
+fn rasterize_still(
+    tree: &Tree,
+    fit_to: usvg::FitTo,
+    background: tiny_skia::Color,
+    mask: Option<(MaskShape, f32)>,
+) -> Result<Pixmap> {
+    rasterize_centered(tree, still_size(tree, fit_to)?, background, mask)
+}
+
+// renders `tree` fitted into a pixmap of `size`, in the middle of the side it does not fill,
+// and on whole pixels so that small icons stay sharp
+fn rasterize_centered(
+    tree: &Tree,
+    size: usvg::ScreenSize,
+    background: tiny_skia::Color,
+    mask: Option<(MaskShape, f32)>,
+) -> Result<Pixmap> {
+    let canvas = centered_canvas(tree, size, tiny_skia::Transform::identity(), background, mask)?;
+    rasterize_canvas(tree, size, &canvas)
+}
+
+// renders `tree` into a pixmap of `size` as `canvas` says
+fn rasterize_canvas(
+    tree: &Tree,
+    size: usvg::ScreenSize,
+    canvas: &render::Canvas,
+) -> Result<Pixmap> {
+    let mut pixmap = Pixmap::new(size.width(), size.height())
+        .ok_or_else(|| Failure::Render("could not allocate memory for rendering".into()))?;
+    render::draw(tree, canvas, &mut pixmap)
+        .ok_or_else(|| Failure::Render("could not rasterize the SVG".into()))?;
+    Ok(pixmap)
+}
+
+// the canvas that `rasterize_centered` draws `tree` with into a pixmap of `size`, with
+// `transform` applied to the fitted SVG as in the window
+fn centered_canvas(
+    tree: &Tree,
+    size: usvg::ScreenSize,
+    transform: tiny_skia::Transform,
+    background: tiny_skia::Color,
+    mask: Option<(MaskShape, f32)>,
+) -> Result<render::Canvas> {
+    let fitted = usvg::FitTo::Size(size.width(), size.height())
+        .fit_to(tree.svg_node().size.to_screen_size())
+        .ok_or_else(|| Failure::Render("invalid export size".into()))?;
+    let area = tiny_skia::Rect::from_xywh(0.0, 0.0, size.width() as f32, size.height() as f32);
+    let offset = |side: u32, fitted: u32| (side.saturating_sub(fitted) / 2) as f32;
+    Ok(render::Canvas {
+        fit_mode: FitMode::Fit,
+        transform: tiny_skia::Transform::from_translate(
+            offset(size.width(), fitted.width()),
+            offset(size.height(), fitted.height()),
+        )
+        .pre_concat(transform),
+        background,
+        checkerboard: false,
+        mask: mask.map(|(shape, _)| shape).zip(area),
+        mask_radius: mask.map_or(0.0, |(_, radius)| radius),
+        density: 1.0,
+        scale_factor: 1.0,
+        turned: false,
+    })
+}
+
+// `tree` as a vector PDF with a page of the size `fit_to` gives it, like `rasterize_still`:
+// svg2pdf converts the document to a form, which the page draws over the background and
+// inside the mask, with `transform` applied as in `centered_canvas`
+fn convert_to_pdf(
+    tree: &Tree,
+    fit_to: usvg::FitTo,
+    transform: tiny_skia::Transform,
+    background: tiny_skia::Color,
+    mask: Option<(MaskShape, f32)>,
+) -> Result<Vec<u8>> {
+    use pdf_writer::{Content, Finish, Name, PdfWriter, Rect, Ref};
+
+    let size = fit_to
+        .fit_to(tree.svg_node().size.to_screen_size())
+        .ok_or_else(|| Failure::Render("invalid export size".into()))?;
+    let area = tiny_skia::Rect::from_xywh(0.0, 0.0, size.width() as f32, size.height() as f32);
+    let clip = mask
+        .zip(area)
+        .and_then(|((shape, radius), area)| mask::outline(shape, radius, area));
+    // CSS pixels are 1/96 inch, PDF points 1/72 inch
+    let (width, height) = (size.width() as f32 * 0.75, size.height() as f32 * 0.75);
+    // the form comes last, as svg2pdf numbers the objects it needs from there on
+    let (catalog, pages, page, contents, opacity, form) = (
+        Ref::new(1),
+        Ref::new(2),
+        Ref::new(3),
+        Ref::new(4),
+        Ref::new(5),
+        Ref::new(6),
+    );
+    let (opacity_name, form_name) = (Name(b"bg"), Name(b"svg"));
+
+    let mut content = Content::new();
+    if background.alpha() > 0.0 {
+        content
+            .save_state()
+            .set_parameters(opacity_name)
+            .set_fill_rgb(background.red(), background.green(), background.blue())
+            .rect(0.0, 0.0, width, height)
+            .fill_nonzero()
+            .restore_state();
+    }
+    // drawn in page pixels from here on, with the y axis pointing down as the mask's
+    content
+        .save_state()
+        .transform([0.75, 0.0, 0.0, -0.75, 0.0, height]);
+    if let Some(clip) = &clip {
+        let mut last = tiny_skia::Point::zero();
+        for segment in clip.segments() {
+            use tiny_skia::PathSegment;
+            match segment {
+                PathSegment::MoveTo(p) => content.move_to(p.x, p.y),
+                PathSegment::LineTo(p) => content.line_to(p.x, p.y),
+                // as the cubic with its control points two thirds of the way to the
+                // quadratic's
+                PathSegment::QuadTo(c, p) => {
+                    let toward = |from: tiny_skia::Point| {
+                        (from.x + (c.x - from.x) * 2.0 / 3.0, from.y + (c.y - from.y) * 2.0 / 3.0)
+                    };
+                    let ((x1, y1), (x2, y2)) = (toward(last), toward(p));
+                    content.cubic_to(x1, y1, x2, y2, p.x, p.y)
+                }
+                PathSegment::CubicTo(c1, c2, p) => {
+                    content.cubic_to(c1.x, c1.y, c2.x, c2.y, p.x, p.y)
+                }
+                PathSegment::Close => content.close_path(),
+            };
+            last = match segment {
+                PathSegment::MoveTo(p) | PathSegment::LineTo(p) => p,
+                PathSegment::QuadTo(_, p) | PathSegment::CubicTo(_, _, p) => p,
+                PathSegment::Close => last,
+            };
+        }
+        content.clip_nonzero().end_path();
+    }
+    // the form is one unit square, with its y axis pointing up
+    let (w, h) = (size.width() as f32, size.height() as f32);
+    let ts = transform;
+    content
+        .transform([ts.sx, ts.ky, ts.kx, ts.sy, ts.tx, ts.ty])
+        .transform([w, 0.0, 0.0, -h, 0.0, h])
+        .x_object(form_name)
+        .restore_state();
+
+    let mut writer = PdfWriter::new();
+    writer.catalog(catalog).pages(pages);
+    writer.pages(pages).kids([page]).count(1);
+    let mut page = writer.page(page);
+    page.media_box(Rect::new(0.0, 0.0, width, height));
+    page.parent(pages);
+    page.contents(contents);
+    let mut resources = page.resources();
+    resources.x_objects().pair(form_name, form);
+    resources.ext_g_states().pair(opacity_name, opacity);
+    resources.finish();
+    page.finish();
+    writer
+        .ext_graphics(opacity)
+        .non_stroking_alpha(background.alpha());
+    writer.stream(contents, &content.finish());
+    let options = svg2pdf::Options {
+        viewport: Some((size.width() as f64, size.height() as f64)),
+        aspect: None,
+        dpi: 96.0,
+    };
+    svg2pdf::convert_tree_into(tree, options, &mut writer, form);
+    Ok(writer.finish())
+}
+
+// tells the event loop that a watched file was written
+fn file_changed(evp: &EventLoopProxy<UserEvent>) -> impl Fn() -> bool + Send + 'static {
+    let evp = evp.clone();
+    move || evp.send_event(UserEvent::FileChanged).is_ok()
+}
+
+// tells the event loop that a watched file is being written, to show that it reloads soon
+fn reload_pending(evp: &EventLoopProxy<UserEvent>) -> impl Fn() + Send + 'static {
+    let evp = evp.clone();
+    move || {
+        let _ = evp.send_event(UserEvent::ReloadPending);
+    }
+}
+
+// the transform that undoes `ts`, if it does not collapse the plane
+fn invert(ts: tiny_skia::Transform) -> Option<tiny_skia::Transform> {
+    let det = ts.sx * ts.sy - ts.kx * ts.ky;
+    if !det.is_normal() {
+        return None;
+    }
+    Some(tiny_skia::Transform::from_row(
+        ts.sy / det,
+        -ts.ky / det,
+        -ts.kx / det,
+        ts.sx / det,
+        (ts.kx * ts.ty - ts.sy * ts.tx) / det,
+        (ts.ky * ts.tx - ts.sx * ts.ty) / det,
+    ))
+}
+
+// how many times `ts` scales areas up, as a factor for lengths
+fn magnification(ts: tiny_skia::Transform) -> f32 {
+    (ts.sx * ts.sy - ts.kx * ts.ky).abs().sqrt()
+}
+
+// the window's pixmap; a `Failure` rather than a panic, since huge windows can run out
+fn display_pixmap(width: u32, height: u32) -> Result<Pixmap> {
+    Pixmap::new(width, height)
+        .ok_or_else(|| Failure::Render("could not allocate memory for the display".into()).into())
+}
+
+fn clear_color(color: tiny_skia::Color) -> pixels::wgpu::Color {
+    pixels::wgpu::Color {
+        r: color.red() as f64,
+        g: color.green() as f64,
+        b: color.blue() as f64,
+        a: color.alpha() as f64,
+    }
+}
+
+// the output format is picked by the file extension
+fn write_output(svg: &Document, path: &Path) -> Result<()> {
+    match path.extension().and_then(|ext| ext.to_str()) {
+        Some("svg") => {
+            let text = svg.tree.to_string(&usvg::XmlOptions::default());
+            std::fs::write(path, text)?;
+        }
+        _ => return Err(usage(format!("unsupported output format: {}", path.display()))),
+    }
+    Ok(())
 }
 
 impl State {
-    fn new(svg: RawSVG, window_size: PhysicalSize<u32>, evp: EventLoopProxy<()>) -> Self {
+    fn new(
+        svg: Document,
+        playlist: Vec<PathBuf>,
+        args: &Args,
+        window_size: PhysicalSize<u32>,
+        scale_factor: f64,
+        evp: EventLoopProxy<UserEvent>,
+    ) -> Result<Self> {
 	// FILE WATCHER
-	let watcher = svg.original_path.clone()
-	    .map(|path|{
-		let (tx, rx) = channel();
-		let mut watcher = raw_watcher(tx)
-		    .expect("Could not create filesystem watcher!");
-		watcher
-		    .watch(path, RecursiveMode::NonRecursive)
-		    .expect("Could not start filesystem watcher!");
-
-		thread::spawn(move || loop {
-		    match rx.recv() {
-			Ok(event) => {
-			    if let Ok(Op::CLOSE_WRITE) = event.op {
-				evp.send_event(())
-				    .expect("Failed to notify UI of file write!");
-			    }
-			}
-			Err(e) => warn!("watch error: {:?}", e),
-		    }
-		});
-
-		watcher
-	    });
+	let on_change = file_changed(&evp);
+	let watcher = match (&args.watch_dir, &svg.path) {
+	    (Some(dir), _) => Some(watch::FileWatcher::dir(dir, args.poll, on_change)),
+	    (None, Some(file)) => Some(watch::FileWatcher::file(file, args.poll, on_change)),
+	    (None, None) => None,
+	}
+	.transpose()
+	.context("could not start the file system watcher")?;
+	if let Some(watcher) = &watcher {
+	    watcher.wake_when_pending(reload_pending(&evp));
+	}
         let mut state = Self {
-	    _watcher: watcher,
-            file: svg.original_path,
-            width: window_size.width,
-            height: window_size.height,
+            keymap: args.keymap.clone(),
+	    watcher,
+            clipboard: arboard::Clipboard::new()
+                .map_err(|e| warn!("Clipboard unavailable: {}", e))
+                .ok(),
+            file: svg.path,
+            url: args.input.first().filter(|input| fetch::is_url(input)).cloned(),
+            slideshow: args
+                .slideshow
+                .filter(|_| playlist.len() > 1)
+                .map(|interval| (interval, Instant::now() + interval)),
+            playlist,
+            position: 0,
+            watch_dir: args.watch_dir.clone(),
+            poll: args.poll,
+            worker: worker::Worker::start(evp.clone(), args.backend, args.render_timeout),
+            backend: args.backend,
+            evp,
 
-            options: svg.opts,
-            pixels: Pixmap::new(window_size.width, window_size.height)
-                .expect("Could not allocate memory for display!"),
-            svg_data: svg.document,
+            options: svg.options,
+            fonts: args.fonts.clone(),
+            fonts_loaded: svg.fonts_loaded,
+            render: display_pixmap(window_size.width, window_size.height)?,
+            pixels: display_pixmap(window_size.width, window_size.height)?,
+            render_transform: tiny_skia::Transform::identity(),
+            submitted_transform: tiny_skia::Transform::identity(),
+            resize_settles: None,
+            view_settles: None,
+            interactive_density: args.interactive_density,
+            generation: 0,
+            tree_changed: true,
+            tree_version: 0,
+            tiles: tiles::Cache::default(),
+            pyramid: pyramid::Pyramid::default(),
+            changed_from: None,
+            animation: animate::Clock::for_source(&svg.source),
+            svg_source: svg.source,
+            scrubbing: false,
+            minimap: None,
+            navigating: false,
+            svg_data: svg.tree,
+            antialias_off_below: args.antialias_off_below,
+            crisp: false,
+            show_defs: false,
+            recent_menu: None,
+            palette: None,
+            layers_menu: false,
+            layer_names: vec![],
+            hidden_layers: BTreeSet::new(),
+            mask: args.mask,
+            mask_radius: args.mask_radius,
+            selection: None,
+            pixel_grid: true,
+            rulers: false,
+            wireframe: false,
+            wireframe_scale: None,
+            cursor: None,
+            inspecting: false,
+            inspected: None,
+            keep_zoom: args.center,
+            flash_changes: args.flash_changes,
+            flash: None,
+            pixel_ratio: args.pixel_ratio,
+            page: args.page,
+            page_count: 0,
+            recolor: args.recolor.clone().unwrap_or_default(),
+            viewport: Viewport {
+                transform: args.transform,
+                ..Viewport::new(
+                    window_size.width,
+                    window_size.height,
+                    scale_factor,
+                    args.fit.unwrap_or(FitMode::Fit),
+                )
+            },
+            background: args.background,
+            checkerboard: false,
+            error: None,
+            reloaded: None,
+            watching_paused: false,
+            missed_change: false,
+            missed_fetch: None,
         };
-        state.rasterize_svg();
-        state
+        if let Some(file) = &state.file {
+            recent::record(file);
+        }
+        state.update_watched_files();
+        if state.page.is_some() || state.animation.is_some() {
+            state.reparse();
+        }
+        state.update_antialiasing();
+        let start = Instant::now();
+        state.try_rasterize()?;
+        if args.profile {
+            eprintln!("profile: first render {:.1?}", start.elapsed());
+        }
+        Ok(state)
     }
 
-    fn resize(&mut self, width: u32, height: u32) {
-        self.width = width;
-        self.height = height;
-        self.pixels =
-            Pixmap::new(self.width, self.height).expect("Could not allocate memory for display!");
-        self.rasterize_svg();
+    fn resize(&mut self, width: u32, height: u32) -> Result<()> {
+        self.flash = None;
+        // a minimized window has no pixels; keep the last frame for when it comes back
+        if width == 0 || height == 0 {
+            return Ok(());
+        }
+        self.viewport.width = width;
+        self.viewport.height = height;
+        self.pixels = display_pixmap(width, height)?;
+        self.update_antialiasing();
+        // rendering for every step of a drag lags behind it
+        self.resize_settles = Some(Instant::now() + RESIZE_DEBOUNCE);
+        self.compose();
+        Ok(())
     }
 
-    fn handle_file_change(&mut self) {
-	if let Some(file) = &self.file{
-            let svg_data = std::fs::read(&file).expect("Could not read input file!");
-            self.svg_data = usvg::Tree::from_data(&svg_data, &self.options.to_ref())
-		.expect("Could not parse data as SVG!");
-            self.rasterize_svg();
+    // renders at the window's size once it has not changed for a moment
+    fn resize_settled(&mut self) -> bool {
+        match self.resize_settles {
+            Some(deadline) if Instant::now() >= deadline => {
+                self.resize_settles = None;
+                self.rasterize_svg();
+                true
+            }
+            _ => false,
+        }
+    }
+
+    // to be called before each step of a drag or turn of the wheel, which are rendered at
+    // the interactive density until the view rests
+    fn moving(&mut self) {
+        self.view_settles = Some(Instant::now() + MOVE_DEBOUNCE);
+    }
+
+    // renders at full density once the view has not moved for a moment
+    fn view_settled(&mut self) -> bool {
+        match self.view_settles {
+            Some(deadline) if Instant::now() >= deadline => {
+                self.view_settles = None;
+                self.rasterize_svg();
+                true
+            }
+            _ => false,
+        }
+    }
+
+    // returns whether the file was reloaded, and was valid
+    fn handle_file_change(&mut self) -> bool {
+        if self.watching_paused {
+            self.missed_change = true;
+            return false;
+        }
+	if let Some(watcher) = &self.watcher {
+	    watcher.clear_pending();
+	}
+	if let Some(newest) = self.watch_dir.as_deref().and_then(newest_svg) {
+	    self.file = Some(newest);
 	}
+	if let Some(file) = self.file.clone() {
+            // the file may be gone for a moment while an editor replaces it
+            match read_svg(&file) {
+                Ok(source) => {
+                    self.reload(source, &file.display().to_string());
+                    return self.error.is_none();
+                }
+                Err(e) => warn!("Could not read {}: {}", file.display(), e),
+            }
+	}
+	// without the pending reload
+	self.compose();
+	false
+    }
+
+    // like `load_source`, for a new version of the shown document
+    fn reload(&mut self, source: Vec<u8>, name: &str) {
+        self.load_source(source, name);
+        if self.error.is_none() {
+            self.reloaded = Some(Instant::now());
+        }
+    }
+
+    // show `source` in place of the current document, or keep showing the last good
+    // render with an error banner until a valid SVG is loaded
+    fn load_source(&mut self, source: Vec<u8>, name: &str) {
+        let previous = self.flash_changes.then(|| self.pixels.clone());
+        let animation = animate::Clock::for_source(&source);
+        let last_good = std::mem::replace(&mut self.svg_source, source);
+        let last_animation = std::mem::replace(&mut self.animation, animation);
+        if let Err(e) = self.try_reparse() {
+            // later reparses, e.g. for crisp rendering, need a valid source
+            self.svg_source = last_good;
+            self.animation = last_animation;
+            self.error = Some(format!("{}: {}", name, e));
+            self.rasterize_svg();
+            return;
+        }
+        self.error = None;
+        self.update_watched_files();
+        self.update_antialiasing();
+        self.changed_from = previous;
+        self.rasterize_svg();
+    }
+
+    // watch the shown file and the images and stylesheets it links to
+    fn update_watched_files(&mut self) {
+        let (watcher, file) = match (&mut self.watcher, &self.watch_dir, &self.file) {
+            (Some(watcher), None, Some(file)) => (watcher, file),
+            _ => return,
+        };
+        let mut files = vec![file.clone()];
+        if let Some(dir) = file.parent() {
+            files.extend(resources::external_files(&self.svg_source, dir));
+        }
+        if let Err(e) = watcher.set_files(files) {
+            warn!("Could not watch linked files: {:?}", e);
+        }
+    }
+
+    // the pixels to display: the change highlight or a notice for a moment, otherwise the render
+    fn frame(&self) -> &[u8] {
+        match &self.flash {
+            Some((highlighted, _)) => highlighted.data(),
+            None => self.pixels.data(),
+        }
+    }
+
+    fn flash_deadline(&self) -> Option<Instant> {
+        self.flash.as_ref().map(|(_, deadline)| *deadline)
+    }
+
+    // when the time since the last reload shown in the title changes next
+    fn title_deadline(&self) -> Option<Instant> {
+        let reloaded = self.reloaded?;
+        let age = reloaded.elapsed().as_secs();
+        let unit = match age {
+            0..=59 => 1,
+            60..=3599 => 60,
+            _ => 3600,
+        };
+        Some(reloaded + Duration::from_secs((age / unit + 1) * unit))
+    }
+
+    // the next time the event loop has to wake up without any input
+    fn deadline(&self) -> Option<Instant> {
+        let slide = self.slideshow.map(|(_, deadline)| deadline);
+        let frame = self.animation.as_ref().and_then(animate::Clock::next_frame);
+        let settles = [self.resize_settles, self.view_settles];
+        [self.flash_deadline(), slide, self.title_deadline(), frame]
+            .into_iter()
+            .chain(settles)
+            .flatten()
+            .min()
+    }
+
+    // moves on to the next file once the slideshow interval has passed
+    fn slide_due(&mut self) -> bool {
+        match self.slideshow {
+            Some((_, deadline)) if Instant::now() >= deadline => {
+                self.turn_file(true);
+                true
+            }
+            _ => false,
+        }
+    }
+
+    // parses the next frame of a playing animation once it is due
+    fn frame_due(&mut self) -> bool {
+        match self.animation.as_ref().and_then(animate::Clock::next_frame) {
+            Some(due) if Instant::now() >= due => {
+                self.show_animation_frame();
+                true
+            }
+            _ => false,
+        }
+    }
+
+    // changes the time of the animation, if there is one, and shows the frame at that time
+    fn seek_animation(&mut self, seek: impl FnOnce(&mut animate::Clock)) {
+        if let Some(clock) = &mut self.animation {
+            seek(clock);
+            self.show_animation_frame();
+        }
+    }
+
+    fn show_animation_frame(&mut self) {
+        if let Err(e) = self.try_reparse() {
+            warn!("Could not animate the SVG: {}", e);
+            self.animation = None;
+        }
+        self.rasterize_svg();
+    }
+
+    // drops the change highlight or notice once it has been shown long enough
+    fn flash_expired(&mut self) -> bool {
+        match self.flash_deadline() {
+            Some(deadline) if Instant::now() >= deadline => {
+                self.flash = None;
+                true
+            }
+            _ => false,
+        }
+    }
+
+    // rebuild the usvg options from the current settings and parse the stored source again
+    fn reparse(&mut self) {
+        if let Err(e) = self.try_reparse() {
+            // the last good document stays, e.g. for an animation frame that is not valid
+            let name = self.file.as_ref().map(|file| file.display().to_string());
+            let name = name.unwrap_or_else(|| "the SVG".to_string());
+            self.error = Some(format!("{}: {}", name, e));
+        }
+    }
+
+    // like `reparse`, but returns why the source is not valid rather than showing it
+    fn try_reparse(&mut self) -> Result<(), usvg::Error> {
+        if !self.fonts_loaded && fonts::has_text(&self.svg_source) {
+            self.load_fonts();
+        }
+        let fontdb = std::mem::replace(&mut self.options.fontdb, usvg::fontdb::Database::new());
+        let font_family = std::mem::take(&mut self.options.font_family);
+        let (shape_rendering, text_rendering) = if self.crisp {
+            (ShapeRendering::CrispEdges, TextRendering::OptimizeSpeed)
+        } else {
+            (ShapeRendering::default(), TextRendering::default())
+        };
+        self.options = Options {
+            resources_dir: self.file.as_deref().and_then(Path::parent).map(Path::to_path_buf),
+            shape_rendering,
+            text_rendering,
+            // keep layers (groups with just an id) so they can be paged through, listed
+            // and hidden, and so the inspector can name the group of an element without an id
+            keep_named_groups: self.page.is_some()
+                || self.inspecting
+                || self.layers_menu
+                || !self.hidden_layers.is_empty(),
+            fontdb,
+            font_family,
+            dpi: self.options.dpi,
+            ..Default::default()
+        };
+        // an animated document is parsed as it is at the current time
+        let frame = match &mut self.animation {
+            Some(clock) => animate::frame(&self.svg_source, clock.show_frame()),
+            None => None,
+        };
+        let source = frame.as_deref().unwrap_or(&self.svg_source);
+        self.svg_data = usvg::Tree::from_data(source, &self.options.to_ref())?;
+        self.hide_layers();
+        self.show_page();
+        recolor::apply(&self.svg_data, &self.recolor);
+        self.wireframe_scale = None;
+        self.tree_changed = true;
+        Ok(())
+    }
+
+    // fills the font database the first time text is drawn, in the SVG or over it
+    fn load_fonts(&mut self) {
+        if !self.fonts_loaded {
+            fonts::load(&mut self.options.fontdb, &self.fonts);
+            self.fonts_loaded = true;
+        }
+    }
+
+    // detach the hidden top-level groups, after naming all of them
+    fn hide_layers(&mut self) {
+        let groups = layers::groups(&self.svg_data);
+        self.layer_names = layers::names(&groups, &self.svg_source);
+        for (i, mut group) in groups.into_iter().enumerate() {
+            if self.hidden_layers.contains(&i) {
+                group.detach();
+            }
+        }
+    }
+
+    fn toggle_layers_menu(&mut self) {
+        self.layers_menu = !self.layers_menu;
+        // the layers are only kept as groups while listed or hidden
+        self.reparse();
+        if self.layers_menu && self.layer_names.is_empty() {
+            self.layers_menu = false;
+            return self.notify("No layers (top-level groups)".to_string());
+        }
+        self.rasterize_svg();
+    }
+
+    // shows the `index`-th layer if it is hidden, or hides it
+    fn toggle_layer(&mut self, index: usize) {
+        if index >= self.layer_names.len() {
+            return;
+        }
+        if !self.hidden_layers.remove(&index) {
+            self.hidden_layers.insert(index);
+        }
+        self.reparse();
+        self.rasterize_svg();
+    }
+
+    // detach every top-level group except the current page
+    fn show_page(&mut self) {
+        let page = match self.page {
+            Some(page) => page,
+            None => return,
+        };
+        let mut pages = layers::groups(&self.svg_data);
+        self.page_count = pages.len();
+        if pages.is_empty() {
+            return;
+        }
+        let page = page.min(pages.len() - 1);
+        self.page = Some(page);
+        for (i, node) in pages.iter_mut().enumerate() {
+            if i != page {
+                node.detach();
+            }
+        }
+    }
+
+    fn turn_page(&mut self, forward: bool) {
+        let count = self.page_count.max(1);
+        self.page = Some(match (self.page, forward) {
+            (None, true) => 0,
+            // the page count is only known once the layers have been kept, so ask for the last
+            (None, false) => usize::MAX,
+            (Some(page), true) => (page + 1) % count,
+            (Some(page), false) => (page + count - 1) % count,
+        });
+        self.reparse();
+        self.rasterize_svg();
+    }
+
+    // the shown file's name and simulated pixel ratio, the zoom, and how long ago the
+    // file was reloaded or that it failed to, e.g. "icon.svg — 250% — reloaded 2s ago"
+    fn title(&self) -> String {
+        let mut name = match self.file.as_deref().and_then(Path::file_name) {
+            Some(name) => name.to_string_lossy().into_owned(),
+            None => match &self.url {
+                Some(url) => fetch::file_name(url).to_string(),
+                None => "svgview".to_string(),
+            },
+        };
+        if let Some(ratio) = self.pixel_ratio {
+            name += &format!(" @{}x", ratio);
+        }
+        let zoom = self.viewport.zoom(&self.svg_data) / self.viewport.scale_factor;
+        let mut parts = vec![name, format!("{:.0}%", zoom * 100.0)];
+        if self.watching_paused {
+            parts.push("watching paused".to_string());
+        }
+        if self.error.is_some() {
+            parts.push("failed to load".to_string());
+        } else if let Some(reloaded) = self.reloaded {
+            let age = reloaded.elapsed().as_secs();
+            parts.push(match age {
+                0..=59 => format!("reloaded {}s ago", age),
+                60..=3599 => format!("reloaded {}m ago", age / 60),
+                _ => format!("reloaded {}h ago", age / 3600),
+            });
+        }
+        parts.extend(self.cursor_readout());
+        parts.join(" \u{2014} ")
+    }
+
+    // hit-tests the mouse position while inspecting and redraws if another element is under
+    // it, returning whether it did
+    fn inspect(&mut self) -> bool {
+        let to_user = invert(self.viewport.user_transform(&self.svg_data));
+        let hit = match (self.inspecting, self.cursor, to_user) {
+            (true, Some((x, y)), Some(to_user)) => {
+                let mut point = tiny_skia::Point::from_xy(x, y);
+                to_user.map_points(std::slice::from_mut(&mut point));
+                let (x, y) = (point.x as f64, point.y as f64);
+                inspect::hit_test(&self.svg_data, &self.svg_source, x, y)
+            }
+            _ => None,
+        };
+        if hit.as_ref().map(|hit| &hit.lines) == self.inspected.as_ref().map(|hit| &hit.lines) {
+            return false;
+        }
+        self.inspected = hit;
+        self.rasterize_svg();
+        true
+    }
+
+    // the user coordinates under the mouse and the color shown there, unpremultiplied
+    fn cursor_readout(&self) -> Option<String> {
+        let (x, y) = self.cursor?;
+        let mut point = tiny_skia::Point::from_xy(x, y);
+        let to_user = invert(self.viewport.user_transform(&self.svg_data))?;
+        to_user.map_points(std::slice::from_mut(&mut point));
+        let color = self.pixels.pixel(x as u32, y as u32)?.demultiply();
+        Some(format!(
+            "{:.1}, {:.1} #{:02x}{:02x}{:02x}{:02x}",
+            point.x,
+            point.y,
+            color.red(),
+            color.green(),
+            color.blue(),
+            color.alpha()
+        ))
+    }
+
+    // show the next or previous file of the playlist, wrapping around
+    fn turn_file(&mut self, forward: bool) {
+        // navigating by hand restarts the slideshow interval
+        if let Some((interval, deadline)) = &mut self.slideshow {
+            *deadline = Instant::now() + *interval;
+        }
+        let count = self.playlist.len();
+        if count < 2 {
+            return;
+        }
+        self.show_file(if forward {
+            (self.position + 1) % count
+        } else {
+            (self.position + count - 1) % count
+        });
+    }
+
+    // replaces the playlist with a dropped file, or the SVGs in a dropped directory, or
+    // adds them to it when they are part of the same drop as the previous file
+    fn drop_file(&mut self, path: &Path, new_drop: bool) {
+        let paths = if path.is_dir() {
+            svgs_in(path)
+        } else {
+            vec![path.to_path_buf()]
+        };
+        if paths.is_empty() {
+            return self.notify(format!("No SVG files in {}", path.display()));
+        }
+        if !new_drop {
+            self.playlist.extend(paths);
+            return;
+        }
+        self.open_playlist(paths);
+    }
+
+    // shows the first of `paths`, which must not be empty, and watches it; only files
+    // opened this way count as recent, not those the slideshow or paging goes on to
+    fn open_playlist(&mut self, paths: Vec<PathBuf>) {
+        recent::record(&paths[0]);
+        self.playlist = paths;
+        // the files are shown instead of the newest file of the watched directory
+        self.watch_dir = None;
+        if self.watcher.is_none() {
+            match watch::FileWatcher::file(&self.playlist[0], self.poll, file_changed(&self.evp)) {
+                Ok(watcher) => {
+                    watcher.wake_when_pending(reload_pending(&self.evp));
+                    self.watcher = Some(watcher);
+                }
+                Err(e) => warn!("Could not watch {}: {:?}", self.playlist[0].display(), e),
+            }
+        }
+        self.show_file(0);
+    }
+
+    fn show_file(&mut self, position: usize) {
+        self.position = position;
+        let file = self.playlist[position].clone();
+        self.file = Some(file.clone());
+        self.reloaded = None;
+        self.hidden_layers.clear();
+        self.update_watched_files();
+        match read_svg(&file) {
+            Ok(source) => self.load_source(source, &file.display().to_string()),
+            Err(e) => self.error = Some(format!("{}: {}", file.display(), e)),
+        }
+        self.flash = None;
+        self.fit_new_document();
+    }
+
+    // opens the most recently opened file other than the one shown
+    #[cfg(feature = "tray")]
+    fn reopen_last(&mut self) {
+        let file = recent::load()
+            .into_iter()
+            .find(|file| Some(file) != self.file.as_ref());
+        match file {
+            Some(file) => self.open_playlist(vec![file]),
+            None => self.notify("No recently opened files".to_string()),
+        }
+    }
+
+    fn toggle_recent_menu(&mut self) {
+        if self.recent_menu.take().is_none() {
+            let files = recent::load();
+            if files.is_empty() {
+                return self.notify("No recently opened files".to_string());
+            }
+            self.recent_menu = Some(files);
+        }
+        self.rasterize_svg();
+    }
+
+    // opens the `index`-th file of the recent files list, closing the list
+    fn open_recent(&mut self, index: usize) {
+        match self.recent_menu.take().and_then(|files| files.into_iter().nth(index)) {
+            Some(file) => self.open_playlist(vec![file]),
+            None => self.rasterize_svg(),
+        }
+    }
+
+    // zooms so the element with `id` fills most of the window, and outlines it until the
+    // mouse moves
+    fn find_element(&mut self, id: &str) {
+        let hit = match inspect::find(&self.svg_data, &self.svg_source, id) {
+            Some(hit) => hit,
+            None => return self.notify(format!("#{} is not drawn", id)),
+        };
+        let bbox = &hit.bbox;
+        let mut corners = [
+            tiny_skia::Point::from_xy(bbox.left() as f32, bbox.top() as f32),
+            tiny_skia::Point::from_xy(bbox.right() as f32, bbox.bottom() as f32),
+        ];
+        self.viewport.user_transform(&self.svg_data).map_points(&mut corners);
+        let (a, b) = (corners[0], corners[1]);
+        // a margin around the element, which also makes lines and dots big enough to zoom to
+        let margin_x = (a.x - b.x).abs() * 0.1 + FIND_MARGIN;
+        let margin_y = (a.y - b.y).abs() * 0.1 + FIND_MARGIN;
+        self.zoom_to_area(
+            (a.x.min(b.x) - margin_x, a.y.min(b.y) - margin_y),
+            (a.x.max(b.x) + margin_x, a.y.max(b.y) + margin_y),
+        );
+        self.inspected = Some(hit);
+        self.rasterize_svg();
+    }
+
+    // stops reloading on changes, or catches up with those missed while paused
+    fn toggle_watching(&mut self) {
+        self.watching_paused = !self.watching_paused;
+        if self.watching_paused {
+            return;
+        }
+        if std::mem::take(&mut self.missed_change) {
+            self.handle_file_change();
+        }
+        if let Some(source) = self.missed_fetch.take() {
+            self.fetched(source);
+        }
+    }
+
+    // a new version of the URL
+    fn fetched(&mut self, source: Vec<u8>) {
+        if self.watching_paused {
+            self.missed_fetch = Some(source);
+            return;
+        }
+        // unless a dropped file replaced the URL
+        if let (None, Some(url)) = (&self.file, self.url.clone()) {
+            self.reload(source, &url);
+        }
+    }
+
+    // shows `text` over the render for a moment without touching the render itself
+    fn notify(&mut self, text: String) {
+        self.load_fonts();
+        let mut frame = self.pixels.clone();
+        overlay::draw_text(&mut frame, &[text], &self.options);
+        self.flash = Some((frame, Instant::now() + NOTICE_DURATION));
+    }
+
+    // write the current render to <name>-<n>.png in the working directory, with the
+    // first n that is not taken yet
+    fn save_png(&mut self) {
+        let path = self.unused_path("png");
+        self.save(&path);
+    }
+
+    // write the shown document, with its hidden layers left out, as a vector PDF at its
+    // intrinsic size to <name>-<n>.pdf in the working directory
+    fn save_pdf(&mut self) {
+        let path = self.unused_path("pdf");
+        self.save(&path);
+    }
+
+    // exports to `path`, telling whether that worked in a notice
+    fn save(&mut self, path: &Path) {
+        let notice = match self.export(path) {
+            Ok(()) => format!("Saved {}", path.display()),
+            Err(e) => {
+                warn!("Could not save {}: {}", path.display(), e);
+                format!("Could not save {}", path.display())
+            }
+        };
+        self.notify(notice);
+    }
+
+    // writes the view as a PNG, or the document as a PDF, by the extension of `path`
+    fn export(&mut self, path: &Path) -> Result<()> {
+        let data = match path.extension().and_then(|ext| ext.to_str()) {
+            Some(ext) if ext.eq_ignore_ascii_case("png") => self.snapshot()?.encode_png()?,
+            Some(ext) if ext.eq_ignore_ascii_case("pdf") => {
+                let transparent = tiny_skia::Color::TRANSPARENT;
+                let identity = tiny_skia::Transform::identity();
+                convert_to_pdf(&self.svg_data, usvg::FitTo::Original, identity, transparent, None)?
+            }
+            _ => anyhow::bail!("{} is neither a .png nor a .pdf file", path.display()),
+        };
+        Ok(std::fs::write(path, data)?)
+    }
+
+    // carries out a command from the control socket
+    fn control(&mut self, command: control::Command) -> control::Reply {
+        match command {
+            control::Command::Load(path) | control::Command::Open(path) => {
+                let path = std::fs::canonicalize(&path)
+                    .map_err(|e| format!("could not open {}: {}", path.display(), e))?;
+                let paths = if path.is_dir() {
+                    svgs_in(&path)
+                } else {
+                    vec![path.clone()]
+                };
+                if paths.is_empty() {
+                    return Err(format!("no SVG files in {}", path.display()));
+                }
+                self.open_playlist(paths);
+                self.error.clone().map_or(Ok(()), Err)
+            }
+            control::Command::Zoom(zoom) => {
+                self.zoom_to(zoom);
+                Ok(())
+            }
+            control::Command::Export(path) => self.export(&path).map_err(|e| format!("{:#}", e)),
+            control::Command::Quit => Ok(()),
+        }
+    }
+
+    // <name>-<n>.<extension> in the working directory, with the first n that is not taken
+    fn unused_path(&self, extension: &str) -> PathBuf {
+        let name = self
+            .file
+            .as_deref()
+            .and_then(Path::file_stem)
+            .map_or("svgview".into(), |stem| stem.to_string_lossy());
+        (1..)
+            .map(|n| PathBuf::from(format!("{}-{}.{}", name, n, extension)))
+            .find(|path| !path.exists())
+            .expect("Ran out of file names!")
+    }
+
+    // put the current view on the clipboard as an image, without the timeline and the
+    // other overlays
+    fn copy_image(&mut self) {
+        let copied = self.snapshot().and_then(|snapshot| {
+            let bytes = snapshot
+                .pixels()
+                .iter()
+                .flat_map(|pixel| {
+                    let color = pixel.demultiply();
+                    [color.red(), color.green(), color.blue(), color.alpha()]
+                })
+                .collect::<Vec<u8>>();
+            let image = arboard::ImageData {
+                width: snapshot.width() as usize,
+                height: snapshot.height() as usize,
+                bytes: bytes.into(),
+            };
+            match &mut self.clipboard {
+                Some(clipboard) => clipboard.set_image(image).map_err(anyhow::Error::from),
+                None => Err(anyhow::anyhow!("no clipboard available")),
+            }
+        });
+        let notice = match copied {
+            Ok(()) => "Copied to clipboard".to_string(),
+            Err(e) => {
+                warn!("Could not copy to clipboard: {}", e);
+                "Could not copy to clipboard".to_string()
+            }
+        };
+        self.notify(notice);
+    }
+
+    // show SVG markup from the clipboard as a document that is not backed by any file
+    fn paste(&mut self) {
+        let text = match self.clipboard.as_mut().map(|clipboard| clipboard.get_text()) {
+            Some(Ok(text)) => text,
+            _ => return self.notify("No text on the clipboard".to_string()),
+        };
+        let animation = animate::Clock::for_source(text.as_bytes());
+        let source = std::mem::replace(&mut self.svg_source, text.into_bytes());
+        let last_animation = std::mem::replace(&mut self.animation, animation);
+        let file = self.file.take();
+        if let Err(e) = self.try_reparse() {
+            self.svg_source = source;
+            self.animation = last_animation;
+            self.file = file;
+            return self.notify(format!("Clipboard is not an SVG: {}", e));
+        }
+        self.error = None;
+        self.reloaded = None;
+        // nothing on disk or on the server replaces the pasted document anymore
+        self.url = None;
+        self.watch_dir = None;
+        self.missed_change = false;
+        self.missed_fetch = None;
+        if let Some(watcher) = &mut self.watcher {
+            let _ = watcher.set_files(vec![]);
+        }
+        self.flash = None;
+        self.fit_new_document();
+    }
+
+    fn cycle_fit_mode(&mut self) {
+        self.viewport.fit_mode = self.viewport.fit_mode.next();
+        self.update_antialiasing();
+        self.rasterize_svg();
+    }
+
+    fn update_antialiasing(&mut self) {
+        let crisp = match self.antialias_off_below {
+            Some(threshold) => self.viewport.zoom(&self.svg_data) > threshold,
+            None => return,
+        };
+        if crisp != self.crisp {
+            self.crisp = crisp;
+            self.reparse();
+        }
     }
 
+    // renders the SVG on the worker thread, showing the overlays over the last render
+    // until the new one arrives
     fn rasterize_svg(&mut self) {
-        self.pixels
-            .data_mut()
-            .copy_from_slice(&vec![0; self.width as usize * self.height as usize * 4]);
-        resvg::render(
-            &self.svg_data,
-            usvg::FitTo::Size(self.width, self.height),
-            tiny_skia::Transform::default(),
-            self.pixels.as_mut(),
-        )
-        .expect("Could not rasterize SVG!");
+        let canvas = self.canvas();
+        // trees cannot be sent to another thread, but their normalized markup can
+        let document = self
+            .tree_changed
+            .then(|| self.svg_data.to_string(&usvg::XmlOptions::default()));
+        if self.tree_changed {
+            self.tree_version += 1;
+            self.tiles.clear();
+        }
+        self.tree_changed = false;
+        let tiles = self.tile_grid().map(|grid| {
+            let missing = grid.visible().filter(|&tile| !self.tiles.contains(grid.zoom, tile));
+            let missing: Vec<(i32, i32)> = missing.collect();
+            (grid, missing)
+        });
+        // panning within the tiles that are already rendered
+        if matches!(&tiles, Some((_, missing)) if missing.is_empty()) && document.is_none() {
+            self.compose();
+            return;
+        }
+        self.submitted_transform = self.viewport.user_transform(&self.svg_data);
+        self.generation += 1;
+        let submitted = self.worker.submit(worker::Job {
+            generation: self.generation,
+            document,
+            version: self.tree_version,
+            canvas,
+            width: self.viewport.width,
+            height: self.viewport.height,
+            tiles,
+        });
+        self.compose();
+        if !submitted {
+            self.notify(format!(
+                "Rendering took longer than {:?}; not rendering again until the SVG changes",
+                self.worker.timeout()
+            ));
+        }
+    }
+
+    // the view rendered afresh at full resolution, without the overlays of the window
+    fn snapshot(&mut self) -> Result<Pixmap> {
+        let canvas = render::Canvas {
+            density: self.density(),
+            ..self.canvas()
+        };
+        let (width, height) = (self.viewport.width, self.viewport.height);
+        let mut pixmap = Pixmap::new(width, height)
+            .ok_or_else(|| Failure::Render("the window has no pixels".into()))?;
+        render::draw(&self.svg_data, &canvas, &mut pixmap)
+            .ok_or_else(|| Failure::Render("could not rasterize the SVG".into()))?;
+        Ok(pixmap)
+    }
+
+    // like `rasterize_svg`, but renders on this thread, for the first frame
+    fn try_rasterize(&mut self) -> Result<()> {
+        let canvas = self.canvas();
+        let rendered = render::draw(&self.svg_data, &canvas, &mut self.render);
+        self.render_transform = self.viewport.user_transform(&self.svg_data);
+        self.compose();
+        rendered.ok_or_else(|| Failure::Render("could not rasterize the SVG".into()).into())
+    }
+
+    // takes the render of the latest job; returns whether it is shown
+    fn rendered(&mut self, generation: u64, render: Pixmap) -> bool {
+        if generation != self.generation {
+            return false;
+        }
+        self.render = render;
+        self.render_transform = self.submitted_transform;
+        if self.compose() {
+            self.highlight_changes();
+        }
+        true
+    }
+
+    // keeps a finished tile; returns whether it is shown
+    fn rendered_tile(&mut self, zoom: tiles::Zoom, tile: (i32, i32), render: Pixmap) -> bool {
+        // of a document that changed since
+        if zoom.document != self.tree_version {
+            return false;
+        }
+        self.tiles.insert(zoom, tile, render);
+        let shown = self
+            .tile_grid()
+            .is_some_and(|grid| grid.zoom == zoom && grid.visible().any(|shown| shown == tile));
+        if shown && self.compose() {
+            self.highlight_changes();
+        }
+        shown
+    }
+
+    // flashes what a reload changed, once the new render is complete
+    fn highlight_changes(&mut self) {
+        if let Some(previous) = self.changed_from.take() {
+            self.flash = diff::highlight_changes(&previous, &self.pixels)
+                .map(|highlighted| (highlighted, Instant::now() + FLASH_DURATION));
+        }
+    }
+
+    // the tiles to render huge documents in, when they do not fit in the window; tiles
+    // cannot be masked or drawn at another density, and the GPU draws the whole window
+    fn tile_grid(&self) -> Option<tiles::Grid> {
+        let gpu = self.backend == render::Backend::Gpu;
+        if gpu || self.mask.is_some() || self.density() != 1.0 {
+            return None;
+        }
+        let area = self.viewport.document_area(&self.svg_data)?;
+        let (width, height) = (self.viewport.width as f32, self.viewport.height as f32);
+        let fits = area.left() >= 0.0
+            && area.top() >= 0.0
+            && area.right() <= width
+            && area.bottom() <= height;
+        let ts = self.viewport.user_transform(&self.svg_data);
+        (!fits).then(|| {
+            tiles::Grid::new(self.tree_version, ts, area, self.viewport.width, self.viewport.height)
+        })
+    }
+
+    // simulated device pixels per window pixel
+    fn density(&self) -> f32 {
+        self.pixel_ratio
+            .map_or(1.0, |ratio| (ratio / self.viewport.scale_factor) as f32)
+    }
+
+    // the fraction of the density to render at while the view moves; tiles are only
+    // rendered at full density, and the GPU is fast enough for it
+    fn moving_density(&self) -> f32 {
+        let gpu = self.backend == render::Backend::Gpu;
+        if self.view_settles.is_some() && !gpu && self.tile_grid().is_none() {
+            self.interactive_density
+        } else {
+            1.0
+        }
+    }
+
+    // how the SVG is rendered into the window at the moment
+    fn canvas(&mut self) -> render::Canvas {
+        // outlines one pixel wide at the current zoom, made again only when that changed
+        if self.wireframe {
+            let ts = self.viewport.user_transform(&self.svg_data);
+            let pixels_per_unit = (ts.sx * ts.sy - ts.kx * ts.ky).abs().sqrt();
+            if self.wireframe_scale != Some(pixels_per_unit) {
+                wireframe::apply(&self.svg_data, pixels_per_unit as f64);
+                self.wireframe_scale = Some(pixels_per_unit);
+                self.tree_changed = true;
+            }
+        }
+        render::Canvas {
+            background: self.background,
+            checkerboard: self.checkerboard,
+            mask: self.mask.zip(self.viewport.document_area(&self.svg_data)),
+            mask_radius: self.mask_radius,
+            density: self.density() * self.moving_density(),
+            ..self.viewport.canvas(&self.svg_data)
+        }
+    }
+
+    // the last render with the overlays drawn over it; returns whether the render is
+    // complete, rather than in part a placeholder for tiles that are still rendering
+    fn compose(&mut self) -> bool {
+        let complete = match self.tile_grid() {
+            Some(grid) => self.draw_tiles(&grid),
+            None => {
+                self.draw_render(None);
+                true
+            }
+        };
+
+        // the simulated device pixels, once each is large enough to tell apart
+        let density = self.pixel_ratio.unwrap_or(1.0) as f32;
+        let zoom = self.viewport.zoom(&self.svg_data) as f32;
+        if self.pixel_grid && zoom / density >= PIXEL_GRID_ZOOM {
+            let size = self.svg_data.svg_node().size;
+            let (width, height) = (size.width() as f32, size.height() as f32);
+            let scale = self
+                .viewport
+                .fitted_size(&self.svg_data)
+                .map_or(1.0, |fitted| fitted.width() as f32 / width);
+            let transform = self.viewport.view_transform(&self.svg_data).pre_scale(scale, scale);
+            overlay::draw_grid(&mut self.pixels, width, height, density, transform);
+        }
+
+        let labelled = self.rulers
+            || self.animation.is_some()
+            || self.recent_menu.is_some()
+            || self.palette.is_some()
+            || self.layers_menu
+            || self.inspected.is_some()
+            || self.show_defs
+            || self.error.is_some();
+        if labelled {
+            self.load_fonts();
+        }
+        if self.rulers {
+            if let Some((top, left)) = self.ruler_axes() {
+                overlay::draw_rulers(&mut self.pixels, &top, &left, &self.options);
+            }
+        }
+        if let Some(clock) = &self.animation {
+            // repeating animations are shown within their first repetition
+            let span = clock.span();
+            let time = clock.shown();
+            let position = if span.endless && span.end > 0.0 {
+                time % span.end
+            } else {
+                time
+            };
+            let label = format!("{:.2}s / {:.2}s", time, span.end);
+            overlay::draw_timeline(&mut self.pixels, position, span.end, &label, &self.options);
+        }
+        if let Some(hit) = &self.inspected {
+            let transform = self.viewport.user_transform(&self.svg_data);
+            overlay::draw_outline(&mut self.pixels, &hit.bbox, transform);
+        }
+        self.draw_minimap();
+        if let Some(palette) = &self.palette {
+            overlay::draw_text(&mut self.pixels, &palette.lines(), &self.options);
+        } else if let Some(files) = &self.recent_menu {
+            let mut lines = vec!["Recent files (1-9 to open, Escape to close)".to_string()];
+            for (i, file) in files.iter().enumerate() {
+                lines.push(format!("{}  {}", i + 1, file.display()));
+            }
+            overlay::draw_text(&mut self.pixels, &lines, &self.options);
+        } else if self.layers_menu {
+            let mut lines = vec!["Layers (1-9 to show or hide, Escape to close)".to_string()];
+            for (i, name) in self.layer_names.iter().enumerate() {
+                let shown = if self.hidden_layers.contains(&i) { " " } else { "x" };
+                lines.push(format!("{}  [{}] {}", i + 1, shown, name));
+            }
+            overlay::draw_text(&mut self.pixels, &lines, &self.options);
+        } else if let Some(hit) = &self.inspected {
+            overlay::draw_text(&mut self.pixels, &hit.lines, &self.options);
+        } else if self.show_defs {
+            let report = defs::usage_report(&self.svg_source);
+            overlay::draw_text(&mut self.pixels, &report, &self.options);
+        }
+        if let Some(error) = &self.error {
+            overlay::draw_banner(&mut self.pixels, error, &self.options);
+        }
+        // a change to the file that is reloaded once the writes to it stop
+        let pending = self.watcher.as_ref().is_some_and(watch::FileWatcher::pending);
+        if pending && !self.watching_paused {
+            overlay::draw_pending(&mut self.pixels);
+        }
+        complete
+    }
+
+    // the last render, helped by the preview pyramid when it is for another view, within `clip`
+    fn draw_render(&mut self, clip: Option<&tiny_skia::ClipMask>) {
+        let same_size = (self.render.width(), self.render.height())
+            == (self.pixels.width(), self.pixels.height());
+        let user_transform = self.viewport.user_transform(&self.svg_data);
+        let preview = invert(self.render_transform).map(|ts| ts.post_concat(user_transform));
+        match preview {
+            _ if clip.is_none() && same_size && self.render_transform == user_transform => {
+                self.pixels.data_mut().copy_from_slice(self.render.data());
+            }
+            // a render for another window size or view, scaled to where the SVG is now
+            preview => {
+                self.pixels.fill(self.background);
+                if self.checkerboard {
+                    background::draw_checkerboard(&mut self.pixels);
+                }
+                let paint = tiny_skia::PixmapPaint {
+                    quality: tiny_skia::FilterQuality::Bilinear,
+                    ..Default::default()
+                };
+                // the level of the preview pyramid closest to the zoom covers what the
+                // last render does not, or all of it when the render is scaled up more
+                let svg = self.svg_data.svg_node();
+                let shown_width = self
+                    .viewport
+                    .fitted_size(&self.svg_data)
+                    .map_or(0.0, |size| size.width() as f32);
+                let shown_width = shown_width * self.viewport.scale;
+                let level = self.pyramid.level(self.tree_version, shown_width);
+                let mut level_scale = f32::INFINITY;
+                if let Some(level) = level {
+                    let scale = level.width() as f32 / svg.size.width() as f32;
+                    let to_level = tiny_skia::Transform::from_scale(scale, scale)
+                        .pre_concat(view_box_transform(&svg));
+                    if let Some(ts) = invert(to_level).map(|ts| ts.post_concat(user_transform)) {
+                        self.pixels.draw_pixmap(0, 0, level.as_ref(), &paint, ts, clip);
+                        level_scale = magnification(ts);
+                    }
+                }
+                if let Some(preview) = preview.filter(|&ts| magnification(ts) <= level_scale) {
+                    self.pixels.draw_pixmap(0, 0, self.render.as_ref(), &paint, preview, clip);
+                }
+            }
+        }
+    }
+
+    // the tiles in view over the background, with the last render in place of those that
+    // are still rendering; returns whether all of them are done
+    fn draw_tiles(&mut self, grid: &tiles::Grid) -> bool {
+        let mut missing = tiny_skia::PathBuilder::new();
+        for tile in grid.visible().filter(|&tile| !self.tiles.contains(grid.zoom, tile)) {
+            let (x, y) = grid.origin(tile);
+            let size = tiles::SIZE as f32;
+            missing.push_rect(x as f32, y as f32, size, size);
+        }
+        let complete = match missing.finish() {
+            Some(missing) => {
+                let mut clip = tiny_skia::ClipMask::new();
+                let (width, height) = (self.viewport.width, self.viewport.height);
+                clip.set_path(width, height, &missing, tiny_skia::FillRule::Winding, false);
+                self.draw_render(Some(&clip));
+                false
+            }
+            None => {
+                self.pixels.fill(self.background);
+                if self.checkerboard {
+                    background::draw_checkerboard(&mut self.pixels);
+                }
+                true
+            }
+        };
+        let paint = tiny_skia::PixmapPaint::default();
+        let identity = tiny_skia::Transform::identity();
+        for tile in grid.visible() {
+            if let Some(render) = self.tiles.get(grid.zoom, tile) {
+                let (x, y) = grid.origin(tile);
+                self.pixels.draw_pixmap(x, y, render.as_ref(), &paint, identity, None);
+            }
+        }
+        // the placeholder for the next view
+        if complete {
+            self.render = self.pixels.clone();
+            self.render_transform = self.viewport.user_transform(&self.svg_data);
+        }
+        complete
+    }
+
+    // shows or hides the minimap
+    fn toggle_minimap(&mut self) {
+        self.minimap = match self.minimap {
+            Some(_) => None,
+            None => minimap::Minimap::new(&self.svg_data, self.tree_version, &self.pyramid),
+        };
+        self.rasterize_svg();
+    }
+
+    // the minimap over the render, with the window's part of the document outlined
+    fn draw_minimap(&mut self) {
+        let stale = self
+            .minimap
+            .as_ref()
+            .is_some_and(|minimap| minimap.version() != self.tree_version);
+        if stale {
+            self.minimap = minimap::Minimap::new(&self.svg_data, self.tree_version, &self.pyramid);
+        }
+        let minimap = match &self.minimap {
+            Some(minimap) => minimap,
+            None => return,
+        };
+        let (width, height) = (self.viewport.width, self.viewport.height);
+        let area = match minimap.area(width, height) {
+            Some(area) => area,
+            None => return,
+        };
+        let to_user = match invert(self.viewport.user_transform(&self.svg_data)) {
+            Some(to_user) => to_user,
+            None => return,
+        };
+        let to_minimap = to_user.post_concat(minimap.transform(&self.svg_data, area));
+        let (width, height) = (width as f32, height as f32);
+        let mut corners = [(0.0, 0.0), (width, 0.0), (width, height), (0.0, height)]
+            .map(|(x, y)| tiny_skia::Point::from_xy(x, y));
+        to_minimap.map_points(&mut corners);
+        let view = corners.map(|corner| (corner.x, corner.y));
+        overlay::draw_minimap(&mut self.pixels, minimap.pixmap(), area, view);
+    }
+
+    // whether the window point `point` is on the minimap
+    fn on_minimap(&self, point: (f32, f32)) -> bool {
+        let (width, height) = (self.viewport.width, self.viewport.height);
+        self.minimap
+            .as_ref()
+            .and_then(|minimap| minimap.area(width, height))
+            .is_some_and(|area| {
+                let (x, y) = point;
+                x >= area.left() && x < area.right() && y >= area.top() && y < area.bottom()
+            })
+    }
+
+    // pans so that the part of the document under the window point `point` of the minimap
+    // is in the middle of the window
+    fn center_on_minimap(&mut self, point: (f32, f32)) {
+        let (width, height) = (self.viewport.width, self.viewport.height);
+        let to_window = self.minimap.as_ref().and_then(|minimap| {
+            let area = minimap.area(width, height)?;
+            let to_user = invert(minimap.transform(&self.svg_data, area))?;
+            Some(to_user.post_concat(self.viewport.user_transform(&self.svg_data)))
+        });
+        if let Some(to_window) = to_window {
+            let mut target = [tiny_skia::Point::from_xy(point.0, point.1)];
+            to_window.map_points(&mut target);
+            self.pan(width as f32 / 2.0 - target[0].x, height as f32 / 2.0 - target[0].y);
+        }
+    }
+
+    // the user units along the top and left edges of the window; each ruler follows the
+    // user axis that runs along it, so a view turned by a quarter swaps them
+    fn ruler_axes(&self) -> Option<(overlay::Axis, overlay::Axis)> {
+        let to_user = invert(self.viewport.user_transform(&self.svg_data))?;
+        let top = if to_user.sx.abs() >= to_user.ky.abs() {
+            (to_user.tx, to_user.sx)
+        } else {
+            (to_user.ty, to_user.ky)
+        };
+        let left = if to_user.sy.abs() >= to_user.kx.abs() {
+            (to_user.ty, to_user.sy)
+        } else {
+            (to_user.tx, to_user.kx)
+        };
+        let axis = |(origin, units_per_pixel): (f32, f32)| overlay::Axis {
+            origin: origin as f64,
+            units_per_pixel: units_per_pixel as f64,
+        };
+        Some((axis(top), axis(left)))
+    }
+
+    // applies a quarter turn or mirroring on top of the current orientation
+    fn reorient(&mut self, change: tiny_skia::Transform) {
+        self.viewport.reorient(change);
+        self.rasterize_svg();
+    }
+
+    fn pan(&mut self, dx: f32, dy: f32) {
+        self.viewport.pan(dx, dy);
+        self.rasterize_svg();
+    }
+
+    // centers the SVG in the window again, keeping the zoom
+    fn center_content(&mut self) {
+        self.viewport.center();
+        self.rasterize_svg();
+    }
+
+    // the view of a file shown in place of another, or of a pasted SVG
+    fn fit_new_document(&mut self) {
+        if self.keep_zoom {
+            self.center_content();
+        } else {
+            self.reset_view();
+        }
+    }
+
+    // back to the SVG fitted to the window
+    fn reset_view(&mut self) {
+        self.viewport.reset();
+        self.update_antialiasing();
+        self.rasterize_svg();
+    }
+
+    // zoom so that the window area between the corners `a` and `b` fills the window
+    fn zoom_to_area(&mut self, a: (f32, f32), b: (f32, f32)) {
+        if self.viewport.zoom_to_area(&self.svg_data, a, b) {
+            self.update_antialiasing();
+            self.rasterize_svg();
+        }
+    }
+
+    // zoom to `zoom` times the intrinsic size in logical pixels, around the window's center
+    fn zoom_to(&mut self, zoom: f32) {
+        let factor = self.viewport.scale_factor / self.viewport.zoom(&self.svg_data);
+        let center = (self.viewport.width as f32 / 2.0, self.viewport.height as f32 / 2.0);
+        self.zoom_at(zoom * factor as f32, center);
+    }
+
+    // multiply the zoom by `factor` while keeping the window point `anchor` fixed
+    fn zoom_at(&mut self, factor: f32, anchor: (f32, f32)) {
+        self.viewport.zoom_at(&self.svg_data, factor, anchor);
+        self.update_antialiasing();
+        self.rasterize_svg();
     }
 }
@@ -0,0 +1,59 @@
+//! A small render of the whole document in the bottom-right corner of the window, with the
+//! part of it in view outlined, to find one's way around a document zoomed far into.
+//! Pressing on it centers the view on the point pressed.
+//!
+//! The render is made once for each version of the document, taken from the preview
+//! pyramid when the worker already rendered one.
+
+use crate::pyramid::{self, Pyramid};
+use crate::viewport::view_box_transform;
+use tiny_skia::{Pixmap, Rect, Transform};
+use usvg::Tree;
+
+// the longer side of the minimap, and its distance from the window's edges, in pixels
+const SIZE: f32 = 160.0;
+const MARGIN: f32 = 12.0;
+
+/// The downscaled render of one version of the document.
+pub struct Minimap {
+    version: u64,
+    pixmap: Pixmap,
+}
+
+impl Minimap {
+    /// The minimap of version `version` of `tree`, reusing the smallest level of `pyramid`.
+    pub fn new(tree: &Tree, version: u64, pyramid: &Pyramid) -> Option<Self> {
+        let pixmap = match pyramid.level(version, SIZE) {
+            Some(level) => level.clone(),
+            None => pyramid::render(tree, SIZE as u32)?,
+        };
+        Some(Self { version, pixmap })
+    }
+
+    pub fn version(&self) -> u64 {
+        self.version
+    }
+
+    pub fn pixmap(&self) -> &Pixmap {
+        &self.pixmap
+    }
+
+    /// Where the minimap is drawn in a window of `width` x `height` pixels.
+    pub fn area(&self, width: u32, height: u32) -> Option<Rect> {
+        let scale = SIZE / self.pixmap.width().max(self.pixmap.height()) as f32;
+        let (w, h) = (
+            self.pixmap.width() as f32 * scale,
+            self.pixmap.height() as f32 * scale,
+        );
+        Rect::from_xywh(width as f32 - MARGIN - w, height as f32 - MARGIN - h, w, h)
+    }
+
+    /// Maps the user units of `tree` to the window pixels of the minimap in `area`.
+    pub fn transform(&self, tree: &Tree, area: Rect) -> Transform {
+        let svg = tree.svg_node();
+        let scale = area.width() / svg.size.width() as f32;
+        Transform::from_translate(area.x(), area.y())
+            .pre_scale(scale, scale)
+            .pre_concat(view_box_transform(&svg))
+    }
+}
@@ -0,0 +1,150 @@
+//! Writing exported renders as PNGs with or without color and alpha.
+
+use anyhow::{Context, Result};
+use std::fs::File;
+use std::io::{BufWriter, Write};
+use std::path::Path;
+use tiny_skia::{Pixmap, PremultipliedColorU8};
+
+/// The channels of an exported PNG.
+#[derive(Clone, Copy, PartialEq, Eq, Debug, Default, clap::ValueEnum)]
+pub enum OutputColor {
+    /// Luma only, over white where the render is not opaque
+    Gray,
+    /// Luma and alpha
+    Graya,
+    /// Red, green and blue, over white where the render is not opaque
+    Rgb,
+    /// Red, green, blue and alpha
+    #[default]
+    Rgba,
+}
+
+impl OutputColor {
+    fn color_type(self) -> png::ColorType {
+        match self {
+            OutputColor::Gray => png::ColorType::Grayscale,
+            OutputColor::Graya => png::ColorType::GrayscaleAlpha,
+            OutputColor::Rgb => png::ColorType::Rgb,
+            OutputColor::Rgba => png::ColorType::Rgba,
+        }
+    }
+
+    // appends the channels of `pixel` to `data`
+    fn push(self, pixel: PremultipliedColorU8, data: &mut Vec<u8>) {
+        // a premultiplied color over white is the color plus white times what is missing
+        let over_white = |channel: u8| channel + (255 - pixel.alpha());
+        match self {
+            OutputColor::Gray => data.push(luma(
+                over_white(pixel.red()),
+                over_white(pixel.green()),
+                over_white(pixel.blue()),
+            )),
+            OutputColor::Graya => {
+                let color = pixel.demultiply();
+                data.extend([
+                    luma(color.red(), color.green(), color.blue()),
+                    color.alpha(),
+                ]);
+            }
+            OutputColor::Rgb => data.extend([
+                over_white(pixel.red()),
+                over_white(pixel.green()),
+                over_white(pixel.blue()),
+            ]),
+            OutputColor::Rgba => {
+                let color = pixel.demultiply();
+                data.extend([color.red(), color.green(), color.blue(), color.alpha()]);
+            }
+        }
+    }
+}
+
+// the Rec. 601 luma of a color
+fn luma(red: u8, green: u8, blue: u8) -> u8 {
+    ((299 * red as u32 + 587 * green as u32 + 114 * blue as u32 + 500) / 1000) as u8
+}
+
+/// Writes `pixmap` to `path` as a PNG with the channels of `color`.
+pub fn write_png(path: &Path, pixmap: &Pixmap, color: OutputColor) -> Result<()> {
+    let mut writer = header(path, pixmap.width(), pixmap.height(), color)?;
+    let mut data = Vec::with_capacity(pixmap.data().len());
+    for pixel in pixmap.pixels() {
+        color.push(*pixel, &mut data);
+    }
+    writer.write_image_data(&data)?;
+    writer.finish()?;
+    Ok(())
+}
+
+/// Writes a PNG of `width` x `height` pixels to `path` one band of rows at a time, so that
+/// only a band is ever in memory. `draw` renders the band whose top row is at the given
+/// offset into the pixmap, which is `band_height` rows high but for the last band.
+pub fn write_png_banded(
+    path: &Path,
+    (width, height): (u32, u32),
+    band_height: u32,
+    color: OutputColor,
+    mut draw: impl FnMut(&mut Pixmap, u32) -> Result<()>,
+) -> Result<()> {
+    let mut stream = header(path, width, height, color)?.into_stream_writer()?;
+    let mut data = vec![];
+    let mut top = 0;
+    while top < height {
+        let rows = band_height.min(height - top);
+        let mut band = Pixmap::new(width, rows).context("could not allocate a band")?;
+        draw(&mut band, top)?;
+        data.clear();
+        for pixel in band.pixels() {
+            color.push(*pixel, &mut data);
+        }
+        stream.write_all(&data)?;
+        top += rows;
+    }
+    stream.finish()?;
+    Ok(())
+}
+
+fn header(
+    path: &Path,
+    width: u32,
+    height: u32,
+    color: OutputColor,
+) -> Result<png::Writer<BufWriter<File>>> {
+    let file = BufWriter::new(File::create(path)?);
+    let mut encoder = png::Encoder::new(file, width, height);
+    encoder.set_color(color.color_type());
+    encoder.set_depth(png::BitDepth::Eight);
+    Ok(encoder.write_header()?)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn channels(color: OutputColor, pixel: PremultipliedColorU8) -> Vec<u8> {
+        let mut data = vec![];
+        color.push(pixel, &mut data);
+        data
+    }
+
+    #[test]
+    fn opaque_pixels_keep_their_color() {
+        let red = PremultipliedColorU8::from_rgba(255, 0, 0, 255).unwrap();
+        assert_eq!(channels(OutputColor::Rgb, red), [255, 0, 0]);
+        assert_eq!(channels(OutputColor::Rgba, red), [255, 0, 0, 255]);
+        assert_eq!(channels(OutputColor::Gray, red), [76]);
+        assert_eq!(channels(OutputColor::Graya, red), [76, 255]);
+    }
+
+    #[test]
+    fn transparency_is_composited_over_white_without_alpha() {
+        let clear = PremultipliedColorU8::from_rgba(0, 0, 0, 0).unwrap();
+        assert_eq!(channels(OutputColor::Rgb, clear), [255, 255, 255]);
+        assert_eq!(channels(OutputColor::Gray, clear), [255]);
+        // half opaque black
+        let shade = PremultipliedColorU8::from_rgba(0, 0, 0, 128).unwrap();
+        assert_eq!(channels(OutputColor::Rgb, shade), [127, 127, 127]);
+        assert_eq!(channels(OutputColor::Graya, shade), [0, 128]);
+    }
+}
@@ -0,0 +1,416 @@
+//! Text and marks drawn on top of the rasterized SVG.
+//!
+//! Overlay text is laid out as a small SVG document and rendered with resvg,
+//! so it uses the same font database as the document being viewed.
+
+use tiny_skia::{Paint, PathBuilder, Pixmap, PixmapMut, Rect, Stroke, Transform};
+use usvg::{FitTo, Options, Tree};
+
+const FONT_SIZE: f64 = 13.0;
+const LINE_HEIGHT: f64 = 17.0;
+const PADDING: f64 = 8.0;
+const FONT_FAMILY: &str =
+    "DejaVu Sans Mono, Liberation Mono, Noto Sans Mono, Menlo, Consolas, monospace";
+// advance of a monospace glyph relative to the font size
+const CHAR_WIDTH: f64 = 0.6;
+const RULER_SIZE: f64 = 20.0;
+const RULER_FONT_SIZE: f64 = 10.0;
+// least distance between labeled ticks of a ruler
+const RULER_LABEL_SPACING: f64 = 60.0;
+/// Height of the timeline bar of animated documents.
+pub const TIMELINE_HEIGHT: f64 = 4.0;
+// the dot for a pending reload, and the distance of its center from the corner
+const PENDING_RADIUS: f32 = 5.0;
+const PENDING_MARGIN: f32 = 14.0;
+
+/// Draws `lines` in a translucent box in the top-left corner of `pixmap`.
+pub fn draw_text(pixmap: &mut Pixmap, lines: &[String], options: &Options) {
+    if lines.is_empty() {
+        return;
+    }
+    let columns = lines.iter().map(|l| l.chars().count()).max().unwrap_or(0);
+    let box_width = columns as f64 * FONT_SIZE * CHAR_WIDTH + 2.0 * PADDING;
+    draw_box(
+        pixmap,
+        lines,
+        box_width,
+        "fill=\"black\" fill-opacity=\"0.75\"",
+        options,
+    );
+}
+
+/// Draws `text` across the top of `pixmap` on a red strip, wrapped to its width.
+pub fn draw_banner(pixmap: &mut Pixmap, text: &str, options: &Options) {
+    let chars: Vec<char> = text.chars().collect();
+    let columns = ((pixmap.width() as f64 - 2.0 * PADDING) / (FONT_SIZE * CHAR_WIDTH)) as usize;
+    let lines: Vec<String> = chars
+        .chunks(columns.max(1))
+        .map(|line| line.iter().collect())
+        .collect();
+    let width = pixmap.width() as f64;
+    draw_box(pixmap, &lines, width, "fill=\"#c62828\"", options);
+}
+
+/// Draws the rectangle between the points `a` and `b` as a translucent selection into
+/// `frame`, the premultiplied RGBA pixels of a `width` x `height` image.
+pub fn draw_selection(frame: &mut [u8], width: u32, height: u32, a: (f32, f32), b: (f32, f32)) {
+    let (mut pixmap, rect) = match (
+        PixmapMut::from_bytes(frame, width, height),
+        Rect::from_ltrb(a.0.min(b.0), a.1.min(b.1), a.0.max(b.0), a.1.max(b.1)),
+    ) {
+        (Some(pixmap), Some(rect)) => (pixmap, rect),
+        _ => return,
+    };
+    let mut paint = Paint::default();
+    paint.set_color_rgba8(0x1e, 0x88, 0xe5, 0x40);
+    pixmap.fill_rect(rect, &paint, Transform::identity(), None);
+    paint.set_color_rgba8(0x1e, 0x88, 0xe5, 0xff);
+    let outline = PathBuilder::from_rect(rect);
+    pixmap.stroke_path(
+        &outline,
+        &paint,
+        &Stroke::default(),
+        Transform::identity(),
+        None,
+    );
+}
+
+/// Draws one-pixel lines between the pixels of a `width` x `height` image with `density`
+/// pixels per unit, drawn into `pixmap` by `transform`.
+pub fn draw_grid(pixmap: &mut Pixmap, width: f32, height: f32, density: f32, transform: Transform) {
+    let mut grid = PathBuilder::new();
+    let (columns, rows) = ((width * density) as u32, (height * density) as u32);
+    for column in 0..=columns {
+        grid.move_to(column as f32 / density, 0.0);
+        grid.line_to(column as f32 / density, height);
+    }
+    for row in 0..=rows {
+        grid.move_to(0.0, row as f32 / density);
+        grid.line_to(width, row as f32 / density);
+    }
+    let grid = match grid.finish() {
+        Some(grid) => grid,
+        None => return,
+    };
+    let mut paint = Paint::default();
+    paint.set_color_rgba8(0x80, 0x80, 0x80, 0x80);
+    paint.anti_alias = false;
+    // a width of 0 strokes hairlines, one pixel wide whatever the transform
+    let stroke = Stroke {
+        width: 0.0,
+        ..Stroke::default()
+    };
+    pixmap.stroke_path(&grid, &paint, &stroke, transform, None);
+}
+
+/// Outlines `bbox`, given in the SVG's user units, which `transform` maps to the pixmap.
+pub fn draw_outline(pixmap: &mut Pixmap, bbox: &usvg::PathBbox, transform: Transform) {
+    let corners = [
+        (bbox.left(), bbox.top()),
+        (bbox.right(), bbox.top()),
+        (bbox.right(), bbox.bottom()),
+        (bbox.left(), bbox.bottom()),
+    ];
+    let mut outline = PathBuilder::new();
+    for (i, (x, y)) in corners.into_iter().enumerate() {
+        let mut corner = tiny_skia::Point::from_xy(x as f32, y as f32);
+        transform.map_points(std::slice::from_mut(&mut corner));
+        if i == 0 {
+            outline.move_to(corner.x, corner.y);
+        } else {
+            outline.line_to(corner.x, corner.y);
+        }
+    }
+    outline.close();
+    let outline = match outline.finish() {
+        Some(outline) => outline,
+        None => return,
+    };
+    let mut paint = Paint::default();
+    paint.set_color_rgba8(0xff, 0x40, 0x80, 0xff);
+    // the same width at any zoom
+    let stroke = Stroke {
+        width: 2.0,
+        ..Stroke::default()
+    };
+    pixmap.stroke_path(&outline, &paint, &stroke, Transform::identity(), None);
+}
+
+/// Draws `map` scaled into `area` of `pixmap` over a translucent backdrop, with the
+/// quadrilateral `view`, in pixels of `pixmap`, outlined within it.
+pub fn draw_minimap(pixmap: &mut Pixmap, map: &Pixmap, area: Rect, view: [(f32, f32); 4]) {
+    let mut paint = Paint::default();
+    paint.set_color_rgba8(0x00, 0x00, 0x00, 0x99);
+    pixmap.fill_rect(area, &paint, Transform::identity(), None);
+    let scale = area.width() / map.width() as f32;
+    let placed = Transform::from_translate(area.x(), area.y()).pre_scale(scale, scale);
+    let quality = tiny_skia::PixmapPaint {
+        quality: tiny_skia::FilterQuality::Bilinear,
+        ..Default::default()
+    };
+    pixmap.draw_pixmap(0, 0, map.as_ref(), &quality, placed, None);
+
+    let mut outline = PathBuilder::new();
+    outline.move_to(view[0].0, view[0].1);
+    for (x, y) in &view[1..] {
+        outline.line_to(*x, *y);
+    }
+    outline.close();
+    let outline = match outline.finish() {
+        Some(outline) => outline,
+        None => return,
+    };
+    let border = PathBuilder::from_rect(area);
+    // the view can reach past the minimap while zoomed out
+    let mut clip = tiny_skia::ClipMask::new();
+    clip.set_path(
+        pixmap.width(),
+        pixmap.height(),
+        &border,
+        tiny_skia::FillRule::Winding,
+        false,
+    );
+    paint.set_color_rgba8(0x1e, 0x88, 0xe5, 0xff);
+    let stroke = Stroke {
+        width: 2.0,
+        ..Stroke::default()
+    };
+    pixmap.stroke_path(
+        &outline,
+        &paint,
+        &stroke,
+        Transform::identity(),
+        Some(&clip),
+    );
+    paint.set_color_rgba8(0x80, 0x80, 0x80, 0xff);
+    pixmap.stroke_path(
+        &border,
+        &paint,
+        &Stroke::default(),
+        Transform::identity(),
+        None,
+    );
+}
+
+/// Draws a dot in the top-right corner of `pixmap`, for a reload that is coming.
+pub fn draw_pending(pixmap: &mut Pixmap) {
+    let (x, y) = (pixmap.width() as f32 - PENDING_MARGIN, PENDING_MARGIN);
+    let dot = match PathBuilder::from_circle(x, y, PENDING_RADIUS) {
+        Some(dot) => dot,
+        None => return,
+    };
+    let mut paint = Paint::default();
+    paint.set_color_rgba8(0xff, 0xa0, 0x00, 0xe0);
+    pixmap.fill_path(
+        &dot,
+        &paint,
+        tiny_skia::FillRule::Winding,
+        Transform::identity(),
+        None,
+    );
+    paint.set_color_rgba8(0x00, 0x00, 0x00, 0x80);
+    pixmap.stroke_path(
+        &dot,
+        &paint,
+        &Stroke::default(),
+        Transform::identity(),
+        None,
+    );
+}
+
+/// Draws a bar along the bottom edge of `pixmap`, filled to `time` of `length` seconds and
+/// labeled with `label`.
+pub fn draw_timeline(pixmap: &mut Pixmap, time: f64, length: f64, label: &str, options: &Options) {
+    let (width, height) = (pixmap.width() as f64, pixmap.height() as f64);
+    let fraction = if length > 0.0 {
+        (time / length).clamp(0.0, 1.0)
+    } else {
+        1.0
+    };
+    let top = height - TIMELINE_HEIGHT;
+    let label_width = label.chars().count() as f64 * RULER_FONT_SIZE * CHAR_WIDTH + 8.0;
+    let label_height = RULER_FONT_SIZE + 6.0;
+    let svg = format!(
+        "<svg xmlns=\"http://www.w3.org/2000/svg\" width=\"{w}\" height=\"{h}\">\
+         <rect y=\"{top}\" width=\"{w}\" height=\"{bar}\" fill=\"black\" fill-opacity=\"0.4\"/>\
+         <rect y=\"{top}\" width=\"{filled}\" height=\"{bar}\" fill=\"#1e88e5\"/>\
+         <rect x=\"{lx}\" y=\"{ly}\" width=\"{lw}\" height=\"{lh}\" fill=\"black\" \
+         fill-opacity=\"0.6\"/>\
+         <text x=\"{tx}\" y=\"{ty}\" font-family=\"{}\" font-size=\"{}\" fill=\"white\">{}</text>\
+         </svg>",
+        FONT_FAMILY,
+        RULER_FONT_SIZE,
+        label,
+        w = width,
+        h = height,
+        top = top,
+        bar = TIMELINE_HEIGHT,
+        filled = width * fraction,
+        lx = width - label_width,
+        ly = top - label_height,
+        lw = label_width,
+        lh = label_height,
+        tx = width - label_width + 4.0,
+        ty = top - 5.0,
+    );
+    match Tree::from_str(&svg, &options.to_ref()) {
+        Ok(tree) => {
+            resvg::render(
+                &tree,
+                FitTo::Original,
+                Transform::default(),
+                pixmap.as_mut(),
+            );
+        }
+        Err(e) => log::warn!("Could not lay out the timeline: {}", e),
+    }
+}
+
+/// Maps positions along a ruler, in window pixels, to SVG user units.
+pub struct Axis {
+    /// The user unit at the start of the ruler.
+    pub origin: f64,
+    pub units_per_pixel: f64,
+}
+
+/// Draws rulers along the top and left edges of `pixmap`, labeled in user units.
+pub fn draw_rulers(pixmap: &mut Pixmap, top: &Axis, left: &Axis, options: &Options) {
+    let (width, height) = (pixmap.width() as f64, pixmap.height() as f64);
+    let mut svg = format!(
+        "<svg xmlns=\"http://www.w3.org/2000/svg\" width=\"{w}\" height=\"{h}\">\
+         <path d=\"M0 0H{w}V{s}H{s}V{h}H0Z\" fill=\"#f0f0f0\" fill-opacity=\"0.9\"/>\
+         <g font-family=\"{}\" font-size=\"{}\" fill=\"#202020\">",
+        FONT_FAMILY,
+        RULER_FONT_SIZE,
+        w = width,
+        h = height,
+        s = RULER_SIZE,
+    );
+    let mut ticks = String::new();
+    for (position, length, label) in ruler_ticks(top, width) {
+        if position < RULER_SIZE {
+            continue;
+        }
+        ticks += &format!("M{} {}V{}", position + 0.5, RULER_SIZE - length, RULER_SIZE);
+        if let Some(label) = label {
+            svg += &format!(
+                "<text x=\"{}\" y=\"{}\">{}</text>",
+                position + 2.0,
+                9.0,
+                label
+            );
+        }
+    }
+    for (position, length, label) in ruler_ticks(left, height) {
+        if position < RULER_SIZE {
+            continue;
+        }
+        ticks += &format!("M{} {}H{}", RULER_SIZE - length, position + 0.5, RULER_SIZE);
+        if let Some(label) = label {
+            // read along the ruler
+            svg += &format!(
+                "<text transform=\"translate(9 {}) rotate(-90)\">{}</text>",
+                position - 2.0,
+                label
+            );
+        }
+    }
+    svg += &format!(
+        "</g><path d=\"{}\" stroke=\"#404040\" stroke-width=\"1\" fill=\"none\"/></svg>",
+        ticks
+    );
+
+    match Tree::from_str(&svg, &options.to_ref()) {
+        Ok(tree) => {
+            resvg::render(
+                &tree,
+                FitTo::Original,
+                Transform::default(),
+                pixmap.as_mut(),
+            );
+        }
+        Err(e) => log::warn!("Could not lay out the rulers: {}", e),
+    }
+}
+
+// positions in pixels, lengths and labels of the ticks of a ruler `length` pixels long:
+// labeled ticks at round numbers of user units, with four unlabeled ones between them
+fn ruler_ticks(axis: &Axis, length: f64) -> Vec<(f64, f64, Option<String>)> {
+    let units_per_pixel = axis.units_per_pixel.abs();
+    if !units_per_pixel.is_normal() {
+        return vec![];
+    }
+    // 1, 2 or 5 times a power of ten
+    let least = RULER_LABEL_SPACING * units_per_pixel;
+    let magnitude = 10f64.powf(least.log10().floor());
+    let step = [1.0, 2.0, 5.0, 10.0]
+        .into_iter()
+        .map(|factor| factor * magnitude)
+        .find(|step| *step >= least)
+        .unwrap_or(10.0 * magnitude);
+    let minor = step / 5.0;
+    let decimals = (-step.log10().floor()).max(0.0) as usize;
+
+    let end = axis.origin + axis.units_per_pixel * length;
+    let (low, high) = (axis.origin.min(end), axis.origin.max(end));
+    let mut ticks = vec![];
+    for index in (low / minor).floor() as i64..=(high / minor).ceil() as i64 {
+        let value = index as f64 * minor;
+        let position = (value - axis.origin) / axis.units_per_pixel;
+        if index % 5 == 0 {
+            ticks.push((
+                position,
+                RULER_SIZE,
+                Some(format!("{:.*}", decimals, value)),
+            ));
+        } else {
+            ticks.push((position, RULER_SIZE / 4.0, None));
+        }
+    }
+    ticks
+}
+
+// `lines` in a box `box_width` wide in the top-left corner, filled as given by `fill`
+fn draw_box(pixmap: &mut Pixmap, lines: &[String], box_width: f64, fill: &str, options: &Options) {
+    let box_height = lines.len() as f64 * LINE_HEIGHT + 2.0 * PADDING;
+    let mut svg = format!(
+        "<svg xmlns=\"http://www.w3.org/2000/svg\" width=\"{}\" height=\"{}\">\
+         <rect width=\"{}\" height=\"{}\" {}/>\
+         <g font-family=\"{}\" font-size=\"{}\" fill=\"white\" xml:space=\"preserve\">",
+        pixmap.width(),
+        pixmap.height(),
+        box_width,
+        box_height,
+        fill,
+        FONT_FAMILY,
+        FONT_SIZE
+    );
+    for (i, line) in lines.iter().enumerate() {
+        let baseline = PADDING + (i + 1) as f64 * LINE_HEIGHT - 4.0;
+        svg.push_str(&format!(
+            "<text x=\"{}\" y=\"{}\">{}</text>",
+            PADDING,
+            baseline,
+            escape(line)
+        ));
+    }
+    svg.push_str("</g></svg>");
+
+    match Tree::from_str(&svg, &options.to_ref()) {
+        Ok(tree) => {
+            resvg::render(
+                &tree,
+                FitTo::Original,
+                tiny_skia::Transform::default(),
+                pixmap.as_mut(),
+            );
+        }
+        Err(e) => log::warn!("Could not lay out overlay text: {}", e),
+    }
+}
+
+fn escape(text: &str) -> String {
+    text.replace('&', "&amp;")
+        .replace('<', "&lt;")
+        .replace('>', "&gt;")
+}
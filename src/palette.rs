@@ -0,0 +1,174 @@
+//! The command palette: every action, recent file and element id of the document in one
+//! list, narrowed down by typing some of the letters of an entry in order, e.g. `tpg` for
+//! "Toggle pixel grid".
+
+use crate::keymap::{Action, Keymap};
+use std::path::PathBuf;
+use winit::event::VirtualKeyCode;
+use winit_input_helper::{TextChar, WinitInputHelper};
+
+// entries listed at once, around the selected one
+const SHOWN: usize = 12;
+
+#[derive(Clone, Debug)]
+pub enum Command {
+    Action(Action),
+    Open(PathBuf),
+    /// Zoom to the element with this id and outline it.
+    Find(String),
+}
+
+struct Entry {
+    label: String,
+    // the keys that do the same, if any
+    hint: String,
+    command: Command,
+}
+
+pub struct Palette {
+    query: String,
+    entries: Vec<Entry>,
+    // indices of the entries that match the query, best first
+    matches: Vec<usize>,
+    selected: usize,
+}
+
+impl Palette {
+    pub fn new(keymap: &Keymap, recent: Vec<PathBuf>, ids: Vec<String>) -> Self {
+        let actions = Action::ALL
+            .iter()
+            .filter(|action| **action != Action::CommandPalette)
+            .map(|action| Entry {
+                label: action.label(),
+                hint: keymap
+                    .keys(*action)
+                    .iter()
+                    .map(ToString::to_string)
+                    .collect::<Vec<_>>()
+                    .join(", "),
+                command: Command::Action(*action),
+            });
+        let files = recent.into_iter().map(|file| Entry {
+            label: format!("Open {}", file.display()),
+            hint: String::new(),
+            command: Command::Open(file),
+        });
+        let elements = ids.into_iter().map(|id| Entry {
+            label: format!("Find #{}", id),
+            hint: String::new(),
+            command: Command::Find(id),
+        });
+        let mut palette = Self {
+            query: String::new(),
+            entries: actions.chain(files).chain(elements).collect(),
+            matches: vec![],
+            selected: 0,
+        };
+        palette.filter();
+        palette
+    }
+
+    /// Takes the text typed and the arrow keys pressed since the last update of `input`.
+    /// Returns the command of the selected entry once Enter is pressed.
+    pub fn update(&mut self, input: &WinitInputHelper) -> Option<Command> {
+        let mut edited = false;
+        for c in input.text() {
+            match c {
+                TextChar::Char(c) if !c.is_control() => self.query.push(c),
+                TextChar::Back => {
+                    self.query.pop();
+                }
+                _ => continue,
+            }
+            edited = true;
+        }
+        if edited {
+            self.filter();
+        }
+        let count = self.matches.len().max(1);
+        if input.key_pressed(VirtualKeyCode::Down) {
+            self.selected = (self.selected + 1) % count;
+        }
+        if input.key_pressed(VirtualKeyCode::Up) {
+            self.selected = (self.selected + count - 1) % count;
+        }
+        let entered = input.key_pressed(VirtualKeyCode::Return)
+            || input.key_pressed(VirtualKeyCode::NumpadEnter);
+        entered
+            .then(|| self.matches.get(self.selected))
+            .flatten()
+            .map(|&index| self.entries[index].command.clone())
+    }
+
+    /// The query and the entries around the selected one, to draw over the render.
+    pub fn lines(&self) -> Vec<String> {
+        let mut lines = vec![format!(
+            "> {}_  (Up/Down to pick, Enter to run, Escape to close)",
+            self.query
+        )];
+        if self.matches.is_empty() {
+            lines.push("  No matches".to_string());
+            return lines;
+        }
+        let first = self.selected.saturating_sub(SHOWN / 2);
+        let first = first.min(self.matches.len().saturating_sub(SHOWN));
+        let shown = &self.matches[first..self.matches.len().min(first + SHOWN)];
+        let width = shown
+            .iter()
+            .map(|&index| self.entries[index].label.chars().count())
+            .max()
+            .unwrap_or(0);
+        for (i, &index) in shown.iter().enumerate() {
+            let entry = &self.entries[index];
+            let marker = if first + i == self.selected { '>' } else { ' ' };
+            let line = format!(
+                "{} {:<width$}  {}",
+                marker,
+                entry.label,
+                entry.hint,
+                width = width
+            );
+            lines.push(line.trim_end().to_string());
+        }
+        lines
+    }
+
+    fn filter(&mut self) {
+        let mut scored: Vec<(i32, usize)> = self
+            .entries
+            .iter()
+            .enumerate()
+            .filter_map(|(index, entry)| score(&self.query, &entry.label).map(|s| (s, index)))
+            .collect();
+        // the sort is stable, so equally good entries stay in the order of the list
+        scored.sort_by_key(|(score, _)| -score);
+        self.matches = scored.into_iter().map(|(_, index)| index).collect();
+        self.selected = 0;
+    }
+}
+
+// how well `label` matches `query`, if it has all of its letters in order: letters that
+// follow each other and those that start a word count the most
+fn score(query: &str, label: &str) -> Option<i32> {
+    let label: Vec<char> = label.chars().flat_map(char::to_lowercase).collect();
+    let mut score = 0;
+    let mut position = 0;
+    let mut previous: Option<usize> = None;
+    for c in query
+        .chars()
+        .flat_map(char::to_lowercase)
+        .filter(|c| !c.is_whitespace())
+    {
+        let found = position + label[position..].iter().position(|l| *l == c)?;
+        score += 1;
+        if found > 0 && previous == Some(found - 1) {
+            score += 4;
+        }
+        if found == 0 || !label[found - 1].is_alphanumeric() {
+            score += 3;
+        }
+        previous = Some(found);
+        position = found + 1;
+    }
+    Some(score)
+}
@@ -0,0 +1,57 @@
+//! Renders of the whole document at a few fixed sizes, so that something can be shown at
+//! once for any view of a document that takes long to render, until the render of the view
+//! itself is done.
+//!
+//! The levels are only rendered for documents whose renders are slow, by the render
+//! worker while it has nothing else to do, the smallest first.
+
+use tiny_skia::{Color, Pixmap, Transform};
+use usvg::Tree;
+
+/// The longer side of each level in pixels.
+pub const SIZES: &[u32] = &[256, 512, 1024, 2048];
+
+/// Renders the level of the given size, over a transparent background.
+pub fn render(tree: &Tree, size: u32) -> Option<Pixmap> {
+    let svg = tree.svg_node().size;
+    let scale = size as f64 / svg.width().max(svg.height());
+    let width = (svg.width() * scale).round().max(1.0) as u32;
+    let height = (svg.height() * scale).round().max(1.0) as u32;
+    let mut pixmap = Pixmap::new(width, height)?;
+    pixmap.fill(Color::TRANSPARENT);
+    let fit_to = usvg::FitTo::Size(width, height);
+    resvg::render(tree, fit_to, Transform::identity(), pixmap.as_mut())?;
+    Some(pixmap)
+}
+
+/// The levels rendered for one version of the document.
+#[derive(Default)]
+pub struct Pyramid {
+    version: u64,
+    levels: Vec<Pixmap>,
+}
+
+impl Pyramid {
+    /// Adds a level of version `version` of the document, dropping those of other versions.
+    pub fn insert(&mut self, version: u64, level: Pixmap) {
+        if version != self.version {
+            self.version = version;
+            self.levels.clear();
+        }
+        self.levels.push(level);
+        self.levels.sort_by_key(Pixmap::width);
+    }
+
+    /// The smallest level of version `version` that is at least `width` pixels wide, or
+    /// else the largest.
+    pub fn level(&self, version: u64, width: f32) -> Option<&Pixmap> {
+        if version != self.version {
+            return None;
+        }
+        let wanted = |level: &&Pixmap| level.width() as f32 >= width;
+        self.levels
+            .iter()
+            .find(wanted)
+            .or_else(|| self.levels.last())
+    }
+}
@@ -0,0 +1,47 @@
+//! The most recently opened files, kept across sessions in `svgview/recent` in
+//! `$XDG_CACHE_HOME`, or in `~/.cache` if that is unset, one path per line.
+
+use log::warn;
+use std::path::{Path, PathBuf};
+use svgview_core::cache_path;
+
+/// How many files are remembered, one per digit key of the list.
+pub const LIMIT: usize = 9;
+
+/// The remembered files, most recently opened first.
+pub fn load() -> Vec<PathBuf> {
+    let text = match cache_path("recent").map(std::fs::read_to_string) {
+        Some(Ok(text)) => text,
+        _ => return vec![],
+    };
+    text.lines()
+        .filter(|line| !line.is_empty())
+        .map(PathBuf::from)
+        .take(LIMIT)
+        .collect()
+}
+
+/// Moves `file`, an absolute path, to the front of the remembered files.
+pub fn record(file: &Path) {
+    let path = match cache_path("recent") {
+        Some(path) => path,
+        None => return,
+    };
+    let mut files = load();
+    files.retain(|recent| recent != file);
+    files.insert(0, file.to_path_buf());
+    files.truncate(LIMIT);
+
+    let mut text = String::new();
+    for file in files {
+        text += &file.to_string_lossy();
+        text.push('\n');
+    }
+    let written = path
+        .parent()
+        .map_or(Ok(()), std::fs::create_dir_all)
+        .and_then(|_| std::fs::write(&path, text));
+    if let Err(e) = written {
+        warn!("Could not remember {}: {}", file.display(), e);
+    }
+}
@@ -0,0 +1,72 @@
+//! Remapping fill and stroke colors of a parsed document, e.g. to preview an
+//! icon in a different color scheme.
+
+use usvg::{Color, NodeKind, Paint, Tree};
+
+pub type ColorMap = Vec<(Color, Color)>;
+
+/// Parses `from=to` pairs separated by commas, e.g. `#000000=#ffffff,red=lime`.
+pub fn parse_map(value: &str) -> Option<ColorMap> {
+    value
+        .split(',')
+        .map(|pair| {
+            let (from, to) = pair.split_once('=')?;
+            Some((parse_color(from)?, parse_color(to)?))
+        })
+        .collect()
+}
+
+fn parse_color(value: &str) -> Option<Color> {
+    let color: svgtypes::Color = value.trim().parse().ok()?;
+    Some(Color::new_rgb(color.red, color.green, color.blue))
+}
+
+/// Replaces every solid fill and stroke color that exactly matches an entry of `map`.
+pub fn apply(tree: &Tree, map: &[(Color, Color)]) {
+    if map.is_empty() {
+        return;
+    }
+    for mut node in tree.root().descendants() {
+        if let NodeKind::Path(ref mut path) = *node.borrow_mut() {
+            if let Some(fill) = path.fill.as_mut() {
+                remap(&mut fill.paint, map);
+            }
+            if let Some(stroke) = path.stroke.as_mut() {
+                remap(&mut stroke.paint, map);
+            }
+        }
+    }
+}
+
+fn remap(paint: &mut Paint, map: &[(Color, Color)]) {
+    if let Paint::Color(color) = paint {
+        if let Some((_, to)) = map.iter().find(|(from, _)| from == color) {
+            *color = *to;
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn maps_parse_pairs_of_colors() {
+        let map = parse_map("#000=red, blue = #fff").unwrap();
+        assert_eq!(
+            map,
+            vec![
+                (Color::black(), Color::new_rgb(255, 0, 0)),
+                (Color::new_rgb(0, 0, 255), Color::white()),
+            ]
+        );
+    }
+
+    #[test]
+    fn malformed_maps_are_refused() {
+        assert_eq!(parse_map("#000"), None);
+        assert_eq!(parse_map("#000=red,blue"), None);
+        assert_eq!(parse_map("#000=notacolor"), None);
+        assert_eq!(parse_map("#00=red"), None);
+    }
+}
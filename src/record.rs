@@ -0,0 +1,226 @@
+//! Writing rendered frames as an animated GIF, PNG or WebP that loops forever.
+
+use anyhow::Result;
+use std::fs::File;
+use std::io::{BufWriter, Write};
+use std::path::Path;
+use tiny_skia::Pixmap;
+
+#[derive(Clone, Copy)]
+pub enum Format {
+    Gif,
+    /// An animated PNG.
+    Apng,
+    /// An animated WebP, with every frame compressed losslessly.
+    Webp,
+}
+
+impl Format {
+    /// The format for the extension of `path`: `.gif`, `.png`/`.apng` or `.webp`.
+    pub fn for_path(path: &Path) -> Option<Self> {
+        let extension = path.extension()?.to_str()?.to_ascii_lowercase();
+        match extension.as_str() {
+            "gif" => Some(Format::Gif),
+            "png" | "apng" => Some(Format::Apng),
+            "webp" => Some(Format::Webp),
+            _ => None,
+        }
+    }
+}
+
+/// Writes `frames`, all of the same size, shown `fps` per second.
+pub fn write(path: &Path, format: Format, frames: &[Pixmap], fps: u16) -> Result<()> {
+    let file = BufWriter::new(File::create(path)?);
+    match format {
+        Format::Gif => write_gif(file, frames, fps),
+        Format::Apng => write_apng(file, frames, fps),
+        Format::Webp => write_webp(file, frames, fps),
+    }
+}
+
+fn write_apng(file: impl Write, frames: &[Pixmap], fps: u16) -> Result<()> {
+    let (width, height) = match frames.first() {
+        Some(first) => (first.width(), first.height()),
+        None => return Ok(()),
+    };
+    let mut encoder = png::Encoder::new(file, width, height);
+    encoder.set_color(png::ColorType::Rgba);
+    encoder.set_depth(png::BitDepth::Eight);
+    encoder.set_animated(frames.len() as u32, 0)?;
+    encoder.set_frame_delay(1, fps)?;
+    let mut writer = encoder.write_header()?;
+    for frame in frames {
+        writer.write_image_data(&demultiplied(frame))?;
+    }
+    writer.finish()?;
+    Ok(())
+}
+
+// an extended WebP whose frames are the lossless images image-webp encodes, each of which
+// replaces the one before rather than being blended over it
+fn write_webp(mut file: impl Write, frames: &[Pixmap], fps: u16) -> Result<()> {
+    let (width, height) = match frames.first() {
+        Some(first) => (first.width(), first.height()),
+        None => return Ok(()),
+    };
+    // 24 bit little endian fields, as the WebP container has them
+    let u24 = |value: u32| [value as u8, (value >> 8) as u8, (value >> 16) as u8];
+    let mut chunks = vec![];
+    // animated, with alpha
+    let mut vp8x = vec![(1 << 4) | (1 << 1), 0, 0, 0];
+    vp8x.extend(u24(width - 1));
+    vp8x.extend(u24(height - 1));
+    write_chunk(&mut chunks, b"VP8X", &vp8x)?;
+    // a transparent background, looping forever
+    write_chunk(&mut chunks, b"ANIM", &[0, 0, 0, 0, 0, 0])?;
+    for (i, frame) in frames.iter().enumerate() {
+        let mut image = vec![];
+        let encoder = image_webp::WebPEncoder::new(&mut image);
+        encoder.encode(
+            &demultiplied(frame),
+            width,
+            height,
+            image_webp::ColorType::Rgba8,
+        )?;
+        // at the origin, shown from the frame's time until the next one's, not blended
+        let time = |frame: usize| (frame as f64 * 1000.0 / fps as f64).round() as u32;
+        let mut anmf = vec![];
+        anmf.extend(u24(0));
+        anmf.extend(u24(0));
+        anmf.extend(u24(width - 1));
+        anmf.extend(u24(height - 1));
+        anmf.extend(u24(time(i + 1) - time(i)));
+        anmf.push(1 << 1);
+        // the VP8L chunk of the still image, after its RIFF header
+        anmf.extend_from_slice(&image[12..]);
+        write_chunk(&mut chunks, b"ANMF", &anmf)?;
+    }
+    file.write_all(b"RIFF")?;
+    file.write_all(&(chunks.len() as u32 + 4).to_le_bytes())?;
+    file.write_all(b"WEBP")?;
+    file.write_all(&chunks)?;
+    file.flush()?;
+    Ok(())
+}
+
+// a RIFF chunk, padded to an even length
+fn write_chunk(mut file: impl Write, name: &[u8; 4], data: &[u8]) -> Result<()> {
+    file.write_all(name)?;
+    file.write_all(&(data.len() as u32).to_le_bytes())?;
+    file.write_all(data)?;
+    if data.len() % 2 == 1 {
+        file.write_all(&[0])?;
+    }
+    Ok(())
+}
+
+// a GIF with a palette of its own for every frame; pixels that are mostly transparent
+// become fully transparent and the rest opaque
+fn write_gif(file: impl Write, frames: &[Pixmap], fps: u16) -> Result<()> {
+    let (width, height) = match frames.first() {
+        Some(first) => (first.width() as u16, first.height() as u16),
+        None => return Ok(()),
+    };
+    let mut encoder = gif::Encoder::new(file, width, height, &[])?;
+    encoder.set_repeat(gif::Repeat::Infinite)?;
+    for (i, frame) in frames.iter().enumerate() {
+        let mut rgba = demultiplied(frame);
+        for pixel in rgba.chunks_mut(4) {
+            pixel[3] = if pixel[3] < 128 { 0 } else { 255 };
+        }
+        let mut gif_frame = gif::Frame::from_rgba_speed(width, height, &mut rgba, 10);
+        // delays are in hundredths of a second, so round the frame times instead
+        let time = |frame: usize| (frame as f64 * 100.0 / fps as f64).round() as u16;
+        gif_frame.delay = time(i + 1) - time(i);
+        gif_frame.dispose = gif::DisposalMethod::Background;
+        encoder.write_frame(&gif_frame)?;
+    }
+    encoder.into_inner()?.flush()?;
+    Ok(())
+}
+
+// the RGBA bytes of `pixmap` without premultiplied alpha
+fn demultiplied(pixmap: &Pixmap) -> Vec<u8> {
+    let mut data = Vec::with_capacity(pixmap.data().len());
+    for pixel in pixmap.pixels() {
+        let color = pixel.demultiply();
+        data.extend([color.red(), color.green(), color.blue(), color.alpha()]);
+    }
+    data
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use image_webp::{LoopCount, WebPDecoder};
+    use tiny_skia::Color;
+
+    #[test]
+    fn webp_frames_decode_as_rendered() {
+        let colors = [
+            Color::from_rgba8(255, 0, 0, 255),
+            Color::from_rgba8(0, 0, 255, 128),
+        ];
+        let frames: Vec<Pixmap> = colors
+            .iter()
+            .map(|color| {
+                let mut frame = Pixmap::new(3, 2).unwrap();
+                frame.fill(*color);
+                frame
+            })
+            .collect();
+        let mut file = vec![];
+        write_webp(&mut file, &frames, 30).unwrap();
+
+        let mut decoder = WebPDecoder::new(std::io::Cursor::new(file)).unwrap();
+        assert!(decoder.is_animated());
+        assert_eq!(decoder.dimensions(), (3, 2));
+        assert_eq!(decoder.num_frames(), 2);
+        assert!(matches!(decoder.loop_count(), LoopCount::Forever));
+        let mut buffer = vec![0; decoder.output_buffer_size().unwrap()];
+        for (frame, delay) in frames.iter().zip([33, 34]) {
+            assert_eq!(decoder.read_frame(&mut buffer).unwrap(), delay);
+            assert_eq!(buffer, demultiplied(frame));
+        }
+    }
+
+    #[test]
+    fn gif_frames_decode_as_rendered() {
+        let colors = [
+            Color::from_rgba8(255, 0, 0, 255),
+            Color::from_rgba8(0, 0, 255, 200),
+            Color::from_rgba8(0, 255, 0, 100),
+        ];
+        let frames: Vec<Pixmap> = colors
+            .iter()
+            .map(|color| {
+                let mut frame = Pixmap::new(3, 2).unwrap();
+                frame.fill(*color);
+                frame
+            })
+            .collect();
+        let mut file = vec![];
+        write_gif(&mut file, &frames, 30).unwrap();
+
+        let mut options = gif::DecodeOptions::new();
+        options.set_color_output(gif::ColorOutput::RGBA);
+        let mut decoder = options.read_info(file.as_slice()).unwrap();
+        assert_eq!((decoder.width(), decoder.height()), (3, 2));
+        let expected = [[255, 0, 0, 255], [0, 0, 255, 255], [0, 0, 0, 0]];
+        for (pixel, delay) in expected.iter().zip([3, 4, 3]) {
+            let frame = decoder.read_next_frame().unwrap().unwrap();
+            assert_eq!(frame.delay, delay);
+            assert_eq!(frame.dispose, gif::DisposalMethod::Background);
+            let alpha = |rgba: &[u8]| {
+                if rgba[3] == 0 {
+                    [0; 4]
+                } else {
+                    rgba.try_into().unwrap()
+                }
+            };
+            assert!(frame.buffer.chunks(4).all(|rgba| alpha(rgba) == *pixel));
+        }
+        assert!(decoder.read_next_frame().unwrap().is_none());
+        assert_eq!(decoder.repeat(), gif::Repeat::Infinite);
+    }
+}
@@ -0,0 +1,63 @@
+//! Files outside the SVG that its rendering depends on, e.g. linked raster images.
+//!
+//! usvg embeds the data of such files while converting, so they are looked up in the
+//! markup rather than the tree.
+
+use std::path::{Path, PathBuf};
+
+/// Absolute paths of the local files referenced by `data`, resolved relative to `dir`.
+pub fn external_files(data: &[u8], dir: &Path) -> Vec<PathBuf> {
+    let text = match std::str::from_utf8(data) {
+        Ok(text) => text,
+        Err(_) => return vec![],
+    };
+    let options = roxmltree::ParsingOptions { allow_dtd: true };
+    let document = match roxmltree::Document::parse_with_options(text, options) {
+        Ok(document) => document,
+        Err(_) => return vec![],
+    };
+
+    let mut files = vec![];
+    for node in document.descendants() {
+        let href = if node.is_element() {
+            if !matches!(node.tag_name().name(), "image" | "feImage") {
+                continue;
+            }
+            node.attributes()
+                .iter()
+                .find(|a| a.name() == "href")
+                .map(|a| a.value())
+        } else if node.pi().is_some_and(|pi| pi.target == "xml-stylesheet") {
+            node.pi().and_then(|pi| pi.value).and_then(stylesheet_href)
+        } else {
+            None
+        };
+        if let Some(path) = href.and_then(local_path) {
+            let path = dir.join(path);
+            let path = std::fs::canonicalize(&path).unwrap_or(path);
+            if !files.contains(&path) {
+                files.push(path);
+            }
+        }
+    }
+    files
+}
+
+// the path of an href that points to a file, rather than into the document, at inline
+// data or at the network
+fn local_path(href: &str) -> Option<&str> {
+    let href = href.trim();
+    if let Some(path) = href.strip_prefix("file://") {
+        return Some(path);
+    }
+    let remote = href.contains("://") || href.starts_with("data:");
+    (!href.is_empty() && !href.starts_with('#') && !remote).then_some(href)
+}
+
+// the href pseudo-attribute of <?xml-stylesheet type="text/css" href="style.css"?>
+fn stylesheet_href(value: &str) -> Option<&str> {
+    let start = value.find("href=")? + "href=".len();
+    let quote = value[start..].chars().next()?;
+    let rest = &value[start + quote.len_utf8()..];
+    rest.find(quote).map(|end| &rest[..end])
+}
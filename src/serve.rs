@@ -0,0 +1,236 @@
+//! `svgview serve`: a preview of an SVG for browsers, rendered again whenever the file is
+//! written, for people who do not have svgview.
+//!
+//! `/` is a page showing the render that swaps in the new one after a change,
+//! `/render.png` the render, `/source.svg` the SVG's markup, and `/version` a line with a
+//! number that grows with each change, followed by why the file is not shown, if it is not.
+//!
+//! Each connection is answered on a thread of its own, from the latest render kept behind
+//! a lock, while the main thread renders the file again after a change, since parsed SVGs
+//! cannot be shared with other threads.
+
+use crate::cli::ServeArgs;
+use crate::document::{read_svg, Document};
+use crate::error::Failure;
+use crate::fonts::Fonts;
+use crate::watch::FileWatcher;
+use anyhow::{Context, Result};
+use log::warn;
+use std::io::{BufRead, BufReader, Write};
+use std::net::{TcpListener, TcpStream};
+use std::path::PathBuf;
+use std::sync::mpsc::channel;
+use std::sync::{Arc, Mutex};
+use std::thread;
+use std::time::Duration;
+
+// how long a client may take to send its request, so one that never does cannot hold up
+// the others
+const CLIENT_TIMEOUT: Duration = Duration::from_secs(5);
+// how often the page asks whether there is a new render, in milliseconds
+const PAGE_POLL: u32 = 1000;
+
+// what is served: the latest render, and what it was rendered from
+struct Shown {
+    png: Vec<u8>,
+    source: Vec<u8>,
+    version: u64,
+    // why the file on disk is not shown
+    error: Option<String>,
+}
+
+// the document as on disk, and what of it is served
+struct Preview {
+    path: PathBuf,
+    document: Document,
+    shown: Arc<Mutex<Shown>>,
+}
+
+impl Preview {
+    fn render(&self, args: &ServeArgs) -> Result<Vec<u8>> {
+        let fit_to = crate::export_fit_to(args.width, args.height, args.zoom);
+        let pixmap = crate::rasterize_still(&self.document.tree, fit_to, args.background, None)?;
+        Ok(pixmap.encode_png()?)
+    }
+
+    // reads the file again, keeping the last good render if it is not a valid SVG
+    fn reload(&mut self, args: &ServeArgs, fonts: &Fonts) {
+        let loaded = read_svg(&self.path)
+            .with_context(|| Failure::Input(format!("could not read {}", self.path.display())))
+            .and_then(|source| self.document.set_source(source, fonts))
+            .and_then(|()| self.render(args));
+        // rendered before taking the lock, so requests are not held up by it
+        let mut shown = self.shown.lock().unwrap();
+        match loaded {
+            Ok(png) => {
+                shown.png = png;
+                shown.source = self.document.source.clone();
+                shown.error = None;
+            }
+            Err(e) => shown.error = Some(format!("{:#}", e)),
+        }
+        shown.version += 1;
+    }
+}
+
+pub fn run(args: &ServeArgs, fonts: &Fonts, poll: Option<Duration>) -> Result<()> {
+    let path = std::fs::canonicalize(&args.input)
+        .with_context(|| Failure::Input(format!("could not open {}", args.input.display())))?;
+    let document = Document::from_file(&path, fonts)?;
+    let shown = Shown {
+        png: vec![],
+        source: document.source.clone(),
+        version: 0,
+        error: None,
+    };
+    let mut preview = Preview {
+        path,
+        document,
+        shown: Arc::new(Mutex::new(shown)),
+    };
+    preview.shown.lock().unwrap().png = preview.render(args)?;
+
+    let (changes, changed) = channel();
+    let watcher = FileWatcher::file(&preview.path, poll, move || changes.send(()).is_ok())
+        .context("could not start the file system watcher")?;
+
+    let listener = TcpListener::bind((args.address.as_str(), args.port))
+        .with_context(|| format!("could not listen on {}:{}", args.address, args.port))?;
+    eprintln!(
+        "Serving {} at http://{}/",
+        preview.path.display(),
+        listener.local_addr()?
+    );
+    let name: Arc<str> = preview
+        .path
+        .file_name()
+        .map_or("SVG".into(), |name| escape_html(&name.to_string_lossy()))
+        .into();
+    let shown = preview.shown.clone();
+    thread::spawn(move || {
+        for stream in listener.incoming() {
+            let stream = match stream {
+                Ok(stream) => stream,
+                Err(e) => {
+                    warn!("Could not accept a connection: {}", e);
+                    continue;
+                }
+            };
+            let (shown, name) = (shown.clone(), name.clone());
+            thread::spawn(move || {
+                if let Err(e) = respond(stream, &shown, &name) {
+                    warn!("Could not answer a request: {}", e);
+                }
+            });
+        }
+    });
+
+    for () in changed {
+        watcher.clear_pending();
+        preview.reload(args, fonts);
+    }
+    Ok(())
+}
+
+// `name` is the file's name, escaped for HTML
+fn respond(mut stream: TcpStream, shown: &Mutex<Shown>, name: &str) -> std::io::Result<()> {
+    stream.set_read_timeout(Some(CLIENT_TIMEOUT))?;
+    stream.set_write_timeout(Some(CLIENT_TIMEOUT))?;
+    let mut reader = BufReader::new(&stream);
+    let mut request = String::new();
+    reader.read_line(&mut request)?;
+    // the headers say nothing the answers depend on
+    let mut header = String::new();
+    while reader.read_line(&mut header)? > 2 {
+        header.clear();
+    }
+
+    let mut words = request.split_whitespace();
+    let (method, target) = (
+        words.next().unwrap_or_default(),
+        words.next().unwrap_or("/"),
+    );
+    let path = target.split('?').next().unwrap_or_default();
+    let shown_now = shown.lock().unwrap();
+    let (status, content_type, body) = match (method, path) {
+        ("GET", "/") => (
+            "200 OK",
+            "text/html; charset=utf-8",
+            page(name, shown_now.version).into_bytes(),
+        ),
+        ("GET", "/render.png") => ("200 OK", "image/png", shown_now.png.clone()),
+        ("GET", "/source.svg") => ("200 OK", "image/svg+xml", shown_now.source.clone()),
+        ("GET", "/version") => {
+            let error = shown_now.error.as_deref().unwrap_or_default();
+            let text = format!("{}\n{}", shown_now.version, error);
+            ("200 OK", "text/plain; charset=utf-8", text.into_bytes())
+        }
+        ("GET", _) => ("404 Not Found", "text/plain", b"Not found\n".to_vec()),
+        _ => (
+            "405 Method Not Allowed",
+            "text/plain",
+            b"Only GET is supported\n".to_vec(),
+        ),
+    };
+    // not held while writing, so a slow client does not hold up the reload
+    drop(shown_now);
+    write!(
+        stream,
+        "HTTP/1.1 {}\r\nContent-Type: {}\r\nContent-Length: {}\r\nCache-Control: no-store\r\n\
+         Connection: close\r\n\r\n",
+        status,
+        content_type,
+        body.len()
+    )?;
+    stream.write_all(&body)?;
+    stream.flush()
+}
+
+// shows the render over a checkerboard, and asks for the version every `PAGE_POLL` to
+// load the new render after a change
+fn page(name: &str, version: u64) -> String {
+    format!(
+        r#"<!DOCTYPE html>
+<html>
+<head>
+<meta charset="utf-8">
+<title>{name} - svgview</title>
+<style>
+body {{ margin: 0; background: #e0e0e0; font-family: sans-serif; text-align: center; }}
+img {{ display: block; margin: 1em auto; max-width: calc(100% - 2em);
+  background: repeating-conic-gradient(#fff 0 25%, #ccc 0 50%) 0 0 / 16px 16px; }}
+#error {{ color: #b00000; white-space: pre-wrap; }}
+</style>
+</head>
+<body>
+<p id="error"></p>
+<img id="render" src="/render.png?v={version}" alt="{name}">
+<p><a href="/source.svg">{name}</a></p>
+<script>
+let version = "{version}";
+setInterval(async () => {{
+  try {{
+    const [current, ...error] = (await (await fetch("/version")).text()).split("\n");
+    document.getElementById("error").textContent = error.join("\n");
+    if (current !== version) {{
+      version = current;
+      document.getElementById("render").src = "/render.png?v=" + version;
+    }}
+  }} catch (e) {{}}
+}}, {poll});
+</script>
+</body>
+</html>
+"#,
+        name = name,
+        version = version,
+        poll = PAGE_POLL
+    )
+}
+
+fn escape_html(text: &str) -> String {
+    text.replace('&', "&amp;")
+        .replace('<', "&lt;")
+        .replace('>', "&gt;")
+        .replace('"', "&quot;")
+}
@@ -0,0 +1,178 @@
+//! Splitting a stream of SVG documents written one after another, e.g. by a plotting
+//! script piping frames into the viewer.
+//!
+//! A document ends at a NUL byte or where its outermost `<svg>` element ends.
+
+use std::io::{self, BufRead};
+
+pub struct Documents<R> {
+    reader: R,
+    buffer: Vec<u8>,
+    // how far `buffer` has been searched for the end of the document, and the number
+    // of <svg> elements open at that point
+    scanned: usize,
+    depth: usize,
+}
+
+impl<R: BufRead> Documents<R> {
+    pub fn new(reader: R) -> Self {
+        Self {
+            reader,
+            buffer: vec![],
+            scanned: 0,
+            depth: 0,
+        }
+    }
+
+    // length of the document at the start of the buffer and of its delimiter, if complete
+    fn find_end(&mut self) -> Option<(usize, usize)> {
+        while self.scanned < self.buffer.len() {
+            let rest = &self.buffer[self.scanned..];
+            if rest[0] == 0 {
+                return Some((self.scanned, 1));
+            }
+            if rest.starts_with(b"</svg") {
+                // wait for the rest of the closing tag
+                let close = rest.iter().position(|&b| b == b'>')?;
+                self.depth = self.depth.saturating_sub(1);
+                if self.depth == 0 {
+                    return Some((self.scanned + close + 1, 0));
+                }
+                self.scanned += close + 1;
+                continue;
+            }
+            if rest.starts_with(b"<svg") {
+                match rest.get(4) {
+                    None => return None,
+                    Some(b) if b.is_ascii_whitespace() || *b == b'>' || *b == b'/' => {
+                        // wait for the rest of the opening tag
+                        let close = rest.iter().position(|&b| b == b'>')?;
+                        let self_closing = rest[close - 1] == b'/';
+                        if self_closing && self.depth == 0 {
+                            return Some((self.scanned + close + 1, 0));
+                        }
+                        if !self_closing {
+                            self.depth += 1;
+                        }
+                        self.scanned += close + 1;
+                        continue;
+                    }
+                    _ => {}
+                }
+            } else if rest[0] == b'<' && rest.len() < 5 {
+                // could still become "<svg" or "</svg"
+                return None;
+            }
+            self.scanned += 1;
+        }
+        None
+    }
+
+    // removes the document and its delimiter from the buffer, without the whitespace
+    // separating it from the previous one, which would come before an XML declaration
+    fn take(&mut self, length: usize, delimiter: usize) -> Vec<u8> {
+        let mut document: Vec<u8> = self.buffer.drain(..length + delimiter).collect();
+        document.truncate(length);
+        let start = document
+            .iter()
+            .position(|b| !b.is_ascii_whitespace())
+            .unwrap_or(length);
+        document.drain(..start);
+        self.scanned = 0;
+        self.depth = 0;
+        document
+    }
+}
+
+impl<R: BufRead> Iterator for Documents<R> {
+    type Item = io::Result<Vec<u8>>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        loop {
+            if let Some((length, delimiter)) = self.find_end() {
+                let document = self.take(length, delimiter);
+                if document.is_empty() {
+                    continue;
+                }
+                return Some(Ok(document));
+            }
+            let chunk = match self.reader.fill_buf() {
+                Ok(chunk) => chunk,
+                Err(e) if e.kind() == io::ErrorKind::Interrupted => continue,
+                Err(e) => return Some(Err(e)),
+            };
+            if chunk.is_empty() {
+                // end of input: whatever is left is the last document
+                let length = self.buffer.len();
+                let document = self.take(length, 0);
+                return (!document.is_empty()).then_some(Ok(document));
+            }
+            let read = chunk.len();
+            self.buffer.extend_from_slice(chunk);
+            self.reader.consume(read);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::io::BufReader;
+
+    // the documents of `input`, read `chunk` bytes at a time
+    fn split(input: &str, chunk: usize) -> Vec<String> {
+        let reader = BufReader::with_capacity(chunk, input.as_bytes());
+        Documents::new(reader)
+            .map(|document| String::from_utf8(document.unwrap()).unwrap())
+            .collect()
+    }
+
+    #[test]
+    fn nested_svg_elements_stay_in_their_document() {
+        let input = "<svg><svg><rect/></svg><svg/></svg><svg></svg>";
+        assert_eq!(
+            split(input, 64),
+            ["<svg><svg><rect/></svg><svg/></svg>", "<svg></svg>"]
+        );
+    }
+
+    #[test]
+    fn a_self_closing_root_is_a_document() {
+        let input = "<svg width=\"1\"/><svg xmlns=\"x\"></svg>";
+        assert_eq!(
+            split(input, 64),
+            ["<svg width=\"1\"/>", "<svg xmlns=\"x\"></svg>"]
+        );
+    }
+
+    #[test]
+    fn nul_bytes_end_documents() {
+        let input = "<svg>a\0<svg>b</svg>\0\0<svg>c";
+        assert_eq!(split(input, 64), ["<svg>a", "<svg>b</svg>", "<svg>c"]);
+    }
+
+    #[test]
+    fn tags_split_across_reads_are_found() {
+        let input = "<svg><svgz/></svg ><svg\n/>";
+        for chunk in 1..8 {
+            assert_eq!(
+                split(input, chunk),
+                ["<svg><svgz/></svg >", "<svg\n/>"],
+                "{}",
+                chunk
+            );
+        }
+    }
+
+    #[test]
+    fn whitespace_and_xml_declarations_go_with_the_next_document() {
+        let input = "<?xml version=\"1.0\"?>\n<svg/>\n<?xml version=\"1.0\"?><svg></svg>\n\n";
+        assert_eq!(
+            split(input, 5),
+            [
+                "<?xml version=\"1.0\"?>\n<svg/>",
+                "<?xml version=\"1.0\"?><svg></svg>"
+            ]
+        );
+    }
+}
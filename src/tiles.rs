@@ -0,0 +1,118 @@
+//! Keeping renders of huge documents in tiles, so that panning only renders the tiles that
+//! come into view, and memory stays bounded however far the document is zoomed in.
+//!
+//! Tiles are squares of the window's pixel grid at one zoom, counted from a corner that
+//! moves with the document, so the same tiles are found again after panning by any number
+//! of pixels.
+
+use std::collections::HashMap;
+use std::ops::Range;
+use tiny_skia::{Pixmap, Rect, Transform};
+
+/// The width and height of a tile in pixels.
+pub const SIZE: u32 = 256;
+
+// 64 MB of tiles
+const MAX_TILES: usize = 256;
+
+/// The zoom a tile was rendered at: the scale, rotation and subpixel position of the
+/// document in the window, for one version of the document.
+#[derive(Clone, Copy, PartialEq, Eq, Hash, Debug)]
+pub struct Zoom {
+    pub document: u64,
+    linear: [u32; 4],
+    // in sixteenths of a pixel
+    fraction: (u32, u32),
+}
+
+/// The tiles covering a document in the window.
+#[derive(Clone, Debug)]
+pub struct Grid {
+    pub zoom: Zoom,
+    // the window position of the tile (0, 0)
+    offset: (i32, i32),
+    columns: Range<i32>,
+    rows: Range<i32>,
+}
+
+impl Grid {
+    /// The grid for version `document` of a document drawn with the user transform `ts`,
+    /// covering the part of `area` within a window of the given size.
+    pub fn new(document: u64, ts: Transform, area: Rect, width: u32, height: u32) -> Self {
+        let (x, y) = (ts.tx.floor(), ts.ty.floor());
+        let sixteenths = |fraction: f32| ((fraction * 16.0).round() as u32).min(15);
+        let zoom = Zoom {
+            document,
+            linear: [
+                ts.sx.to_bits(),
+                ts.kx.to_bits(),
+                ts.ky.to_bits(),
+                ts.sy.to_bits(),
+            ],
+            fraction: (sixteenths(ts.tx - x), sixteenths(ts.ty - y)),
+        };
+        let span = |start: f32, end: f32, offset: f32, size: u32| {
+            let (start, end) = (start.max(0.0), end.min(size as f32));
+            let first = ((start - offset) / SIZE as f32).floor() as i32;
+            let last = ((end - offset) / SIZE as f32).ceil() as i32;
+            first..last.max(first)
+        };
+        Self {
+            zoom,
+            offset: (x as i32, y as i32),
+            columns: span(area.left(), area.right(), x, width),
+            rows: span(area.top(), area.bottom(), y, height),
+        }
+    }
+
+    /// The tiles in the window, by column and row.
+    pub fn visible(&self) -> impl Iterator<Item = (i32, i32)> + '_ {
+        self.rows
+            .clone()
+            .flat_map(move |row| self.columns.clone().map(move |column| (column, row)))
+    }
+
+    /// Where the top left corner of a tile is in the window.
+    pub fn origin(&self, (column, row): (i32, i32)) -> (i32, i32) {
+        (
+            self.offset.0 + column * SIZE as i32,
+            self.offset.1 + row * SIZE as i32,
+        )
+    }
+}
+
+/// The most recently used tiles.
+#[derive(Default)]
+pub struct Cache {
+    tiles: HashMap<(Zoom, (i32, i32)), (Pixmap, u64)>,
+    // counts the uses, to find the tile unused for the longest
+    clock: u64,
+}
+
+impl Cache {
+    pub fn get(&mut self, zoom: Zoom, tile: (i32, i32)) -> Option<&Pixmap> {
+        self.clock += 1;
+        let (pixmap, used) = self.tiles.get_mut(&(zoom, tile))?;
+        *used = self.clock;
+        Some(pixmap)
+    }
+
+    pub fn contains(&self, zoom: Zoom, tile: (i32, i32)) -> bool {
+        self.tiles.contains_key(&(zoom, tile))
+    }
+
+    pub fn insert(&mut self, zoom: Zoom, tile: (i32, i32), pixmap: Pixmap) {
+        if self.tiles.len() >= MAX_TILES {
+            let oldest = self.tiles.iter().min_by_key(|(_, (_, used))| *used);
+            if let Some(&key) = oldest.map(|(key, _)| key) {
+                self.tiles.remove(&key);
+            }
+        }
+        self.clock += 1;
+        self.tiles.insert((zoom, tile), (pixmap, self.clock));
+    }
+
+    pub fn clear(&mut self) {
+        self.tiles.clear();
+    }
+}
@@ -0,0 +1,79 @@
+//! `--tray`: an icon in the system tray, for a viewer kept open next to an editor for a
+//! long session. Clicking it shows the window, hidden or not, and its menu reopens the last
+//! file, pauses watching or quits.
+//!
+//! The icon is a StatusNotifierItem on D-Bus, as KDE, most other Linux desktops and their
+//! tray extensions show them; ksni serves it on a thread of its own.
+
+use crate::UserEvent;
+use ksni::blocking::{Handle, TrayMethods};
+use ksni::menu::{CheckmarkItem, StandardItem};
+use ksni::MenuItem;
+use winit::event_loop::EventLoopProxy;
+
+/// What was chosen on the icon or in its menu.
+#[derive(Clone, Copy, Debug)]
+pub enum Choice {
+    Show,
+    ReopenLast,
+    PauseWatching,
+    Quit,
+}
+
+pub struct Tray {
+    evp: EventLoopProxy<UserEvent>,
+    /// Whether watching is paused, for the checkmark of the menu.
+    pub paused: bool,
+}
+
+impl Tray {
+    fn choose(&self, choice: Choice) {
+        let _ = self.evp.send_event(UserEvent::Tray(choice));
+    }
+}
+
+impl ksni::Tray for Tray {
+    fn id(&self) -> String {
+        env!("CARGO_PKG_NAME").into()
+    }
+
+    fn title(&self) -> String {
+        "svgview".into()
+    }
+
+    fn icon_name(&self) -> String {
+        "image-x-generic".into()
+    }
+
+    fn activate(&mut self, _x: i32, _y: i32) {
+        self.choose(Choice::Show);
+    }
+
+    fn menu(&self) -> Vec<MenuItem<Self>> {
+        let item = |label: &str, choice| {
+            StandardItem {
+                label: label.into(),
+                activate: Box::new(move |tray: &mut Self| tray.choose(choice)),
+                ..Default::default()
+            }
+            .into()
+        };
+        vec![
+            item("Reopen last file", Choice::ReopenLast),
+            CheckmarkItem {
+                label: "Pause watching".into(),
+                checked: self.paused,
+                activate: Box::new(|tray: &mut Self| tray.choose(Choice::PauseWatching)),
+                ..Default::default()
+            }
+            .into(),
+            MenuItem::Separator,
+            item("Quit", Choice::Quit),
+        ]
+    }
+}
+
+/// Shows the icon, sending what is chosen on it to the event loop.
+pub fn spawn(evp: EventLoopProxy<UserEvent>) -> Result<Handle<Tray>, ksni::Error> {
+    Tray { evp, paused: false }.spawn()
+}
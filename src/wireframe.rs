@@ -0,0 +1,48 @@
+//! Drawing only the outlines of a parsed document, to see overlapping geometry and
+//! elements hidden by fills, masks, clipping, opacity or `visibility`.
+
+use usvg::{Color, Node, NodeExt, NodeKind, Opacity, Paint, Stroke, StrokeWidth, Tree, Visibility};
+
+// visible on white, black and gray backgrounds alike
+const OUTLINE_COLOR: Color = Color {
+    red: 0x00,
+    green: 0x80,
+    blue: 0xff,
+};
+
+/// Replaces the paint of every path by a plain stroke `1 / pixels_per_unit` user units
+/// wide, so one pixel at that zoom, and drops images and group effects. Applying it again
+/// only updates the widths.
+pub fn apply(tree: &Tree, pixels_per_unit: f64) {
+    let images: Vec<Node> = tree
+        .root()
+        .descendants()
+        .filter(|node| matches!(*node.borrow(), NodeKind::Image(_)))
+        .collect();
+    for mut image in images {
+        image.detach();
+    }
+    for mut node in tree.root().descendants() {
+        // the scale of the node's own coordinates relative to the user units
+        let ts = node.abs_transform();
+        let scale = (ts.a * ts.d - ts.b * ts.c).abs().sqrt() * pixels_per_unit;
+        match *node.borrow_mut() {
+            NodeKind::Path(ref mut path) => {
+                path.fill = None;
+                path.visibility = Visibility::Visible;
+                path.stroke = Some(Stroke {
+                    paint: Paint::Color(OUTLINE_COLOR),
+                    width: StrokeWidth::new(if scale > 0.0 { 1.0 / scale } else { 1.0 }),
+                    ..Stroke::default()
+                });
+            }
+            NodeKind::Group(ref mut group) => {
+                group.opacity = Opacity::default();
+                group.clip_path = None;
+                group.mask = None;
+                group.filter.clear();
+            }
+            _ => {}
+        }
+    }
+}
@@ -0,0 +1,329 @@
+//! Rendering the SVG on a thread of its own, so that the window keeps responding while a
+//! large document rasterizes. Only the latest job is rendered: one given while another is
+//! waiting replaces it, and a render that was superseded while drawing is dropped.
+//!
+//! A render cannot be interrupted, so one that runs for longer than the timeout, e.g. of
+//! a document with a huge blur, is abandoned once another job comes: its thread is left
+//! to finish on its own and drop the result, and a new thread renders the job. The version
+//! of the document it was drawing is not rendered again, so that a document that always
+//! hangs does not leave one more thread behind for each job; the next version is.
+
+use crate::pyramid;
+use crate::render::{self, Backend, Canvas, Renderer};
+use crate::tiles::{self, Grid};
+use crate::UserEvent;
+use log::warn;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::{Arc, Condvar, Mutex};
+use std::thread;
+use std::time::{Duration, Instant};
+use tiny_skia::Pixmap;
+use usvg::{Options, Tree};
+use winit::event_loop::EventLoopProxy;
+
+/// A render of the document into a pixmap of the given size.
+pub struct Job {
+    /// Sent back with the render, to tell which job it is for.
+    pub generation: u64,
+    /// The document as usvg writes it, when it changed since the previous job.
+    pub document: Option<String>,
+    /// Counts the changes of the document, sent back with its preview pyramid.
+    pub version: u64,
+    pub canvas: Canvas,
+    pub width: u32,
+    pub height: u32,
+    /// Renders only these tiles of the grid instead, each sent as soon as it is done.
+    pub tiles: Option<(Grid, Vec<(i32, i32)>)>,
+}
+
+// how long a render has to take for the document to get a preview pyramid
+const SLOW_RENDER: Duration = Duration::from_millis(100);
+
+// the job waiting to be rendered, shared with one thread
+#[derive(Default)]
+struct Mailbox {
+    job: Mutex<Option<Job>>,
+    ready: Condvar,
+    // when the thread started drawing, and the version of the document it draws, while it is
+    drawing: Mutex<Option<(Instant, u64)>>,
+    // set when the thread was replaced, for it to stop once its draw is done
+    abandoned: AtomicBool,
+}
+
+impl Mailbox {
+    fn draw<T>(&self, version: u64, draw: impl FnOnce() -> T) -> T {
+        *self.drawing.lock().unwrap() = Some((Instant::now(), version));
+        let drawn = draw();
+        *self.drawing.lock().unwrap() = None;
+        drawn
+    }
+
+    fn abandoned(&self) -> bool {
+        self.abandoned.load(Ordering::SeqCst)
+    }
+}
+
+// gives up on renders that take too long, and on the versions they were of
+struct Watchdog {
+    timeout: Duration,
+    timed_out: Option<u64>,
+}
+
+impl Watchdog {
+    // whether to abandon the thread drawing what `drawing` says, remembering its version
+    fn check(&mut self, drawing: Option<(Instant, u64)>) -> bool {
+        match drawing {
+            Some((started, version)) if started.elapsed() > self.timeout => {
+                self.timed_out = Some(version);
+                true
+            }
+            _ => false,
+        }
+    }
+
+    fn refuses(&self, version: u64) -> bool {
+        self.timed_out == Some(version)
+    }
+}
+
+pub struct Worker {
+    mailbox: Arc<Mailbox>,
+    evp: EventLoopProxy<UserEvent>,
+    backend: Backend,
+    watchdog: Watchdog,
+    // the latest document given, for a new thread to start with
+    document: Option<String>,
+}
+
+impl Worker {
+    /// Starts the thread, which sends `UserEvent::Rendered` with each finished render.
+    /// Renders that take longer than `timeout` are abandoned once another job is given.
+    pub fn start(evp: EventLoopProxy<UserEvent>, backend: Backend, timeout: Duration) -> Self {
+        let mut worker = Self {
+            mailbox: Arc::default(),
+            evp,
+            backend,
+            watchdog: Watchdog {
+                timeout,
+                timed_out: None,
+            },
+            document: None,
+        };
+        worker.spawn();
+        worker
+    }
+
+    fn spawn(&mut self) {
+        let mailbox = Arc::new(Mailbox::default());
+        self.mailbox = mailbox.clone();
+        let (evp, backend) = (self.evp.clone(), self.backend);
+        thread::spawn(move || run(&mailbox, evp, Renderer::new(backend)));
+    }
+
+    /// Renders `job` once the current render is done, instead of any job still waiting.
+    /// `false` if it is not rendered, as a render of its version of the document took
+    /// longer than the timeout.
+    pub fn submit(&mut self, mut job: Job) -> bool {
+        if let Some(markup) = &job.document {
+            self.document = Some(markup.clone());
+        }
+        let drawing = *self.mailbox.drawing.lock().unwrap();
+        if self.watchdog.check(drawing) {
+            warn!(
+                "Abandoned a render that took longer than {:?}; the last frame stays",
+                self.watchdog.timeout
+            );
+            self.mailbox.abandoned.store(true, Ordering::SeqCst);
+            self.spawn();
+            // the new thread has not seen the document yet
+            job.document = self.document.clone();
+        }
+        if self.watchdog.refuses(job.version) {
+            return false;
+        }
+        let mut pending = self.mailbox.job.lock().unwrap();
+        // the document of a superseded job is still needed when this one has none
+        if let Some(superseded) = pending.take() {
+            job.document = job.document.or(superseded.document);
+        }
+        *pending = Some(job);
+        self.mailbox.ready.notify_one();
+        true
+    }
+
+    pub fn timeout(&self) -> Duration {
+        self.watchdog.timeout
+    }
+}
+
+// the document the worker renders
+struct Document {
+    tree: Tree,
+    version: u64,
+    // whether its renders are slow enough to render the preview pyramid for
+    slow: bool,
+    levels: usize,
+}
+
+impl Document {
+    fn wants_level(&self) -> bool {
+        self.slow && self.levels < pyramid::SIZES.len()
+    }
+}
+
+fn run(mailbox: &Mailbox, evp: EventLoopProxy<UserEvent>, mut renderer: Renderer) {
+    let mut document: Option<Document> = None;
+    loop {
+        let job = {
+            let mut pending = mailbox.job.lock().unwrap();
+            while pending.is_none() && !document.as_ref().is_some_and(Document::wants_level) {
+                pending = mailbox.ready.wait(pending).unwrap();
+            }
+            pending.take()
+        };
+        let job = match (job, &mut document) {
+            (Some(job), _) => job,
+            // nothing else to do
+            (None, Some(document)) => {
+                let size = pyramid::SIZES[document.levels];
+                document.levels += 1;
+                let level =
+                    mailbox.draw(document.version, || pyramid::render(&document.tree, size));
+                let level = match level {
+                    Some(level) => level,
+                    None if mailbox.abandoned() => break,
+                    None => continue,
+                };
+                if mailbox.abandoned() {
+                    break;
+                }
+                if evp
+                    .send_event(UserEvent::Level(document.version, level))
+                    .is_err()
+                {
+                    break;
+                }
+                continue;
+            }
+            (None, None) => continue,
+        };
+        if let Some(markup) = &job.document {
+            // the text is already converted to paths, so no fonts are needed
+            document = match Tree::from_str(markup, &Options::default().to_ref()) {
+                Ok(tree) => Some(Document {
+                    tree,
+                    version: job.version,
+                    slow: false,
+                    levels: 0,
+                }),
+                Err(e) => {
+                    warn!("Could not pass the SVG to the renderer: {}", e);
+                    None
+                }
+            };
+        }
+        let document = match &mut document {
+            Some(document) => document,
+            None => continue,
+        };
+        let start = Instant::now();
+        let tree = &document.tree;
+        if let Some((grid, wanted)) = &job.tiles {
+            let sent = render_tiles(mailbox, &evp, document, &job, grid, wanted);
+            document.slow |= start.elapsed() >= SLOW_RENDER;
+            if sent.is_err() {
+                break;
+            }
+            continue;
+        }
+        let mut pixmap = match Pixmap::new(job.width, job.height) {
+            Some(pixmap) => pixmap,
+            None => {
+                warn!(
+                    "Could not allocate memory for a {}x{} render",
+                    job.width, job.height
+                );
+                continue;
+            }
+        };
+        let drawn = mailbox.draw(document.version, || {
+            renderer.draw(tree, &job.canvas, &mut pixmap)
+        });
+        if mailbox.abandoned() {
+            break;
+        }
+        if drawn.is_none() {
+            warn!("Could not rasterize the SVG");
+        }
+        document.slow |= start.elapsed() >= SLOW_RENDER;
+        // a newer job makes this render useless
+        if mailbox.job.lock().unwrap().is_some() {
+            continue;
+        }
+        if evp
+            .send_event(UserEvent::Rendered(job.generation, pixmap))
+            .is_err()
+        {
+            // the event loop is gone
+            break;
+        }
+    }
+}
+
+// fails once the event loop is gone or the thread was abandoned
+fn render_tiles(
+    mailbox: &Mailbox,
+    evp: &EventLoopProxy<UserEvent>,
+    document: &Document,
+    job: &Job,
+    grid: &Grid,
+    wanted: &[(i32, i32)],
+) -> Result<(), ()> {
+    for &tile in wanted {
+        // the tiles that are done are kept, but the rest may not be needed anymore
+        if mailbox.job.lock().unwrap().is_some() {
+            return Ok(());
+        }
+        let mut pixmap = match Pixmap::new(tiles::SIZE, tiles::SIZE) {
+            Some(pixmap) => pixmap,
+            None => return Ok(()),
+        };
+        let size = (job.width, job.height);
+        let origin = grid.origin(tile);
+        let drawn = mailbox.draw(document.version, || {
+            render::draw_tile(&document.tree, &job.canvas, size, origin, &mut pixmap)
+        });
+        if mailbox.abandoned() {
+            return Err(());
+        }
+        if drawn.is_none() {
+            warn!("Could not rasterize the SVG");
+            return Ok(());
+        }
+        evp.send_event(UserEvent::Tile(grid.zoom, tile, pixmap))
+            .map_err(|_| ())?;
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn timed_out_versions_are_not_rendered_again() {
+        let mut watchdog = Watchdog {
+            timeout: Duration::from_secs(1),
+            timed_out: None,
+        };
+        assert!(!watchdog.check(Some((Instant::now(), 3))));
+        assert!(!watchdog.refuses(3));
+        let hung = Instant::now() - Duration::from_secs(2);
+        assert!(watchdog.check(Some((hung, 3))));
+        assert!(watchdog.refuses(3));
+        // once the thread is replaced, nothing is drawing, and later versions render
+        assert!(!watchdog.check(None));
+        assert!(watchdog.refuses(3));
+        assert!(!watchdog.refuses(4));
+    }
+}